@@ -0,0 +1,405 @@
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "streaming")]
+type BoxStream =
+    std::pin::Pin<Box<dyn futures_util::Stream<Item = std::io::Result<Vec<u8>>> + Send>>;
+
+/// The body of a [`crate::Request`].
+///
+/// Usually just bytes, but it can also come from an [`std::io::Read`], a file (native only),
+/// or an async stream of chunks (with the `streaming` feature),
+/// so that large uploads don't have to be loaded into memory first.
+///
+/// ```
+/// use std::io::Read as _;
+///
+/// let body = ehttp::Body::from_reader(std::io::repeat(b'x').take(1_000_000));
+/// let request = ehttp::Request::post("https://www.example.com/upload", vec![]).with_body(body);
+/// ```
+///
+/// On native, a body of known size is sent with a `Content-Length` header,
+/// and any other body with chunked transfer encoding.
+///
+/// On web, a stream is sent as a `ReadableStream` if the browser supports it,
+/// and is otherwise read into memory first, like a reader always is.
+///
+/// A reader or stream can only be read once,
+/// so requests with such a body are never retried,
+/// and don't follow redirects that would send the body again (`307` and `308`).
+#[derive(Clone, Default)]
+pub struct Body(Kind);
+
+#[derive(Clone)]
+enum Kind {
+    Bytes(Vec<u8>),
+
+    Reader {
+        reader: Once<Box<dyn std::io::Read + Send>>,
+        length: Option<u64>,
+    },
+
+    #[cfg(not(target_arch = "wasm32"))]
+    File(std::path::PathBuf),
+
+    #[cfg(feature = "streaming")]
+    Stream(Once<BoxStream>),
+}
+
+impl Default for Kind {
+    fn default() -> Self {
+        Self::Bytes(vec![])
+    }
+}
+
+impl std::fmt::Debug for Body {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.0 {
+            Kind::Bytes(bytes) => write!(f, "Body::Bytes({} bytes)", bytes.len()),
+            Kind::Reader { length, .. } => match length {
+                Some(length) => write!(f, "Body::Reader({length} bytes)"),
+                None => f.write_str("Body::Reader"),
+            },
+            #[cfg(not(target_arch = "wasm32"))]
+            Kind::File(path) => write!(f, "Body::File({path:?})"),
+            #[cfg(feature = "streaming")]
+            Kind::Stream(_) => f.write_str("Body::Stream"),
+        }
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(Kind::Bytes(bytes))
+    }
+}
+
+impl From<&[u8]> for Body {
+    fn from(bytes: &[u8]) -> Self {
+        bytes.to_vec().into()
+    }
+}
+
+impl From<String> for Body {
+    fn from(text: String) -> Self {
+        text.into_bytes().into()
+    }
+}
+
+impl From<&str> for Body {
+    fn from(text: &str) -> Self {
+        text.as_bytes().into()
+    }
+}
+
+impl Body {
+    /// Read the body from the given reader, of unknown size.
+    ///
+    /// On native, it is sent with chunked transfer encoding.
+    pub fn from_reader(reader: impl std::io::Read + Send + 'static) -> Self {
+        Self(Kind::Reader {
+            reader: Once::new(Box::new(reader)),
+            length: None,
+        })
+    }
+
+    /// Read `length` bytes from the given reader.
+    ///
+    /// On native, it is sent with a `Content-Length` header,
+    /// so the request fails if the reader doesn't produce exactly that many bytes.
+    pub fn from_reader_with_length(
+        reader: impl std::io::Read + Send + 'static,
+        length: u64,
+    ) -> Self {
+        Self(Kind::Reader {
+            reader: Once::new(Box::new(reader)),
+            length: Some(length),
+        })
+    }
+
+    /// Read the body from the file at the given path when the request is sent.
+    ///
+    /// It is sent with a `Content-Length` header.
+    /// Only available when compiling for native.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_file(path: impl Into<std::path::PathBuf>) -> Self {
+        Self(Kind::File(path.into()))
+    }
+
+    /// Send the chunks of the given stream as they become available.
+    ///
+    /// On native, it is sent with chunked transfer encoding.
+    /// On web, it is sent as a `ReadableStream` if the browser supports it
+    /// (at the time of writing only Chromium does, and only over HTTP/2),
+    /// otherwise the whole stream is read into memory first.
+    #[cfg(feature = "streaming")]
+    pub fn from_stream(
+        stream: impl futures_util::Stream<Item = std::io::Result<Vec<u8>>> + Send + 'static,
+    ) -> Self {
+        Self(Kind::Stream(Once::new(Box::pin(stream))))
+    }
+
+    /// The bytes of the body, if it is in memory.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match &self.0 {
+            Kind::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// Is this an empty in-memory body?
+    ///
+    /// Readers, files and streams never count as empty, even if they turn out to be.
+    pub fn is_empty(&self) -> bool {
+        self.as_bytes().is_some_and(|bytes| bytes.is_empty())
+    }
+
+    /// The size of the body in bytes, if known up front.
+    pub fn content_length(&self) -> Option<u64> {
+        match &self.0 {
+            Kind::Bytes(bytes) => Some(bytes.len() as u64),
+            Kind::Reader { length, .. } => *length,
+            #[cfg(not(target_arch = "wasm32"))]
+            Kind::File(path) => std::fs::metadata(path).ok().map(|metadata| metadata.len()),
+            #[cfg(feature = "streaming")]
+            Kind::Stream(_) => None,
+        }
+    }
+
+    /// Can the body be sent again, e.g. when retrying or following a redirect?
+    pub(crate) fn can_be_resent(&self) -> bool {
+        match &self.0 {
+            Kind::Bytes(_) => true,
+            Kind::Reader { .. } => false,
+            #[cfg(not(target_arch = "wasm32"))]
+            Kind::File(_) => true,
+            #[cfg(feature = "streaming")]
+            Kind::Stream(_) => false,
+        }
+    }
+
+    /// Open the body for reading, and return its size (if known).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn reader(
+        &self,
+    ) -> crate::Result<(Box<dyn std::io::Read + Send + '_>, Option<u64>)> {
+        match &self.0 {
+            Kind::Bytes(bytes) => Ok((Box::new(bytes.as_slice()), Some(bytes.len() as u64))),
+            Kind::Reader { reader, length } => Ok((reader.take()?, *length)),
+            Kind::File(path) => {
+                let file = std::fs::File::open(path).map_err(|err| {
+                    crate::Error::Other(format!("Failed to open {}: {err}", path.display()))
+                })?;
+                let length = file.metadata().ok().map(|metadata| metadata.len());
+                Ok((Box::new(file), length))
+            }
+            #[cfg(feature = "streaming")]
+            Kind::Stream(stream) => Ok((Box::new(StreamReader::new(stream.take()?)), None)),
+        }
+    }
+
+    /// Is this a body that should be sent as a `ReadableStream`, if possible?
+    #[cfg(all(target_arch = "wasm32", feature = "streaming"))]
+    pub(crate) fn is_stream(&self) -> bool {
+        matches!(self.0, Kind::Stream(_))
+    }
+
+    /// Take the stream of a body created with [`Self::from_stream`].
+    #[cfg(all(target_arch = "wasm32", feature = "streaming"))]
+    pub(crate) fn take_stream(&self) -> crate::Result<BoxStream> {
+        match &self.0 {
+            Kind::Stream(stream) => stream.take(),
+            _ => Err(crate::Error::Other("The body is not a stream".to_owned())),
+        }
+    }
+
+    /// Read the whole body into memory.
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) async fn to_bytes(&self) -> crate::Result<std::borrow::Cow<'_, [u8]>> {
+        use std::borrow::Cow;
+
+        let read_error = |err: std::io::Error| {
+            crate::Error::Other(format!("Failed to read the request body: {err}"))
+        };
+        match &self.0 {
+            Kind::Bytes(bytes) => Ok(Cow::Borrowed(bytes)),
+            Kind::Reader { reader, .. } => {
+                use std::io::Read as _;
+
+                let mut bytes = vec![];
+                reader.take()?.read_to_end(&mut bytes).map_err(read_error)?;
+                Ok(Cow::Owned(bytes))
+            }
+            #[cfg(feature = "streaming")]
+            Kind::Stream(stream) => {
+                use futures_util::StreamExt as _;
+
+                let mut stream = stream.take()?;
+                let mut bytes = vec![];
+                while let Some(chunk) = stream.next().await {
+                    bytes.extend_from_slice(&chunk.map_err(read_error)?);
+                }
+                Ok(Cow::Owned(bytes))
+            }
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Something that can be taken out once, even from a clone.
+struct Once<T>(Arc<Mutex<Option<T>>>);
+
+impl<T> Clone for Once<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Once<T> {
+    fn new(value: T) -> Self {
+        Self(Arc::new(Mutex::new(Some(value))))
+    }
+
+    fn take(&self) -> crate::Result<T> {
+        self.0.lock().unwrap().take().ok_or_else(|| {
+            crate::Error::Other(
+                "The request body has already been sent, and can't be sent again".to_owned(),
+            )
+        })
+    }
+}
+
+/// Reads a stream, blocking the thread while waiting for the next chunk.
+#[cfg(all(not(target_arch = "wasm32"), feature = "streaming"))]
+struct StreamReader {
+    stream: BoxStream,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "streaming"))]
+impl StreamReader {
+    fn new(stream: BoxStream) -> Self {
+        Self {
+            stream,
+            chunk: vec![],
+            position: 0,
+        }
+    }
+}
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "streaming"))]
+impl std::io::Read for StreamReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        use futures_util::StreamExt as _;
+
+        while self.position == self.chunk.len() {
+            match block_on(self.stream.next()) {
+                Some(chunk) => {
+                    self.chunk = chunk?;
+                    self.position = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+
+        let n = buf.len().min(self.chunk.len() - self.position);
+        buf[..n].copy_from_slice(&self.chunk[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+/// Run a future to completion on the current thread.
+#[cfg(all(not(target_arch = "wasm32"), feature = "streaming"))]
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    use std::task::{Context, Poll};
+
+    struct ThreadWaker(std::thread::Thread);
+
+    impl std::task::Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Arc::new(ThreadWaker(std::thread::current())).into();
+    let mut context = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    loop {
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => std::thread::park(),
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    fn read(body: &Body) -> crate::Result<(Vec<u8>, Option<u64>)> {
+        let (mut reader, length) = body.reader()?;
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes).unwrap();
+        Ok((bytes, length))
+    }
+
+    #[test]
+    fn bytes() {
+        let body = Body::from("hello");
+        assert_eq!(body.as_bytes(), Some(&b"hello"[..]));
+        assert_eq!(body.content_length(), Some(5));
+        assert!(!body.is_empty());
+        assert!(Body::default().is_empty());
+        assert!(body.can_be_resent());
+        assert_eq!(read(&body).unwrap(), (b"hello".to_vec(), Some(5)));
+        assert_eq!(read(&body).unwrap(), (b"hello".to_vec(), Some(5)));
+    }
+
+    #[test]
+    fn reader() {
+        let body = Body::from_reader(&b"hello"[..]);
+        assert_eq!(body.as_bytes(), None);
+        assert_eq!(body.content_length(), None);
+        assert!(!body.is_empty());
+        assert!(!body.can_be_resent());
+        assert_eq!(read(&body).unwrap(), (b"hello".to_vec(), None));
+
+        // Only once, even from a clone:
+        let body = Body::from_reader_with_length(&b"hello"[..], 5);
+        let clone = body.clone();
+        assert_eq!(body.content_length(), Some(5));
+        assert_eq!(read(&clone).unwrap(), (b"hello".to_vec(), Some(5)));
+        assert!(matches!(read(&body), Err(crate::Error::Other(_))));
+    }
+
+    #[test]
+    fn file() {
+        let path = std::env::temp_dir().join(format!("ehttp-body-test-{}", std::process::id()));
+        std::fs::write(&path, "hello").unwrap();
+
+        let body = Body::from_file(&path);
+        assert_eq!(body.as_bytes(), None);
+        assert_eq!(body.content_length(), Some(5));
+        assert!(body.can_be_resent());
+        assert_eq!(read(&body).unwrap(), (b"hello".to_vec(), Some(5)));
+        // Opened again for every attempt:
+        assert_eq!(read(&body).unwrap(), (b"hello".to_vec(), Some(5)));
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(body.content_length(), None);
+        assert!(matches!(read(&body), Err(crate::Error::Other(_))));
+    }
+
+    #[cfg(feature = "streaming")]
+    #[test]
+    fn stream() {
+        let chunks = vec![Ok(b"hel".to_vec()), Ok(vec![]), Ok(b"lo".to_vec())];
+        let body = Body::from_stream(futures_util::stream::iter(chunks));
+        assert_eq!(body.content_length(), None);
+        assert!(!body.can_be_resent());
+        assert_eq!(read(&body).unwrap(), (b"hello".to_vec(), None));
+        assert!(read(&body).is_err());
+    }
+}
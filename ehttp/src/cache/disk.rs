@@ -0,0 +1,382 @@
+use std::{
+    io::Write as _,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use super::{Cache, CacheEntry};
+use crate::{Headers, Redirect, Response};
+
+/// A [`Cache`] that stores each entry as a file in a directory.
+///
+/// The directory is created when the first entry is stored.
+/// Nothing is ever evicted, so call [`Self::clear`] now and then if the directory grows too large.
+///
+/// Only available when compiling for native.
+///
+/// ```
+/// let client = ehttp::Client::new().with_cache(ehttp::cache::DiskCache::new("/tmp/my-app/http-cache"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct DiskCache {
+    dir: PathBuf,
+}
+
+/// The first line of every entry file.
+const MAGIC: &str = "ehttp-cache-v2";
+
+impl DiskCache {
+    /// Store the entries in the given directory.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The directory the entries are stored in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Remove all entries.
+    pub fn clear(&self) -> std::io::Result<()> {
+        match std::fs::remove_dir_all(&self.dir) {
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        // FNV-1a, because it is stable across Rust versions (unlike `DefaultHasher`).
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for byte in key.bytes() {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+        self.dir.join(format!("{hash:016x}.entry"))
+    }
+
+    fn write(&self, key: &str, entry: &CacheEntry) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+
+        let path = self.path(key);
+        // Write to a temporary file first, so that readers never see a half-written entry.
+        // Its name is unique, so that concurrent writes of the same entry don't mix:
+        static NEXT_TMP: AtomicU64 = AtomicU64::new(0);
+        let tmp_path = path.with_extension(format!(
+            "tmp{}-{}",
+            std::process::id(),
+            NEXT_TMP.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut file = std::fs::File::create(&tmp_path)?;
+        file.write_all(&encode(key, entry))?;
+        drop(file);
+        std::fs::rename(&tmp_path, path).inspect_err(|_| {
+            let _ = std::fs::remove_file(&tmp_path);
+        })
+    }
+}
+
+impl Cache for DiskCache {
+    fn get(&self, key: &str) -> Option<CacheEntry> {
+        let bytes = std::fs::read(self.path(key)).ok()?;
+        decode(key, &bytes)
+    }
+
+    fn put(&self, key: &str, entry: CacheEntry) {
+        if self.write(key, &entry).is_err() {
+            self.remove(key);
+        }
+    }
+
+    fn remove(&self, key: &str) {
+        let _ = std::fs::remove_file(self.path(key));
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// The format is line-based (header names and values can't contain newlines), followed by the body:
+//
+// ```
+// ehttp-cache-v2
+// <key>
+// <url>
+// <status> <ok> <request time> <response time>
+// <status text>
+// <number of response headers>
+// <name>: <value>
+// …
+// <number of vary headers>
+// <name>: <value>
+// …
+// <number of redirects>
+// <status> <url> <location>
+// …
+// <body length>
+// <body>
+// ```
+
+fn encode(key: &str, entry: &CacheEntry) -> Vec<u8> {
+    let CacheEntry {
+        response,
+        vary,
+        request_time,
+        response_time,
+    } = entry;
+
+    let mut text = String::new();
+    let mut line = |line: &str| {
+        text.push_str(line);
+        text.push('\n');
+    };
+    line(MAGIC);
+    line(key);
+    line(&response.url);
+    line(&format!(
+        "{} {} {} {}",
+        response.status,
+        u8::from(response.ok),
+        millis_since_epoch(*request_time),
+        millis_since_epoch(*response_time)
+    ));
+    line(&response.status_text);
+    for headers in [&response.headers, vary] {
+        line(&headers.headers.len().to_string());
+        for (name, value) in headers {
+            line(&format!("{name}: {value}"));
+        }
+    }
+    line(&response.redirects.len().to_string());
+    for redirect in &response.redirects {
+        line(&format!(
+            "{} {} {}",
+            redirect.status, redirect.url, redirect.location
+        ));
+    }
+    line(&response.bytes.len().to_string());
+
+    let mut bytes = text.into_bytes();
+    bytes.extend_from_slice(&response.bytes);
+    bytes
+}
+
+/// Returns `None` if the file is corrupt, or belongs to another key with the same hash.
+fn decode(key: &str, mut bytes: &[u8]) -> Option<CacheEntry> {
+    let mut line = || -> Option<String> {
+        let end = bytes.iter().position(|&b| b == b'\n')?;
+        let line = std::str::from_utf8(&bytes[..end]).ok()?.to_owned();
+        bytes = &bytes[end + 1..];
+        Some(line)
+    };
+
+    if line()? != MAGIC || line()? != key {
+        return None;
+    }
+    let url = line()?;
+    let status_line = line()?;
+    let mut status_parts = status_line.split(' ');
+    let status = status_parts.next()?.parse().ok()?;
+    let ok = status_parts.next()? == "1";
+    let mut time = || -> Option<SystemTime> {
+        UNIX_EPOCH.checked_add(Duration::from_millis(status_parts.next()?.parse().ok()?))
+    };
+    let request_time = time()?;
+    let response_time = time()?;
+    let status_text = line()?;
+
+    let mut read_headers = || -> Option<Headers> {
+        let count: usize = line()?.parse().ok()?;
+        let mut headers = Headers::default();
+        for _ in 0..count {
+            let header = line()?;
+            let (name, value) = header.split_once(": ")?;
+            headers.insert(name, value);
+        }
+        Some(headers)
+    };
+    let headers = read_headers()?;
+    let vary = read_headers()?;
+
+    let num_redirects: usize = line()?.parse().ok()?;
+    let mut redirects = vec![]; // Not `with_capacity`: the count may be corrupt
+    for _ in 0..num_redirects {
+        let redirect = line()?;
+        let mut parts = redirect.split(' ');
+        redirects.push(Redirect {
+            status: parts.next()?.parse().ok()?,
+            url: parts.next()?.to_owned(),
+            location: parts.next()?.to_owned(),
+        });
+    }
+
+    let body_len: usize = line()?.parse().ok()?;
+    if bytes.len() != body_len {
+        return None; // Truncated
+    }
+
+    Some(CacheEntry {
+        response: Response {
+            url,
+            ok,
+            status,
+            status_text,
+            headers,
+            redirects,
+            bytes: bytes.to_vec(),
+        },
+        vary,
+        request_time,
+        response_time,
+    })
+}
+
+fn millis_since_epoch(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "GET https://example.com/page";
+
+    fn entry() -> CacheEntry {
+        let time = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+        CacheEntry {
+            response: Response {
+                url: "https://example.com/page".to_owned(),
+                ok: true,
+                status: 200,
+                status_text: "OK".to_owned(),
+                headers: Headers::new(&[
+                    ("content-type", "text/plain"),
+                    ("set-cookie", "a=1"),
+                    ("set-cookie", "b=2"),
+                    ("x-empty", ""),
+                    ("x-colon", "a: b"),
+                ]),
+                redirects: vec![Redirect {
+                    url: "https://example.com/old".to_owned(),
+                    status: 301,
+                    location: "https://example.com/page".to_owned(),
+                }],
+                bytes: b"line 1\nline 2\n\x00\xff".to_vec(),
+            },
+            vary: Headers::new(&[("accept-language", "en")]),
+            request_time: time,
+            response_time: time + Duration::from_millis(250),
+        }
+    }
+
+    fn assert_same(a: &CacheEntry, b: &CacheEntry) {
+        assert_eq!(a.response.url, b.response.url);
+        assert_eq!(a.response.ok, b.response.ok);
+        assert_eq!(a.response.status, b.response.status);
+        assert_eq!(a.response.status_text, b.response.status_text);
+        assert_eq!(a.response.headers, b.response.headers);
+        assert_eq!(a.response.redirects, b.response.redirects);
+        assert_eq!(a.response.bytes, b.response.bytes);
+        assert_eq!(a.vary, b.vary);
+        assert_eq!(a.request_time, b.request_time);
+        assert_eq!(a.response_time, b.response_time);
+    }
+
+    #[test]
+    fn encode_and_decode() {
+        let entry = entry();
+        let bytes = encode(KEY, &entry);
+        assert_same(&decode(KEY, &bytes).unwrap(), &entry);
+
+        let mut empty = entry;
+        empty.response.headers = Headers::default();
+        empty.response.redirects.clear();
+        empty.response.bytes.clear();
+        empty.response.status_text.clear();
+        empty.vary = Headers::default();
+        assert_same(&decode(KEY, &encode(KEY, &empty)).unwrap(), &empty);
+    }
+
+    #[test]
+    fn decode_rejects_other_keys() {
+        let bytes = encode(KEY, &entry());
+        assert!(decode("GET https://example.com/other", &bytes).is_none());
+    }
+
+    #[test]
+    fn decode_rejects_truncated_entries() {
+        let bytes = encode(KEY, &entry());
+        for len in 0..bytes.len() {
+            assert!(
+                decode(KEY, &bytes[..len]).is_none(),
+                "truncated to {} bytes",
+                len
+            );
+        }
+
+        let mut longer = bytes;
+        longer.push(b'!');
+        assert!(decode(KEY, &longer).is_none());
+    }
+
+    #[test]
+    fn decode_rejects_corrupt_entries() {
+        let text = String::from_utf8_lossy(&encode(KEY, &entry())).into_owned();
+        let corruptions = [
+            ("ehttp-cache-v2", "ehttp-cache-v1"),
+            ("200 1", "two-hundred 1"),
+            ("1700000000123", "99999999999999999999999"),
+            ("1700000000373", "18446744073709551615"), // Overflows `SystemTime`
+            ("\n1\n301", "\n18446744073709551615\n301"), // Huge number of redirects
+            ("\n5\n", "\nfive\n"),
+            ("content-type: text/plain", "content-type=text/plain"),
+            ("301 https", "https"),
+        ];
+        for (from, to) in corruptions {
+            assert!(text.contains(from), "{:?} not in the entry", from);
+            let corrupt = text.replacen(from, to, 1);
+            assert!(decode(KEY, corrupt.as_bytes()).is_none(), "{:?}", to);
+        }
+
+        let mut not_utf8 = encode(KEY, &entry());
+        not_utf8[20] = 0xff;
+        assert!(decode(KEY, &not_utf8).is_none());
+    }
+
+    #[test]
+    fn concurrent_writes() {
+        let dir =
+            std::env::temp_dir().join(format!("ehttp-disk-cache-test-{}", std::process::id()));
+        let cache = DiskCache::new(&dir);
+
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..20 {
+                        cache.put(KEY, entry());
+                        if let Some(stored) = cache.get(KEY) {
+                            assert_same(&stored, &entry());
+                        }
+                    }
+                });
+            }
+        });
+        assert_same(&cache.get(KEY).unwrap(), &entry());
+
+        let files: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|file| file.unwrap().file_name())
+            .collect();
+        assert_eq!(
+            files.len(),
+            1,
+            "temporary files were left behind: {files:?}"
+        );
+
+        cache.remove(KEY);
+        assert!(cache.get(KEY).is_none());
+        cache.clear().unwrap();
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,190 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use super::{Cache, CacheEntry};
+
+/// An in-memory [`Cache`] that evicts the least recently used entries
+/// once it holds more than a given number of bytes.
+///
+/// To share the cache between clients, wrap it in an `Arc`.
+pub struct MemoryCache {
+    max_bytes: usize,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// The entries, and when they were last used.
+    entries: HashMap<String, (CacheEntry, u64)>,
+
+    /// The sum of [`CacheEntry::size`] of all entries.
+    num_bytes: usize,
+
+    /// Incremented on every access.
+    clock: u64,
+}
+
+impl MemoryCache {
+    /// Create a cache that holds at most `max_bytes` (approximately).
+    ///
+    /// Responses larger than this are not cached at all.
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// How many bytes the cached entries take up (approximately).
+    pub fn num_bytes(&self) -> usize {
+        self.state.lock().unwrap().num_bytes
+    }
+
+    /// Remove all entries.
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.entries.clear();
+        state.num_bytes = 0;
+    }
+}
+
+impl std::fmt::Debug for MemoryCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.state.lock().unwrap();
+        f.debug_struct("MemoryCache")
+            .field("max_bytes", &self.max_bytes)
+            .field("num_bytes", &state.num_bytes)
+            .field("num_entries", &state.entries.len())
+            .finish()
+    }
+}
+
+impl Cache for MemoryCache {
+    fn get(&self, key: &str) -> Option<CacheEntry> {
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+        let (entry, last_used) = state.entries.get_mut(key)?;
+        *last_used = clock;
+        Some(entry.clone())
+    }
+
+    fn put(&self, key: &str, entry: CacheEntry) {
+        let mut state = self.state.lock().unwrap();
+        state.remove(key);
+
+        let size = entry.size();
+        if size > self.max_bytes {
+            return;
+        }
+
+        while state.num_bytes + size > self.max_bytes {
+            let least_recently_used = state
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| key.clone());
+            match least_recently_used {
+                Some(key) => state.remove(&key),
+                None => break,
+            }
+        }
+
+        state.clock += 1;
+        let clock = state.clock;
+        state.num_bytes += size;
+        state.entries.insert(key.to_owned(), (entry, clock));
+    }
+
+    fn remove(&self, key: &str) {
+        self.state.lock().unwrap().remove(key);
+    }
+}
+
+impl State {
+    fn remove(&mut self, key: &str) {
+        if let Some((entry, _)) = self.entries.remove(key) {
+            self.num_bytes -= entry.size();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Headers, Response};
+
+    /// An entry that takes up `size` bytes.
+    fn entry(size: usize) -> CacheEntry {
+        let now = crate::http_date::now();
+        CacheEntry {
+            response: Response {
+                url: String::new(),
+                ok: true,
+                status: 200,
+                status_text: String::new(),
+                headers: Headers::default(),
+                redirects: vec![],
+                bytes: vec![0; size],
+            },
+            vary: Headers::default(),
+            request_time: now,
+            response_time: now,
+        }
+    }
+
+    fn keys(cache: &MemoryCache) -> Vec<String> {
+        let mut keys: Vec<String> = cache
+            .state
+            .lock()
+            .unwrap()
+            .entries
+            .keys()
+            .cloned()
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used() {
+        let cache = MemoryCache::new(100);
+        cache.put("a", entry(40));
+        cache.put("b", entry(40));
+        assert_eq!(cache.num_bytes(), 80);
+
+        // Using "a" makes "b" the least recently used:
+        assert!(cache.get("a").is_some());
+        cache.put("c", entry(40));
+        assert_eq!(keys(&cache), ["a", "c"]);
+        assert_eq!(cache.num_bytes(), 80);
+
+        // Evicts as many as needed:
+        cache.put("d", entry(90));
+        assert_eq!(keys(&cache), ["d"]);
+        assert_eq!(cache.num_bytes(), 90);
+
+        // Too large to cache at all, but it still replaces the old entry:
+        cache.put("d", entry(101));
+        assert!(cache.get("d").is_none());
+        assert_eq!(cache.num_bytes(), 0);
+    }
+
+    #[test]
+    fn replace_and_remove() {
+        let cache = MemoryCache::new(100);
+        cache.put("a", entry(10));
+        cache.put("a", entry(30));
+        assert_eq!(cache.num_bytes(), 30);
+        assert_eq!(cache.get("a").unwrap().response.bytes.len(), 30);
+
+        cache.put("b", entry(20));
+        cache.remove("a");
+        cache.remove("missing");
+        assert_eq!(keys(&cache), ["b"]);
+        assert_eq!(cache.num_bytes(), 20);
+
+        cache.clear();
+        assert!(keys(&cache).is_empty());
+        assert_eq!(cache.num_bytes(), 0);
+    }
+}
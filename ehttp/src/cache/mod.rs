@@ -0,0 +1,946 @@
+//! An HTTP response cache, following [RFC 9111](https://www.rfc-editor.org/rfc/rfc9111).
+//!
+//! The cache is opt-in. Enable it for a [`crate::Client`] with [`crate::Client::with_cache`]:
+//!
+//! ```
+//! let client = ehttp::Client::new().with_cache(ehttp::cache::MemoryCache::new(16 * 1024 * 1024));
+//! ```
+//!
+//! Only `GET` requests are cached, and only if the response allows it
+//! (based on `Cache-Control`, `Expires`, `ETag`, `Last-Modified` and `Vary`).
+//! It is a private cache, so `s-maxage` is ignored and `private` responses are stored.
+//!
+//! A fresh response is returned without touching the network.
+//! A stale response with an `ETag` or `Last-Modified` header is revalidated
+//! with `If-None-Match` / `If-Modified-Since`, and if the server responds with `304 Not Modified`,
+//! you get the cached response back (with the updated headers).
+//!
+//! If you send `If-None-Match` / `If-Modified-Since` yourself, you get the server's `304 Not Modified` as it is,
+//! and it freshens the cached response if it is about the same version.
+//!
+//! Requests with `Cache-Control: no-cache` are always revalidated,
+//! and requests with `Cache-Control: no-store` bypass the cache.
+//!
+//! Successful `POST`, `PUT`, `PATCH` and `DELETE` requests remove the cached response for their URL.
+//!
+//! Streaming requests are not cached.
+//!
+//! Note that browsers already have an HTTP cache of their own,
+//! so on web this is mostly useful for its memory backend (which is faster) or for custom [`Cache`] implementations.
+
+use std::time::{Duration, SystemTime};
+
+use crate::{Headers, Method, Request, Response, Result};
+
+mod memory;
+pub use memory::MemoryCache;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod disk;
+#[cfg(not(target_arch = "wasm32"))]
+pub use disk::DiskCache;
+
+/// Where cached responses are stored.
+///
+/// Implemented by [`MemoryCache`] and (on native) [`DiskCache`].
+///
+/// The key is the URL of the request.
+/// Implementations should not fail: if something goes wrong, behave as if the entry was missing.
+pub trait Cache: Send + Sync {
+    /// Look up a stored entry.
+    fn get(&self, key: &str) -> Option<CacheEntry>;
+
+    /// Store an entry, replacing any previous entry with the same key.
+    fn put(&self, key: &str, entry: CacheEntry);
+
+    /// Remove an entry, if there is one.
+    fn remove(&self, key: &str);
+}
+
+impl<T: Cache + ?Sized> Cache for std::sync::Arc<T> {
+    fn get(&self, key: &str) -> Option<CacheEntry> {
+        (**self).get(key)
+    }
+
+    fn put(&self, key: &str, entry: CacheEntry) {
+        (**self).put(key, entry);
+    }
+
+    fn remove(&self, key: &str) {
+        (**self).remove(key);
+    }
+}
+
+/// A response stored in a [`Cache`].
+#[derive(Clone, Debug)]
+pub struct CacheEntry {
+    /// The stored response.
+    pub response: Response,
+
+    /// The request headers named by the `Vary` header of the response, as they were sent.
+    ///
+    /// The entry is only used for requests with the same values for these.
+    pub vary: Headers,
+
+    /// When the request was sent.
+    pub request_time: SystemTime,
+
+    /// When the response was received.
+    pub response_time: SystemTime,
+}
+
+impl CacheEntry {
+    /// Approximately how many bytes the entry takes up.
+    pub fn size(&self) -> usize {
+        let headers_size = |headers: &Headers| -> usize {
+            headers
+                .headers
+                .iter()
+                .map(|(key, value)| key.len() + value.len())
+                .sum()
+        };
+        self.response.url.len()
+            + self.response.status_text.len()
+            + headers_size(&self.response.headers)
+            + headers_size(&self.vary)
+            + self.response.bytes.len()
+    }
+
+    /// How old the response is, as defined by <https://www.rfc-editor.org/rfc/rfc9111#name-calculating-age>.
+    fn age(&self, now: SystemTime) -> Duration {
+        let headers = &self.response.headers;
+        let date = headers
+            .get("Date")
+            .and_then(crate::http_date::parse)
+            .unwrap_or(self.response_time);
+        let apparent_age = duration_between(date, self.response_time);
+        let age_value = headers
+            .get("Age")
+            .and_then(|age| age.trim().parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or_default();
+        let response_delay = duration_between(self.request_time, self.response_time);
+        let initial_age = apparent_age.max(age_value.saturating_add(response_delay));
+        let resident_time = duration_between(self.response_time, now);
+        initial_age.saturating_add(resident_time)
+    }
+
+    /// How long the response stays fresh after it was generated,
+    /// as defined by <https://www.rfc-editor.org/rfc/rfc9111#name-calculating-freshness-lifet>.
+    fn freshness_lifetime(&self) -> Duration {
+        let headers = &self.response.headers;
+        let cache_control = CacheControl::new(headers);
+        if cache_control.has("no-cache") {
+            return Duration::ZERO;
+        }
+        if let Some(max_age) = cache_control.seconds("max-age") {
+            return max_age;
+        }
+
+        let date = headers
+            .get("Date")
+            .and_then(crate::http_date::parse)
+            .unwrap_or(self.response_time);
+        if let Some(expires) = headers.get("Expires") {
+            // An invalid date (e.g. "0") means "already expired".
+            return crate::http_date::parse(expires)
+                .map(|expires| duration_between(date, expires))
+                .unwrap_or_default();
+        }
+
+        // Heuristic freshness: 10% of the time since the last modification, up to a day.
+        if is_heuristically_cacheable(self.response.status) {
+            if let Some(last_modified) = headers
+                .get("Last-Modified")
+                .and_then(crate::http_date::parse)
+            {
+                return (duration_between(last_modified, date) / 10)
+                    .min(Duration::from_secs(24 * 60 * 60));
+            }
+        }
+
+        Duration::ZERO
+    }
+
+    /// Does the request select this entry, according to `Vary`?
+    fn matches(&self, request: &Request) -> bool {
+        vary_names(&self.response.headers)
+            .all(|name| name != "*" && self.vary.get(name) == request.headers.get(name))
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// The parsed `Cache-Control` header(s).
+struct CacheControl(Vec<(String, Option<String>)>);
+
+impl CacheControl {
+    fn new(headers: &Headers) -> Self {
+        let mut directives = Vec::new();
+        for value in headers.get_all("Cache-Control") {
+            for directive in value.split(',') {
+                let (name, argument) = match directive.split_once('=') {
+                    Some((name, argument)) => (name, Some(argument.trim().trim_matches('"'))),
+                    None => (directive, None),
+                };
+                let name = name.trim().to_lowercase();
+                if !name.is_empty() {
+                    directives.push((name, argument.map(|argument| argument.to_owned())));
+                }
+            }
+        }
+        Self(directives)
+    }
+
+    fn has(&self, name: &str) -> bool {
+        self.0.iter().any(|(n, _)| n == name)
+    }
+
+    fn seconds(&self, name: &str) -> Option<Duration> {
+        let argument = self.0.iter().find(|(n, _)| n == name)?.1.as_ref()?;
+        argument.parse().ok().map(Duration::from_secs)
+    }
+}
+
+/// Status codes that can be cached without explicit freshness information.
+///
+/// See <https://www.rfc-editor.org/rfc/rfc9110#name-overview-of-status-codes>.
+fn is_heuristically_cacheable(status: u16) -> bool {
+    matches!(
+        status,
+        200 | 203 | 204 | 300 | 301 | 308 | 404 | 405 | 410 | 414 | 501
+    )
+}
+
+fn vary_names(headers: &Headers) -> impl Iterator<Item = &str> {
+    headers
+        .get_all("Vary")
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+}
+
+/// `to - from`, or zero if `to` is earlier.
+fn duration_between(from: SystemTime, to: SystemTime) -> Duration {
+    to.duration_since(from).unwrap_or_default()
+}
+
+/// Is it allowed and useful to store this response?
+fn is_storable(request: &Request, response: &Response) -> bool {
+    let response_cache_control = CacheControl::new(&response.headers);
+    let has_explicit_freshness = response_cache_control.has("max-age")
+        || response_cache_control.has("public")
+        || response.headers.get("Expires").is_some();
+    let has_validator =
+        response.headers.get("ETag").is_some() || response.headers.get("Last-Modified").is_some();
+
+    request.method == Method::GET
+        && !CacheControl::new(&request.headers).has("no-store")
+        && !response_cache_control.has("no-store")
+        && !vary_names(&response.headers).any(|name| name == "*")
+        && (has_explicit_freshness || is_heuristically_cacheable(response.status))
+        && response.status != 206
+        && response.status != 304
+        && (has_explicit_freshness || has_validator)
+}
+
+// ----------------------------------------------------------------------------
+
+/// What to do with a request, according to the cache.
+pub(crate) enum Lookup {
+    /// Use this cached response, without touching the network.
+    Fresh(Response),
+
+    /// Send the request and then call [`Pending::finish`] with the result.
+    ///
+    /// If there is a request here, send that instead (it asks the server to revalidate our stale entry).
+    Send(Option<Box<Request>>, Box<Pending>),
+}
+
+/// A request that we are waiting for the response to.
+pub(crate) struct Pending {
+    stale: Option<CacheEntry>,
+    request_time: SystemTime,
+
+    /// The caller sent their own `If-None-Match` or `If-Modified-Since`,
+    /// so they get a `304 Not Modified` as it is.
+    conditional: bool,
+}
+
+/// Look up the request in the cache.
+pub(crate) fn lookup(cache: &dyn Cache, request: &Request) -> Lookup {
+    let pending = Box::new(Pending {
+        stale: None,
+        request_time: crate::http_date::now(),
+        conditional: false,
+    });
+
+    let request_cache_control = CacheControl::new(&request.headers);
+    if request.method != Method::GET
+        || request_cache_control.has("no-store")
+        || ["If-Match", "Range"]
+            .iter()
+            .any(|name| request.headers.get(name).is_some())
+    {
+        return Lookup::Send(None, pending);
+    }
+    if ["If-None-Match", "If-Modified-Since"]
+        .iter()
+        .any(|name| request.headers.get(name).is_some())
+    {
+        // The caller revalidates their own copy, but a `304` can still freshen ours:
+        return Lookup::Send(
+            None,
+            Box::new(Pending {
+                stale: cache
+                    .get(&request.url)
+                    .filter(|entry| entry.matches(request)),
+                conditional: true,
+                ..*pending
+            }),
+        );
+    }
+
+    let Some(entry) = cache.get(&request.url) else {
+        return Lookup::Send(None, pending);
+    };
+    if !entry.matches(request) {
+        return Lookup::Send(None, pending);
+    }
+
+    let age = entry.age(pending.request_time);
+    let freshness_lifetime = entry.freshness_lifetime();
+    let no_cache = request_cache_control.has("no-cache")
+        || (request.headers.get("Cache-Control").is_none()
+            && request.headers.get("Pragma") == Some("no-cache"));
+    let too_old = request_cache_control
+        .seconds("max-age")
+        .is_some_and(|max_age| max_age < age);
+    let not_fresh_enough = request_cache_control
+        .seconds("min-fresh")
+        .is_some_and(|min_fresh| freshness_lifetime < age.saturating_add(min_fresh));
+
+    if age < freshness_lifetime && !no_cache && !too_old && !not_fresh_enough {
+        let mut response = entry.response;
+        set_header(&mut response.headers, "Age", age.as_secs().to_string());
+        return Lookup::Fresh(response);
+    }
+
+    let etag = entry.response.headers.get("ETag");
+    let last_modified = entry.response.headers.get("Last-Modified");
+    if etag.is_none() && last_modified.is_none() {
+        return Lookup::Send(None, pending);
+    }
+
+    let mut conditional = request.clone();
+    if let Some(etag) = etag {
+        conditional.headers.insert("If-None-Match", etag);
+    }
+    if let Some(last_modified) = last_modified {
+        conditional
+            .headers
+            .insert("If-Modified-Since", last_modified);
+    }
+    Lookup::Send(
+        Some(Box::new(conditional)),
+        Box::new(Pending {
+            stale: Some(entry),
+            ..*pending
+        }),
+    )
+}
+
+impl Pending {
+    /// Update the cache with the result of the request,
+    /// and turn a `304 Not Modified` back into the cached response
+    /// (unless the original request was conditional itself).
+    ///
+    /// `request` is the original request, without any headers added for revalidation.
+    pub(crate) fn finish(
+        self,
+        cache: &dyn Cache,
+        request: &Request,
+        result: Result<Response>,
+    ) -> Result<Response> {
+        let Ok(response) = result else {
+            return result;
+        };
+
+        let is_safe = matches!(
+            request.method,
+            Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+        );
+        if !is_safe && response.status < 400 {
+            // https://www.rfc-editor.org/rfc/rfc9111#name-invalidating-stored-respons
+            cache.remove(&request.url);
+            return Ok(response);
+        }
+        if request.method != Method::GET {
+            return Ok(response);
+        }
+
+        let response_time = crate::http_date::now();
+
+        let entry = match self.stale {
+            Some(mut stale) if response.status == 304 => {
+                // https://www.rfc-editor.org/rfc/rfc9111#name-freshening-stored-responses
+                if self.conditional && !is_same_version(&stale.response.headers, &response.headers)
+                {
+                    // The caller's copy is still valid, which doesn't tell us anything about ours.
+                    return Ok(response);
+                }
+                for (key, value) in &response.headers {
+                    if !key.eq_ignore_ascii_case("Content-Length") {
+                        set_header(&mut stale.response.headers, key, value.clone());
+                    }
+                }
+                stale.request_time = self.request_time;
+                stale.response_time = response_time;
+                if self.conditional {
+                    if is_storable(request, &stale.response) {
+                        cache.put(&request.url, stale);
+                    }
+                    return Ok(response);
+                }
+                stale
+            }
+            _ if response.status == 304 => {
+                // Not about anything we have stored.
+                return Ok(response);
+            }
+            Some(_) if 500 <= response.status => {
+                // Keep the entry we have, the server may be having a temporary problem.
+                return Ok(response);
+            }
+            _ => CacheEntry {
+                vary: {
+                    let mut vary = Headers::default();
+                    for name in vary_names(&response.headers) {
+                        for value in request.headers.get_all(name) {
+                            vary.insert(name, value);
+                        }
+                    }
+                    vary
+                },
+                response,
+                request_time: self.request_time,
+                response_time,
+            },
+        };
+
+        if is_storable(request, &entry.response) {
+            cache.put(&request.url, entry.clone());
+        } else {
+            cache.remove(&request.url);
+        }
+        Ok(entry.response)
+    }
+}
+
+/// Does a `304 Not Modified` with these headers refer to the stored response with the `stored` headers?
+///
+/// See <https://www.rfc-editor.org/rfc/rfc9111#name-freshening-stored-responses>.
+fn is_same_version(stored: &Headers, not_modified: &Headers) -> bool {
+    if let Some(etag) = not_modified.get("ETag") {
+        stored.get("ETag") == Some(etag)
+    } else if let Some(last_modified) = not_modified.get("Last-Modified") {
+        stored.get("Last-Modified") == Some(last_modified)
+    } else {
+        stored.get("ETag").is_none() && stored.get("Last-Modified").is_none()
+    }
+}
+
+/// Replace all headers with the given key.
+fn set_header(headers: &mut Headers, key: &str, value: String) {
+    headers
+        .headers
+        .retain(|(k, _)| !k.eq_ignore_ascii_case(key));
+    headers.insert(key, value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URL: &str = "https://example.com/";
+
+    type HeaderList<'a> = &'a [(&'a str, &'a str)];
+
+    /// `Sun, 06 Nov 1994 08:49:37 GMT`
+    fn date() -> SystemTime {
+        crate::http_date::parse("Sun, 06 Nov 1994 08:49:37 GMT").unwrap()
+    }
+
+    fn response(status: u16, headers: &[(&str, &str)]) -> Response {
+        Response {
+            url: URL.to_owned(),
+            ok: (200..300).contains(&status),
+            status,
+            status_text: String::new(),
+            headers: Headers::new(headers),
+            redirects: vec![],
+            bytes: b"cached body".to_vec(),
+        }
+    }
+
+    fn entry(headers: &[(&str, &str)], response_time: SystemTime) -> CacheEntry {
+        CacheEntry {
+            response: response(200, headers),
+            vary: Headers::default(),
+            request_time: response_time,
+            response_time,
+        }
+    }
+
+    /// Store a response that was received `age` seconds ago.
+    fn store(cache: &MemoryCache, headers: &[(&str, &str)], age: u64) {
+        let response_time = crate::http_date::now() - Duration::from_secs(age);
+        cache.put(URL, entry(headers, response_time));
+    }
+
+    enum Expected {
+        Fresh,
+        Revalidate,
+        Send,
+    }
+
+    fn check(cache: &MemoryCache, request: &Request, expected: &Expected) {
+        match (lookup(cache, request), expected) {
+            (Lookup::Fresh(_), Expected::Fresh)
+            | (Lookup::Send(Some(_), _), Expected::Revalidate)
+            | (Lookup::Send(None, _), Expected::Send) => {}
+            (Lookup::Fresh(_), _) => panic!("{:?}: unexpectedly fresh", request.headers),
+            (Lookup::Send(Some(_), _), _) => {
+                panic!("{:?}: unexpectedly revalidated", request.headers)
+            }
+            (Lookup::Send(None, _), _) => panic!("{:?}: unexpectedly sent", request.headers),
+        }
+    }
+
+    #[test]
+    fn freshness_lifetime() {
+        let cases: &[(HeaderList<'_>, u64)] = &[
+            (&[], 0),
+            (&[("Cache-Control", "max-age=60")], 60),
+            (&[("Cache-Control", "MAX-AGE=\"60\"")], 60),
+            (
+                &[("Cache-Control", "public"), ("Cache-Control", "max-age=60")],
+                60,
+            ),
+            (&[("Cache-Control", "max-age=60, no-cache")], 0),
+            (&[("Cache-Control", "max-age=60, must-revalidate")], 60),
+            (&[("Cache-Control", "max-age=invalid")], 0),
+            // We are a private cache, so `s-maxage` doesn't apply:
+            (&[("Cache-Control", "s-maxage=600, max-age=60")], 60),
+            (&[("Cache-Control", "s-maxage=600")], 0),
+            // `Expires` is relative to `Date`, not to when we got the response:
+            (
+                &[
+                    ("Date", "Sun, 06 Nov 1994 08:49:37 GMT"),
+                    ("Expires", "Sun, 06 Nov 1994 09:49:37 GMT"),
+                ],
+                3600,
+            ),
+            (
+                &[
+                    ("Date", "Sun, 06 Nov 1994 08:49:37 GMT"),
+                    ("Expires", "Sun, 06 Nov 1994 07:49:37 GMT"),
+                ],
+                0,
+            ),
+            (&[("Expires", "0")], 0),
+            // `max-age` wins over `Expires`:
+            (
+                &[
+                    ("Cache-Control", "max-age=60"),
+                    ("Expires", "Sun, 06 Nov 1994 09:49:37 GMT"),
+                ],
+                60,
+            ),
+            // Heuristic: 10% of the time since the last modification…
+            (&[("Last-Modified", "Sun, 06 Nov 1994 07:49:37 GMT")], 360),
+            // … up to a day:
+            (
+                &[("Last-Modified", "Sun, 06 Nov 1984 08:49:37 GMT")],
+                86_400,
+            ),
+        ];
+        for (headers, expected) in cases {
+            assert_eq!(
+                entry(headers, date()).freshness_lifetime(),
+                Duration::from_secs(*expected),
+                "{headers:?}"
+            );
+        }
+
+        // Only some status codes get heuristic freshness:
+        let mut not_heuristic = entry(
+            &[("Last-Modified", "Sun, 06 Nov 1994 07:49:37 GMT")],
+            date(),
+        );
+        not_heuristic.response.status = 302;
+        assert_eq!(not_heuristic.freshness_lifetime(), Duration::ZERO);
+    }
+
+    #[test]
+    fn age() {
+        let seconds = Duration::from_secs;
+        let now = date() + seconds(10);
+
+        assert_eq!(entry(&[], date()).age(now), seconds(10));
+
+        // `Age` plus the time the request took:
+        let mut delayed = entry(&[("Age", "100")], date());
+        delayed.request_time = date() - seconds(2);
+        assert_eq!(delayed.age(now), seconds(112));
+
+        // The `Date` is 50 seconds before we got the response, which is more than `Age` says:
+        let late = entry(
+            &[("Date", "Sun, 06 Nov 1994 08:48:47 GMT"), ("Age", "20")],
+            date(),
+        );
+        assert_eq!(late.age(now), seconds(60));
+
+        // A `Date` in the future (clock skew) doesn't make the age negative:
+        let skewed = entry(&[("Date", "Sun, 06 Nov 1994 09:49:37 GMT")], date());
+        assert_eq!(skewed.age(now), seconds(10));
+
+        let invalid = entry(&[("Age", "old")], date());
+        assert_eq!(invalid.age(now), seconds(10));
+
+        let huge = entry(&[("Age", &u64::MAX.to_string())], date());
+        assert_eq!(huge.age(now), Duration::MAX);
+    }
+
+    #[test]
+    fn lookup_by_freshness() {
+        let request = Request::get(URL);
+        let cases: &[(HeaderList<'_>, u64, Expected)] = &[
+            (&[("Cache-Control", "max-age=60")], 10, Expected::Fresh),
+            (&[("Cache-Control", "max-age=60")], 100, Expected::Send),
+            (
+                &[("Cache-Control", "max-age=60"), ("ETag", "\"v1\"")],
+                100,
+                Expected::Revalidate,
+            ),
+            (
+                &[
+                    ("Cache-Control", "max-age=60, must-revalidate"),
+                    ("Last-Modified", "Sun, 06 Nov 1994 08:49:37 GMT"),
+                ],
+                100,
+                Expected::Revalidate,
+            ),
+            (
+                &[("Cache-Control", "max-age=60"), ("Age", "55")],
+                10,
+                Expected::Send,
+            ),
+            (
+                &[("Cache-Control", "no-cache"), ("ETag", "\"v1\"")],
+                0,
+                Expected::Revalidate,
+            ),
+        ];
+        for (headers, age, expected) in cases {
+            let cache = MemoryCache::new(1024 * 1024);
+            store(&cache, headers, *age);
+            check(&cache, &request, expected);
+        }
+    }
+
+    #[test]
+    fn lookup_by_request_headers() {
+        let cases: &[(&str, &str, Expected)] = &[
+            ("Cache-Control", "no-cache", Expected::Revalidate),
+            ("Pragma", "no-cache", Expected::Revalidate),
+            ("Cache-Control", "no-store", Expected::Send),
+            ("Cache-Control", "max-age=5", Expected::Revalidate),
+            ("Cache-Control", "max-age=500", Expected::Fresh),
+            ("Cache-Control", "min-fresh=100", Expected::Revalidate),
+            (
+                "Cache-Control",
+                "min-fresh=18446744073709551615",
+                Expected::Revalidate,
+            ),
+            ("If-None-Match", "\"v0\"", Expected::Send),
+            ("Range", "bytes=0-10", Expected::Send),
+        ];
+        for (name, value, expected) in cases {
+            let cache = MemoryCache::new(1024 * 1024);
+            store(
+                &cache,
+                &[("Cache-Control", "max-age=60"), ("ETag", "\"v1\"")],
+                10,
+            );
+            check(
+                &cache,
+                &Request::get(URL).with_header(name, value),
+                expected,
+            );
+        }
+
+        let cache = MemoryCache::new(1024 * 1024);
+        store(&cache, &[("Cache-Control", "max-age=60")], 10);
+        check(
+            &cache,
+            &Request::get(URL).with_method(Method::HEAD),
+            &Expected::Send,
+        );
+    }
+
+    #[test]
+    fn conditional_request() {
+        let cache = MemoryCache::new(1024 * 1024);
+        store(
+            &cache,
+            &[
+                ("Cache-Control", "max-age=0"),
+                ("ETag", "\"v1\""),
+                ("Last-Modified", "Sun, 06 Nov 1994 08:49:37 GMT"),
+            ],
+            10,
+        );
+        let Lookup::Send(Some(conditional), _) = lookup(&cache, &Request::get(URL)) else {
+            panic!("Expected a conditional request");
+        };
+        assert_eq!(conditional.headers.get("If-None-Match"), Some("\"v1\""));
+        assert_eq!(
+            conditional.headers.get("If-Modified-Since"),
+            Some("Sun, 06 Nov 1994 08:49:37 GMT")
+        );
+    }
+
+    #[test]
+    fn vary() {
+        let cache = MemoryCache::new(1024 * 1024);
+        let english = Request::get(URL).with_header("Accept-Language", "en");
+        let Lookup::Send(None, pending) = lookup(&cache, &english) else {
+            panic!("Expected a cache miss");
+        };
+        let vary_response = response(
+            200,
+            &[("Cache-Control", "max-age=60"), ("Vary", "Accept-Language")],
+        );
+        pending.finish(&cache, &english, Ok(vary_response)).unwrap();
+
+        check(&cache, &english, &Expected::Fresh);
+        check(
+            &cache,
+            &Request::get(URL).with_header("accept-language", "en"),
+            &Expected::Fresh,
+        );
+        check(
+            &cache,
+            &Request::get(URL).with_header("Accept-Language", "sv"),
+            &Expected::Send,
+        );
+        check(&cache, &Request::get(URL), &Expected::Send);
+
+        // `Vary: *` never matches, so it isn't even stored:
+        let cache = MemoryCache::new(1024 * 1024);
+        let Lookup::Send(None, pending) = lookup(&cache, &english) else {
+            panic!("Expected a cache miss");
+        };
+        let vary_response = response(200, &[("Cache-Control", "max-age=60"), ("Vary", "*")]);
+        pending.finish(&cache, &english, Ok(vary_response)).unwrap();
+        assert!(cache.get(URL).is_none());
+    }
+
+    /// Send `request` through an empty cache, with the given response, and return whether it was stored.
+    fn is_stored(request: &Request, response: Response) -> bool {
+        let cache = MemoryCache::new(1024 * 1024);
+        let Lookup::Send(_, pending) = lookup(&cache, request) else {
+            panic!("Expected a cache miss");
+        };
+        pending.finish(&cache, request, Ok(response)).unwrap();
+        cache.get(URL).is_some()
+    }
+
+    #[test]
+    fn storable() {
+        let get = Request::get(URL);
+        let cases: &[(u16, HeaderList<'_>, bool)] = &[
+            (200, &[("Cache-Control", "max-age=60")], true),
+            (200, &[("Expires", "Sun, 06 Nov 1994 09:49:37 GMT")], true),
+            (200, &[("ETag", "\"v1\"")], true),
+            (200, &[], false),
+            (200, &[("Cache-Control", "max-age=60, no-store")], false),
+            (
+                200,
+                &[("Cache-Control", "no-cache"), ("ETag", "\"v1\"")],
+                true,
+            ),
+            (
+                404,
+                &[("Last-Modified", "Sun, 06 Nov 1994 08:49:37 GMT")],
+                true,
+            ),
+            (
+                302,
+                &[("Last-Modified", "Sun, 06 Nov 1994 08:49:37 GMT")],
+                false,
+            ),
+            (302, &[("Cache-Control", "max-age=60")], true),
+            (206, &[("Cache-Control", "max-age=60")], false),
+        ];
+        for (status, headers, expected) in cases {
+            assert_eq!(
+                is_stored(&get, response(*status, headers)),
+                *expected,
+                "{status} {headers:?}"
+            );
+        }
+
+        let cacheable = || response(200, &[("Cache-Control", "max-age=60")]);
+        let no_store = Request::get(URL).with_header("Cache-Control", "no-store");
+        assert!(!is_stored(&no_store, cacheable()));
+        let post = Request::get(URL).with_method(Method::POST);
+        assert!(!is_stored(&post, cacheable()));
+    }
+
+    #[test]
+    fn not_modified_freshens_the_entry() {
+        let cache = MemoryCache::new(1024 * 1024);
+        store(
+            &cache,
+            &[
+                ("Cache-Control", "max-age=60"),
+                ("ETag", "\"v1\""),
+                ("Content-Length", "11"),
+                ("X-Version", "old"),
+                ("X-Kept", "yes"),
+            ],
+            100,
+        );
+
+        let request = Request::get(URL);
+        let Lookup::Send(Some(_), pending) = lookup(&cache, &request) else {
+            panic!("Expected a conditional request");
+        };
+        let mut not_modified = response(
+            304,
+            &[
+                ("Cache-Control", "max-age=120"),
+                ("Content-Length", "0"),
+                ("x-version", "new"),
+            ],
+        );
+        not_modified.bytes.clear();
+        let response = pending.finish(&cache, &request, Ok(not_modified)).unwrap();
+
+        assert_eq!(response.status, 200);
+        assert_eq!(response.bytes, b"cached body");
+        assert_eq!(response.headers.get("Cache-Control"), Some("max-age=120"));
+        assert_eq!(response.headers.get("Content-Length"), Some("11"));
+        assert_eq!(response.headers.get("X-Version"), Some("new"));
+        assert_eq!(response.headers.get_all("X-Version").count(), 1);
+        assert_eq!(response.headers.get("X-Kept"), Some("yes"));
+
+        // The entry is fresh again:
+        check(&cache, &request, &Expected::Fresh);
+    }
+
+    #[test]
+    fn not_modified_to_the_callers_own_conditional_request() {
+        let stored: HeaderList<'_> = &[("Cache-Control", "max-age=60"), ("ETag", "\"v1\"")];
+        let not_modified = |etag| {
+            let mut response = response(304, &[("Cache-Control", "max-age=120"), ("ETag", etag)]);
+            response.bytes.clear();
+            response
+        };
+
+        // Our version: the entry is freshened, and the caller gets their 304.
+        let cache = MemoryCache::new(1024 * 1024);
+        store(&cache, stored, 100);
+        let request = Request::get(URL).with_header("If-None-Match", "\"v1\"");
+        let Lookup::Send(None, pending) = lookup(&cache, &request) else {
+            panic!("Expected the request to be sent as it is");
+        };
+        let response = pending
+            .finish(&cache, &request, Ok(not_modified("\"v1\"")))
+            .unwrap();
+        assert_eq!(response.status, 304);
+        assert!(response.bytes.is_empty());
+        check(&cache, &Request::get(URL), &Expected::Fresh);
+        let entry = cache.get(URL).unwrap();
+        assert_eq!(entry.response.status, 200);
+        assert_eq!(entry.response.bytes, b"cached body");
+        assert_eq!(
+            entry.response.headers.get("Cache-Control"),
+            Some("max-age=120")
+        );
+
+        // Another version: the entry stays as it was.
+        let cache = MemoryCache::new(1024 * 1024);
+        store(&cache, stored, 100);
+        let request = Request::get(URL).with_header("If-None-Match", "\"v0\"");
+        let Lookup::Send(None, pending) = lookup(&cache, &request) else {
+            panic!("Expected the request to be sent as it is");
+        };
+        let response = pending
+            .finish(&cache, &request, Ok(not_modified("\"v0\"")))
+            .unwrap();
+        assert_eq!(response.status, 304);
+        check(&cache, &Request::get(URL), &Expected::Revalidate);
+        assert_eq!(
+            cache
+                .get(URL)
+                .unwrap()
+                .response
+                .headers
+                .get("Cache-Control"),
+            Some("max-age=60")
+        );
+
+        // Nothing stored: nothing to do.
+        let cache = MemoryCache::new(1024 * 1024);
+        let request =
+            Request::get(URL).with_header("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT");
+        let Lookup::Send(None, pending) = lookup(&cache, &request) else {
+            panic!("Expected the request to be sent as it is");
+        };
+        let response = pending
+            .finish(&cache, &request, Ok(not_modified("\"v1\"")))
+            .unwrap();
+        assert_eq!(response.status, 304);
+        assert!(cache.get(URL).is_none());
+    }
+
+    #[test]
+    fn server_errors_keep_the_stale_entry() {
+        let cache = MemoryCache::new(1024 * 1024);
+        store(
+            &cache,
+            &[("Cache-Control", "max-age=60"), ("ETag", "\"v1\"")],
+            100,
+        );
+
+        let request = Request::get(URL);
+        let Lookup::Send(Some(_), pending) = lookup(&cache, &request) else {
+            panic!("Expected a conditional request");
+        };
+        let response = pending
+            .finish(&cache, &request, Ok(response(503, &[])))
+            .unwrap();
+        assert_eq!(response.status, 503);
+        assert!(cache.get(URL).is_some());
+    }
+
+    #[test]
+    fn unsafe_methods_invalidate() {
+        for (status, invalidated) in [(200, true), (303, true), (400, false), (500, false)] {
+            let cache = MemoryCache::new(1024 * 1024);
+            store(&cache, &[("Cache-Control", "max-age=60")], 10);
+
+            let post = Request::post(URL, b"data".to_vec());
+            let Lookup::Send(None, pending) = lookup(&cache, &post) else {
+                panic!("Expected the request to be sent");
+            };
+            pending
+                .finish(&cache, &post, Ok(response(status, &[])))
+                .unwrap();
+            assert_eq!(cache.get(URL).is_none(), invalidated, "{status}");
+        }
+    }
+}
@@ -0,0 +1,1032 @@
+//! Record HTTP exchanges to a file once, and replay them from it later, without touching the network.
+//!
+//! Requires the `cassette` feature to be enabled. Only available on native.
+//!
+//! ```no_run
+//! use ehttp::cassette::{Cassette, Mode};
+//!
+//! // Records the first time (when the file doesn't exist yet), and replays after that:
+//! let cassette = Cassette::open("tests/cassettes/users.json", Mode::Auto).unwrap();
+//!
+//! let client = ehttp::Client::new().with_transport(cassette.clone());
+//! let response = client.fetch_blocking(&ehttp::Request::get("https://api.example.com/users"));
+//!
+//! // Or, for the free functions too:
+//! ehttp::set_global_transport(cassette);
+//! let response = ehttp::fetch_blocking(&ehttp::Request::get("https://api.example.com/users"));
+//! ehttp::clear_global_transport();
+//! ```
+//!
+//! The file is human-readable JSON.
+//! Bodies are stored as text if they are valid UTF-8, and as base64 otherwise.
+//! Responses from the [`crate::streaming`] API are stored chunk by chunk, so they replay with the same chunk boundaries.
+//! Errors are recorded too (except [`Error::Aborted`]), including those in the middle of a streamed body.
+//!
+//! Sensitive headers (by default `Authorization`, `Proxy-Authorization`, `Cookie` and `Set-Cookie`)
+//! are replaced with `[REDACTED]` before they are saved.
+//!
+//! When replaying, each request gets the first recorded exchange that [matches](MatchRules) it
+//! and hasn't been replayed yet.
+
+use std::{
+    convert::TryFrom as _,
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use base64::Engine as _;
+use serde_json::{json, Value};
+
+use crate::{
+    Error, Headers, Method, PartialResponse, Redirect, Request, RequestHandle, Response, Result,
+    Transport,
+};
+
+#[cfg(feature = "streaming")]
+use {crate::streaming::Part, std::ops::ControlFlow};
+
+/// What a [`Cassette`] does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Perform the requests, and record them, replacing the file.
+    Record,
+
+    /// Replay the requests from the file, which must exist.
+    /// Requests that weren't recorded fail.
+    Replay,
+
+    /// [`Self::Replay`] if the file exists, otherwise [`Self::Record`].
+    Auto,
+}
+
+/// Which parts of a request must be the same as in a recorded exchange for it to be replayed.
+#[derive(Clone, Debug)]
+pub struct MatchRules {
+    /// Match the method. Default: `true`.
+    pub method: bool,
+
+    /// Match the URL. Default: `true`.
+    pub url: bool,
+
+    /// Match the body. Default: `false`.
+    pub body: bool,
+
+    /// Match the values of these headers (case-insensitive). Default: none.
+    ///
+    /// Redacted headers (see [`Cassette::with_redacted_header`]) can only match on whether they are present.
+    pub headers: Vec<String>,
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        Self {
+            method: true,
+            url: true,
+            body: false,
+            headers: vec![],
+        }
+    }
+}
+
+impl MatchRules {
+    fn matches(&self, recorded: &RecordedRequest, request: &RecordedRequest) -> bool {
+        (!self.method || recorded.method == request.method)
+            && (!self.url || recorded.url == request.url)
+            && (!self.body || recorded.body == request.body)
+            && self.headers.iter().all(|key| {
+                recorded
+                    .headers
+                    .get_all(key)
+                    .eq(request.headers.get_all(key))
+            })
+    }
+}
+
+/// A [`Transport`] that records or replays HTTP exchanges.
+///
+/// See the [module-level docs](self).
+///
+/// Cloning it is cheap, and the clones share the recorded exchanges.
+#[derive(Clone)]
+pub struct Cassette {
+    path: PathBuf,
+    recording: bool,
+    match_rules: MatchRules,
+    redacted_headers: Vec<String>,
+    transport: Arc<dyn Transport>,
+    exchanges: Arc<Mutex<Vec<Exchange>>>,
+}
+
+impl std::fmt::Debug for Cassette {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Cassette")
+            .field("path", &self.path)
+            .field("recording", &self.recording)
+            .field("match_rules", &self.match_rules)
+            .field("redacted_headers", &self.redacted_headers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Cassette {
+    /// Open the cassette at the given path.
+    ///
+    /// Fails if the file should be replayed, but can't be read.
+    pub fn open(path: impl AsRef<Path>, mode: Mode) -> io::Result<Self> {
+        let path = path.as_ref().to_owned();
+        let recording = match mode {
+            Mode::Record => true,
+            Mode::Replay => false,
+            Mode::Auto => !path.exists(),
+        };
+        let exchanges = if recording {
+            vec![]
+        } else {
+            let json = std::fs::read_to_string(&path)?;
+            parse_cassette(&json).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid cassette {}: {err}", path.display()),
+                )
+            })?
+        };
+        Ok(Self {
+            path,
+            recording,
+            match_rules: MatchRules::default(),
+            redacted_headers: [
+                "Authorization",
+                "Proxy-Authorization",
+                "Cookie",
+                "Set-Cookie",
+            ]
+            .map(String::from)
+            .to_vec(),
+            transport: crate::client::default_transport(),
+            exchanges: Arc::new(Mutex::new(exchanges)),
+        })
+    }
+
+    /// Decide which recorded exchange is replayed for a request.
+    pub fn with_match_rules(mut self, match_rules: MatchRules) -> Self {
+        self.match_rules = match_rules;
+        self
+    }
+
+    /// Also replace the value of this header (case-insensitive) with `[REDACTED]` before saving.
+    pub fn with_redacted_header(mut self, key: impl ToString) -> Self {
+        self.redacted_headers.push(key.to_string());
+        self
+    }
+
+    /// Record the exchanges of the given transport, instead of the default one.
+    pub fn with_transport(mut self, transport: impl Transport + 'static) -> Self {
+        self.transport = Arc::new(transport);
+        self
+    }
+
+    /// Are we recording (rather than replaying)?
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    fn redact(&self, headers: &Headers) -> Headers {
+        let mut redacted = Headers::default();
+        for (key, value) in headers {
+            if self
+                .redacted_headers
+                .iter()
+                .any(|redacted_key| redacted_key.eq_ignore_ascii_case(key))
+            {
+                redacted.insert(key, "[REDACTED]");
+            } else {
+                redacted.insert(key, value);
+            }
+        }
+        redacted
+    }
+
+    fn recorded_request(&self, request: &Request) -> RecordedRequest {
+        RecordedRequest {
+            method: request.method.clone(),
+            url: request.url.clone(),
+            headers: self.redact(&request.headers),
+            body: request.body.as_bytes().map(<[u8]>::to_vec),
+        }
+    }
+
+    /// Find the recorded result for the request.
+    fn replay(&self, request: &Request) -> Result<RecordedResponse> {
+        let request = self.recorded_request(request);
+        let mut exchanges = self.exchanges.lock().unwrap();
+        let exchange = exchanges
+            .iter_mut()
+            .find(|exchange| {
+                !exchange.replayed && self.match_rules.matches(&exchange.request, &request)
+            })
+            .ok_or_else(|| {
+                Error::Other(format!(
+                    "No recorded exchange in {} matches {} {}",
+                    self.path.display(),
+                    request.method.as_str(),
+                    request.url
+                ))
+            })?;
+        exchange.replayed = true;
+        exchange.result.clone()
+    }
+
+    /// Add the exchange, and save the cassette.
+    fn record(&self, request: RecordedRequest, result: Result<RecordedResponse>) -> Result<()> {
+        let error = match &result {
+            Ok(response) => response.error.as_ref(),
+            Err(err) => Some(err),
+        };
+        if matches!(error, Some(Error::Aborted)) {
+            return Ok(());
+        }
+        let mut exchanges = self.exchanges.lock().unwrap();
+        exchanges.push(Exchange {
+            request,
+            result: result.map(|mut response| {
+                response.partial.headers = self.redact(&response.partial.headers);
+                response
+            }),
+            replayed: false,
+        });
+        self.save(&exchanges).map_err(|err| {
+            Error::Other(format!(
+                "Failed to save cassette {}: {err}",
+                self.path.display()
+            ))
+        })
+    }
+
+    fn save(&self, exchanges: &[Exchange]) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let json = json!({
+            "exchanges": exchanges.iter().map(Exchange::to_json).collect::<Vec<_>>(),
+        });
+        let mut text = serde_json::to_string_pretty(&json)?;
+        text.push('\n');
+        std::fs::write(&self.path, text)
+    }
+}
+
+impl Transport for Cassette {
+    fn fetch(
+        &self,
+        request: Request,
+        handle: RequestHandle,
+        on_done: Box<dyn FnOnce(Result<Response>) + Send>,
+    ) {
+        if !self.recording {
+            on_done(self.replay(&request).and_then(|response| {
+                if let Some(err) = response.error {
+                    return Err(err);
+                }
+                let bytes = response.chunks.concat();
+                Ok(response.partial.complete(bytes))
+            }));
+            return;
+        }
+
+        let recorded_request = self.recorded_request(&request);
+        let cassette = self.clone();
+        self.transport.fetch(
+            request,
+            handle,
+            Box::new(move |result| {
+                let recorded = result.clone().map(|response| {
+                    let bytes = response.bytes.clone();
+                    RecordedResponse {
+                        partial: partial_response(response),
+                        chunks: vec![bytes],
+                        error: None,
+                    }
+                });
+                on_done(cassette.record(recorded_request, recorded).and(result));
+            }),
+        );
+    }
+
+    #[cfg(feature = "streaming")]
+    fn fetch_streaming(
+        &self,
+        request: Request,
+        handle: RequestHandle,
+        on_data: Box<dyn Fn(Result<Part>) -> ControlFlow<()> + Send>,
+    ) {
+        if !self.recording {
+            let response = match self.replay(&request) {
+                Ok(response) => response,
+                Err(err) => {
+                    let _ = on_data(Err(err));
+                    return;
+                }
+            };
+            if on_data(Ok(Part::Response(response.partial))).is_break() {
+                return;
+            }
+            for chunk in response.chunks {
+                if handle.is_aborted() {
+                    let _ = on_data(Err(Error::Aborted));
+                    return;
+                }
+                if !chunk.is_empty() && on_data(Ok(Part::Chunk(chunk))).is_break() {
+                    return;
+                }
+            }
+            let _ = on_data(match response.error {
+                Some(err) => Err(err),
+                None => Ok(Part::Chunk(vec![])),
+            });
+            return;
+        }
+
+        // The response so far, until it is recorded:
+        let recording = Mutex::new(Some((self.recorded_request(&request), None)));
+        let cassette = self.clone();
+        self.transport.fetch_streaming(
+            request,
+            handle,
+            Box::new(move |part| {
+                let mut recording = recording.lock().unwrap();
+                let mut finished = None;
+                if let Some((_, response)) = recording.as_mut() {
+                    match &part {
+                        Ok(Part::Response(partial)) => {
+                            *response = Some(RecordedResponse {
+                                partial: partial.clone(),
+                                chunks: vec![],
+                                error: None,
+                            });
+                        }
+                        Ok(Part::Chunk(chunk)) if chunk.is_empty() => {
+                            finished = response.take().map(Ok);
+                        }
+                        Ok(Part::Chunk(chunk)) => {
+                            if let Some(response) = response {
+                                response.chunks.push(chunk.clone());
+                            }
+                        }
+                        Err(err) => {
+                            finished = Some(match response.take() {
+                                // In the middle of the body:
+                                Some(mut response) => {
+                                    response.error = Some(err.clone());
+                                    Ok(response)
+                                }
+                                None => Err(err.clone()),
+                            });
+                        }
+                    }
+                }
+
+                if let Some(result) = finished {
+                    if let Some((request, _)) = recording.take() {
+                        if let Err(err) = cassette.record(request, result) {
+                            return on_data(Err(err));
+                        }
+                    }
+                }
+
+                let flow = on_data(part);
+                if flow.is_break() {
+                    // Record what we have, so that the replay can be stopped at the same point:
+                    if let Some((request, Some(response))) = recording.take() {
+                        cassette.record(request, Ok(response)).ok();
+                    }
+                }
+                flow
+            }),
+        );
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+fn partial_response(response: Response) -> PartialResponse {
+    let Response {
+        url,
+        ok,
+        status,
+        status_text,
+        headers,
+        redirects,
+        bytes: _,
+    } = response;
+    PartialResponse {
+        url,
+        ok,
+        status,
+        status_text,
+        headers,
+        redirects,
+    }
+}
+
+struct Exchange {
+    request: RecordedRequest,
+    result: Result<RecordedResponse>,
+
+    /// Each recorded exchange is only replayed once.
+    replayed: bool,
+}
+
+struct RecordedRequest {
+    method: Method,
+    url: String,
+    headers: Headers,
+
+    /// `None` for a streamed [`crate::Body`].
+    body: Option<Vec<u8>>,
+}
+
+#[derive(Clone)]
+struct RecordedResponse {
+    partial: PartialResponse,
+    chunks: Vec<Vec<u8>>,
+
+    /// The error that ended a streamed body early, after the `chunks`.
+    error: Option<Error>,
+}
+
+impl Exchange {
+    fn to_json(&self) -> Value {
+        let request = &self.request;
+        let mut json = json!({
+            "request": {
+                "method": request.method.as_str(),
+                "url": request.url,
+                "headers": headers_to_json(&request.headers),
+                "body": request.body.as_deref().map(body_to_json),
+            },
+        });
+        json[match &self.result {
+            Ok(_) => "response",
+            Err(_) => "error",
+        }] = match &self.result {
+            Ok(response) => {
+                let partial = &response.partial;
+                let mut json = json!({
+                    "url": partial.url,
+                    "status": partial.status,
+                    "status_text": partial.status_text,
+                    "headers": headers_to_json(&partial.headers),
+                    "redirects": partial.redirects.iter().map(|redirect| json!({
+                        "url": redirect.url,
+                        "status": redirect.status,
+                        "location": redirect.location,
+                    })).collect::<Vec<_>>(),
+                    "chunks": response.chunks.iter().map(|chunk| body_to_json(chunk)).collect::<Vec<_>>(),
+                });
+                if let Some(err) = &response.error {
+                    json["error"] = error_to_json(err);
+                }
+                json
+            }
+            Err(err) => error_to_json(err),
+        };
+        json
+    }
+
+    fn from_json(json: &Value) -> std::result::Result<Self, String> {
+        let request = &json["request"];
+        let request = RecordedRequest {
+            method: Method::parse(str_field(request, "method")?).map_err(|err| err.to_string())?,
+            url: str_field(request, "url")?.to_owned(),
+            headers: headers_from_json(&request["headers"])?,
+            body: match &request["body"] {
+                Value::Null => None,
+                body => Some(body_from_json(body)?),
+            },
+        };
+
+        let result = if let Some(response) = json.get("response") {
+            let status = status_field(response)?;
+            let redirects = array_field(response, "redirects")?
+                .iter()
+                .map(|redirect| {
+                    Ok(Redirect {
+                        url: str_field(redirect, "url")?.to_owned(),
+                        status: status_field(redirect)?,
+                        location: str_field(redirect, "location")?.to_owned(),
+                    })
+                })
+                .collect::<std::result::Result<_, String>>()?;
+            Ok(RecordedResponse {
+                partial: PartialResponse {
+                    url: str_field(response, "url")?.to_owned(),
+                    ok: (200..300).contains(&status),
+                    status,
+                    status_text: str_field(response, "status_text")?.to_owned(),
+                    headers: headers_from_json(&response["headers"])?,
+                    redirects,
+                },
+                chunks: array_field(response, "chunks")?
+                    .iter()
+                    .map(body_from_json)
+                    .collect::<std::result::Result<_, _>>()?,
+                error: match response.get("error") {
+                    Some(error) => Some(error_from_json(error)?),
+                    None => None,
+                },
+            })
+        } else {
+            Err(error_from_json(&json["error"])?)
+        };
+
+        Ok(Self {
+            request,
+            result,
+            replayed: false,
+        })
+    }
+}
+
+fn parse_cassette(json: &str) -> std::result::Result<Vec<Exchange>, String> {
+    let json: Value = serde_json::from_str(json).map_err(|err| err.to_string())?;
+    array_field(&json, "exchanges")?
+        .iter()
+        .map(Exchange::from_json)
+        .collect()
+}
+
+fn str_field<'a>(json: &'a Value, key: &str) -> std::result::Result<&'a str, String> {
+    json[key]
+        .as_str()
+        .ok_or_else(|| format!("Missing string {key:?}"))
+}
+
+fn status_field(json: &Value) -> std::result::Result<u16, String> {
+    json["status"]
+        .as_u64()
+        .and_then(|status| u16::try_from(status).ok())
+        .ok_or_else(|| "Missing status".to_owned())
+}
+
+fn array_field<'a>(json: &'a Value, key: &str) -> std::result::Result<&'a [Value], String> {
+    json[key]
+        .as_array()
+        .map(Vec::as_slice)
+        .ok_or_else(|| format!("Missing array {key:?}"))
+}
+
+fn headers_to_json(headers: &Headers) -> Value {
+    headers
+        .into_iter()
+        .map(|(key, value)| json!([key, value]))
+        .collect()
+}
+
+fn headers_from_json(json: &Value) -> std::result::Result<Headers, String> {
+    let mut headers = Headers::default();
+    for pair in json.as_array().ok_or("Invalid headers")? {
+        match pair.as_array().map(Vec::as_slice) {
+            Some([Value::String(key), Value::String(value)]) => headers.insert(key, value),
+            _ => return Err(format!("Invalid header {pair}")),
+        }
+    }
+    Ok(headers)
+}
+
+/// Text if possible, so that the cassette is easy to read (and edit).
+fn body_to_json(bytes: &[u8]) -> Value {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => json!(text),
+        Err(_) => json!({ "base64": base64::engine::general_purpose::STANDARD.encode(bytes) }),
+    }
+}
+
+fn body_from_json(json: &Value) -> std::result::Result<Vec<u8>, String> {
+    if let Some(text) = json.as_str() {
+        return Ok(text.as_bytes().to_vec());
+    }
+    let base64 = str_field(json, "base64")?;
+    base64::engine::general_purpose::STANDARD
+        .decode(base64)
+        .map_err(|err| err.to_string())
+}
+
+fn error_to_json(err: &Error) -> Value {
+    let (kind, message) = match err {
+        Error::Timeout(message) => ("Timeout", message),
+        Error::Connect(message) => ("Connect", message),
+        Error::Dns(message) => ("Dns", message),
+        Error::Certificate(message) => ("Certificate", message),
+        Error::Tls(message) => ("Tls", message),
+        Error::InvalidUrl(message) => ("InvalidUrl", message),
+        Error::BodyRead(message) => ("BodyRead", message),
+        Error::Network(message) => ("Network", message),
+        Error::Other(message) => ("Other", message),
+        Error::Status(err) => {
+            return json!({
+                "kind": "Status",
+                "url": err.url,
+                "status": err.status.as_u16(),
+                "status_text": err.status_text,
+                "headers": headers_to_json(&err.headers),
+                "body": err.body,
+            });
+        }
+        Error::Aborted => unreachable!("Aborted requests are not recorded"),
+    };
+    json!({ "kind": kind, "message": message })
+}
+
+fn error_from_json(json: &Value) -> std::result::Result<Error, String> {
+    let kind = str_field(json, "kind")?;
+    if kind == "Status" {
+        return Ok(Error::Status(Box::new(crate::StatusError {
+            url: str_field(json, "url")?.to_owned(),
+            status: status_field(json)?.into(),
+            status_text: str_field(json, "status_text")?.to_owned(),
+            headers: headers_from_json(&json["headers"])?,
+            body: str_field(json, "body")?.to_owned(),
+        })));
+    }
+
+    let message = str_field(json, "message")?.to_owned();
+    Ok(match kind {
+        "Timeout" => Error::Timeout(message),
+        "Connect" => Error::Connect(message),
+        "Dns" => Error::Dns(message),
+        "Certificate" => Error::Certificate(message),
+        "Tls" => Error::Tls(message),
+        "InvalidUrl" => Error::InvalidUrl(message),
+        "BodyRead" => Error::BodyRead(message),
+        "Network" => Error::Network(message),
+        "Other" => Error::Other(message),
+        kind => return Err(format!("Unknown error kind {kind:?}")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::{Client, StatusCode, StatusError};
+
+    /// Answers a few fixed URLs, and counts the requests.
+    #[derive(Clone, Default)]
+    struct Server {
+        requests: Arc<AtomicUsize>,
+    }
+
+    impl Server {
+        fn respond(&self, request: &Request) -> Result<Response> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            let response = |status: u16, headers: &[(&str, &str)], bytes: &[u8]| {
+                Ok(PartialResponse {
+                    url: request.url.clone(),
+                    ok: (200..300).contains(&status),
+                    status,
+                    status_text: StatusCode::from(status)
+                        .canonical_reason()
+                        .unwrap_or_default()
+                        .to_owned(),
+                    headers: Headers::new(headers),
+                    redirects: vec![],
+                }
+                .complete(bytes.to_vec()))
+            };
+            match request.url.as_str() {
+                "https://a.com/text" => {
+                    let mut response = response(
+                        200,
+                        &[("content-type", "text/plain"), ("set-cookie", "session=1")],
+                        "héllo\n".as_bytes(),
+                    )?;
+                    response.redirects.push(Redirect {
+                        url: "https://a.com/old".to_owned(),
+                        status: 301,
+                        location: "https://a.com/text".to_owned(),
+                    });
+                    Ok(response)
+                }
+                "https://a.com/binary" => response(200, &[], &[0, 159, 146, 150, 255]),
+                // Only the start of the body, see `fetch_streaming`:
+                "https://a.com/broken" => response(200, &[], b"start"),
+                "https://a.com/echo" => response(200, &[], request.body.as_bytes().unwrap()),
+                "https://a.com/missing" => Err(Error::Status(Box::new(StatusError {
+                    url: request.url.clone(),
+                    status: StatusCode::from(404),
+                    status_text: "Not Found".to_owned(),
+                    headers: Headers::new(&[("content-type", "text/plain")]),
+                    body: "not here".to_owned(),
+                }))),
+                _ => Err(Error::Dns(format!("No such host: {}", request.url))),
+            }
+        }
+    }
+
+    impl Transport for Server {
+        fn fetch(
+            &self,
+            request: Request,
+            _handle: RequestHandle,
+            on_done: Box<dyn FnOnce(Result<Response>) + Send>,
+        ) {
+            on_done(self.respond(&request));
+        }
+
+        #[cfg(feature = "streaming")]
+        fn fetch_streaming(
+            &self,
+            request: Request,
+            _handle: RequestHandle,
+            on_data: Box<dyn Fn(Result<Part>) -> ControlFlow<()> + Send>,
+        ) {
+            let response = match self.respond(&request) {
+                Ok(response) => response,
+                Err(err) => {
+                    let _ = on_data(Err(err));
+                    return;
+                }
+            };
+            let bytes = response.bytes.clone();
+            let _ = on_data(Ok(Part::Response(partial_response(response))));
+            for chunk in bytes.chunks(2) {
+                if on_data(Ok(Part::Chunk(chunk.to_vec()))).is_break() {
+                    return;
+                }
+            }
+            let _ = on_data(if request.url == "https://a.com/broken" {
+                Err(Error::BodyRead("Connection reset".to_owned()))
+            } else {
+                Ok(Part::Chunk(vec![]))
+            });
+        }
+    }
+
+    /// A path in the temporary directory, which is removed when dropped.
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "ehttp-cassette-test-{}/{name}.json",
+                std::process::id()
+            ));
+            let _ = std::fs::remove_file(&path);
+            Self(path)
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn requests() -> Vec<Request> {
+        vec![
+            Request::get("https://a.com/text").with_header("Authorization", "Bearer secret"),
+            Request::get("https://a.com/binary"),
+            Request::post("https://a.com/echo", b"first".to_vec()),
+            Request::post("https://a.com/echo", b"second".to_vec()),
+            Request::get("https://a.com/missing"),
+            Request::get("https://nowhere.invalid/"),
+        ]
+    }
+
+    /// Everything but the redacted headers.
+    fn describe(result: &Result<Response>) -> String {
+        match result {
+            Ok(response) => {
+                let headers: Vec<_> = response
+                    .headers
+                    .headers
+                    .iter()
+                    .filter(|(key, _)| key != "set-cookie")
+                    .collect();
+                format!(
+                    "{} {} {} {:?} {:?} {:?} {:?}",
+                    response.url,
+                    response.ok,
+                    response.status,
+                    response.status_text,
+                    headers,
+                    response.redirects,
+                    response.bytes,
+                )
+            }
+            Err(err) => format!("{err:?}"),
+        }
+    }
+
+    /// Record the requests with a new cassette at the path.
+    fn record(path: &TempPath, server: &Server, requests: &[Request]) -> Vec<Result<Response>> {
+        let cassette = Cassette::open(&path.0, Mode::Auto)
+            .unwrap()
+            .with_transport(server.clone());
+        assert!(cassette.is_recording());
+        let client = Client::new().with_transport(cassette);
+        requests
+            .iter()
+            .map(|request| client.fetch_blocking(request))
+            .collect()
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let path = TempPath::new("record_and_replay");
+        let server = Server::default();
+        let recorded = record(&path, &server, &requests());
+        assert_eq!(server.requests.load(Ordering::SeqCst), 6);
+
+        let json = std::fs::read_to_string(&path.0).unwrap();
+        assert!(!json.contains("secret"), "{}", json);
+        assert!(!json.contains("session=1"), "{}", json);
+        assert!(json.contains("héllo"), "{}", json);
+        assert!(json.contains(r#""base64": "AJ+Slv8=""#), "{}", json);
+
+        let cassette = Cassette::open(&path.0, Mode::Auto)
+            .unwrap()
+            .with_transport(server.clone());
+        assert!(!cassette.is_recording());
+        let client = Client::new().with_transport(cassette);
+        for (request, recorded) in requests().iter().zip(&recorded) {
+            let replayed = client.fetch_blocking(request);
+            assert_eq!(describe(&replayed), describe(recorded));
+        }
+        assert_eq!(server.requests.load(Ordering::SeqCst), 6);
+
+        // Each exchange is replayed only once:
+        let result = client.fetch_blocking(&requests()[0]);
+        assert!(matches!(result, Err(Error::Other(_))), "{:?}", result.ok());
+
+        let client = Client::new().with_transport(Cassette::open(&path.0, Mode::Replay).unwrap());
+        let response = client.fetch_blocking(&requests()[0]).unwrap();
+        assert_eq!(response.headers.get("set-cookie"), Some("[REDACTED]"));
+    }
+
+    #[test]
+    fn match_rules() {
+        let path = TempPath::new("match_rules");
+        let server = Server::default();
+        record(&path, &server, &requests());
+
+        let second = Request::post("https://a.com/echo", b"second".to_vec());
+        let replay = |match_rules: MatchRules, request: &Request| {
+            let cassette = Cassette::open(&path.0, Mode::Replay)
+                .unwrap()
+                .with_match_rules(match_rules);
+            Client::new()
+                .with_transport(cassette)
+                .fetch_blocking(request)
+                .map(|response| response.bytes)
+        };
+
+        assert_eq!(replay(MatchRules::default(), &second).unwrap(), b"first");
+        let by_body = MatchRules {
+            body: true,
+            ..MatchRules::default()
+        };
+        assert_eq!(replay(by_body.clone(), &second).unwrap(), b"second");
+        let third = Request::post("https://a.com/echo", b"third".to_vec());
+        assert!(replay(by_body, &third).is_err());
+
+        let any_url = MatchRules {
+            url: false,
+            ..MatchRules::default()
+        };
+        let other_url = Request::get("https://a.com/other");
+        assert_eq!(replay(any_url, &other_url).unwrap(), "héllo\n".as_bytes());
+
+        // Redacted headers only match on whether they are there:
+        let by_header = MatchRules {
+            headers: vec!["authorization".to_owned()],
+            ..MatchRules::default()
+        };
+        let request = Request::get("https://a.com/text").with_header("Authorization", "other");
+        assert!(replay(by_header.clone(), &request).is_ok());
+        assert!(replay(by_header, &Request::get("https://a.com/text")).is_err());
+    }
+
+    /// Stream the response to `url` through the cassette, and return the status, the chunks and any error.
+    #[cfg(feature = "streaming")]
+    fn fetch_parts(cassette: Cassette, url: &str) -> Vec<Vec<u8>> {
+        let parts = Arc::new(Mutex::new(vec![]));
+        Client::new()
+            .with_transport(cassette)
+            .fetch_streaming_blocking(
+                Request::get(url),
+                Box::new({
+                    let parts = parts.clone();
+                    move |part| {
+                        parts.lock().unwrap().push(match part {
+                            Ok(Part::Response(response)) => {
+                                response.status.to_string().into_bytes()
+                            }
+                            Ok(Part::Chunk(chunk)) => chunk,
+                            Err(err) => err.to_string().into_bytes(),
+                        });
+                        ControlFlow::Continue(())
+                    }
+                }),
+            );
+        let parts = parts.lock().unwrap().clone();
+        parts
+    }
+
+    #[cfg(feature = "streaming")]
+    #[test]
+    fn record_and_replay_streams() {
+        let fetch = |cassette| fetch_parts(cassette, "https://a.com/text");
+
+        let path = TempPath::new("record_and_replay_streams");
+        let server = Server::default();
+        let recording = Cassette::open(&path.0, Mode::Record)
+            .unwrap()
+            .with_transport(server.clone());
+        let recorded = fetch(recording);
+        // The chunks split the "é" in two, so they are stored as base64:
+        let expected: [&[u8]; 6] = [b"200", b"h\xc3", b"\xa9l", b"lo", b"\n", b""];
+        assert_eq!(recorded, expected);
+        let json = std::fs::read_to_string(&path.0).unwrap();
+        assert!(json.contains(r#""base64": "aMM=""#), "{}", json);
+
+        let replaying = Cassette::open(&path.0, Mode::Replay).unwrap();
+        assert_eq!(fetch(replaying), recorded);
+        assert_eq!(server.requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[cfg(feature = "streaming")]
+    #[test]
+    fn errors_in_the_middle_of_streams_are_replayed() {
+        let url = "https://a.com/broken";
+        let path = TempPath::new("errors_in_the_middle_of_streams_are_replayed");
+        let recording = Cassette::open(&path.0, Mode::Record)
+            .unwrap()
+            .with_transport(Server::default());
+        let recorded = fetch_parts(recording, url);
+        let error = Error::BodyRead("Connection reset".to_owned()).to_string();
+        let expected: [&[u8]; 5] = [b"200", b"st", b"ar", b"t", error.as_bytes()];
+        assert_eq!(recorded, expected);
+
+        assert_eq!(
+            fetch_parts(Cassette::open(&path.0, Mode::Replay).unwrap(), url),
+            recorded
+        );
+
+        // Without streaming, the whole request fails:
+        let client = Client::new().with_transport(Cassette::open(&path.0, Mode::Replay).unwrap());
+        let result = client.fetch_blocking(&Request::get(url));
+        assert!(
+            matches!(result, Err(Error::BodyRead(_))),
+            "{:?}",
+            result.ok()
+        );
+    }
+
+    #[cfg(feature = "streaming")]
+    #[test]
+    fn replayed_streams_can_be_aborted() {
+        let url = "https://a.com/text";
+        let path = TempPath::new("replayed_streams_can_be_aborted");
+        let recording = Cassette::open(&path.0, Mode::Record)
+            .unwrap()
+            .with_transport(Server::default());
+        fetch_parts(recording, url);
+
+        let handle = RequestHandle::default();
+        let parts = Arc::new(Mutex::new(vec![]));
+        Cassette::open(&path.0, Mode::Replay)
+            .unwrap()
+            .fetch_streaming(
+                Request::get(url),
+                handle.clone(),
+                Box::new({
+                    let parts = parts.clone();
+                    move |part| {
+                        if matches!(part, Ok(Part::Chunk(_))) {
+                            handle.abort();
+                        }
+                        parts.lock().unwrap().push(part.map(|_| ()));
+                        ControlFlow::Continue(())
+                    }
+                }),
+            );
+        let parts = parts.lock().unwrap();
+        assert_eq!(parts.len(), 3, "the response, one chunk and the abort");
+        assert!(matches!(parts[2], Err(Error::Aborted)), "{:?}", parts[2]);
+    }
+
+    #[test]
+    fn replaying_needs_a_cassette() {
+        let path = TempPath::new("replaying_needs_a_cassette");
+        let err = Cassette::open(&path.0, Mode::Replay).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        std::fs::create_dir_all(path.0.parent().unwrap()).unwrap();
+        std::fs::write(&path.0, r#"{"exchanges": [{"request": {}}]}"#).unwrap();
+        let err = Cassette::open(&path.0, Mode::Auto).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,732 @@
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    interceptor::Interceptors, Headers, Interceptor, Request, RequestHandle, Response, Result,
+    RetryPolicy, Transport,
+};
+
+#[cfg(feature = "streaming")]
+use std::ops::ControlFlow;
+
+/// A reusable HTTP client with shared configuration.
+///
+/// The free functions ([`crate::fetch`] etc) all use a shared default client.
+/// Create your own client if you want a base URL, default headers, a custom User-Agent or a different default timeout:
+///
+/// ```
+/// let client = ehttp::Client::new()
+///     .with_base_url("https://www.example.com/api/")
+///     .with_header("Authorization", "Bearer my-secret-token")
+///     .with_user_agent("my-app/1.0");
+///
+/// client.fetch(ehttp::Request::get("users"), move |result: ehttp::Result<ehttp::Response>| {
+///     println!("Status code: {:?}", result.unwrap().status);
+/// });
+/// ```
+///
+/// On native, a client keeps connections alive between requests,
+/// so you save a TCP and TLS handshake for every request to the same host.
+///
+/// Cloning a client is cheap, and the clones share the same connection pool.
+#[derive(Clone)]
+pub struct Client {
+    base_url: Option<String>,
+    headers: Headers,
+    user_agent: Option<String>,
+    timeout: Option<Duration>,
+    retry: Option<RetryPolicy>,
+    interceptors: Vec<Arc<dyn Interceptor>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    proxy: Option<crate::Proxy>,
+    #[cfg(not(target_arch = "wasm32"))]
+    tls: Option<crate::TlsConfig>,
+    backend: Backend,
+
+    /// Replaces [`Self::backend`], if set.
+    transport: Option<Arc<dyn Transport>>,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for Client {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut debug = f.debug_struct("Client");
+        debug
+            .field("base_url", &self.base_url)
+            .field("headers", &self.headers)
+            .field("user_agent", &self.user_agent)
+            .field("timeout", &self.timeout)
+            .field("retry", &self.retry)
+            .field("interceptors", &self.interceptors.len());
+        #[cfg(not(target_arch = "wasm32"))]
+        debug.field("proxy", &self.proxy).field("tls", &self.tls);
+        debug
+            .field("cache", &self.backend.cache.is_some())
+            .field("custom_transport", &self.transport.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Client {
+    /// Create a client without a base URL or default headers,
+    /// and with [`Request::DEFAULT_TIMEOUT`].
+    pub fn new() -> Self {
+        Self {
+            base_url: None,
+            headers: Headers::default(),
+            user_agent: None,
+            timeout: Some(Request::DEFAULT_TIMEOUT),
+            retry: None,
+            interceptors: Vec::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            proxy: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            tls: None,
+            backend: Backend {
+                #[cfg(not(target_arch = "wasm32"))]
+                // We follow redirects ourselves, see `Request::redirect_policy`:
+                agent: ureq::Agent::config_builder()
+                    .max_redirects(0)
+                    .build()
+                    .into(),
+                #[cfg(not(target_arch = "wasm32"))]
+                cookie_jar: None,
+                cache: None,
+                #[cfg(not(target_arch = "wasm32"))]
+                executor: crate::Executor::default(),
+            },
+            transport: None,
+        }
+    }
+
+    /// Relative request URLs (e.g. `"users/42"`) are resolved against this.
+    ///
+    /// Request URLs that start with a scheme and `://` (e.g. `"https://…"`) are left as-is.
+    /// Anything else is relative, even if it looks like a scheme, e.g. `"users:42"` or `"localhost:8080/x"`.
+    ///
+    /// Unlike [`crate::Url::join`], this appends the request URL to the base URL,
+    /// so with the base URL `https://example.com/api/v1`,
+    /// both `users` and `/users` become `https://example.com/api/v1/users`.
+    pub fn with_base_url(mut self, base_url: impl ToString) -> Self {
+        self.base_url = Some(base_url.to_string());
+        self
+    }
+
+    /// Add a header that is sent with every request,
+    /// unless the request already has a header with the same key.
+    pub fn with_header(mut self, key: impl ToString, value: impl ToString) -> Self {
+        self.headers.insert(key, value);
+        self
+    }
+
+    /// Replace all default headers.
+    ///
+    /// See also [`Self::with_header`].
+    pub fn with_headers(mut self, headers: Headers) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Set the `User-Agent` header, unless the request already has one.
+    ///
+    /// Note that browsers may ignore this.
+    pub fn with_user_agent(mut self, user_agent: impl ToString) -> Self {
+        self.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    /// Set the timeout for requests, or `None` to disable it.
+    ///
+    /// This is used for requests that still have the default [`Request::timeout`]
+    /// (i.e. [`Request::DEFAULT_TIMEOUT`]).
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Retry failed requests according to the given policy.
+    ///
+    /// This is used for requests that don't have their own [`Request::retry`].
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Cache responses according to their `Cache-Control` etc headers.
+    ///
+    /// See [`crate::cache`] for details.
+    pub fn with_cache(mut self, cache: impl crate::cache::Cache + 'static) -> Self {
+        self.backend.cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// Store the cookies the server sets, and send them with later requests.
+    ///
+    /// See [`crate::cookies`] for details.
+    ///
+    /// Only available when compiling for native. On web, the browser handles cookies.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_cookie_jar(mut self, cookie_jar: Arc<crate::cookies::CookieJar>) -> Self {
+        self.backend.cookie_jar = Some(cookie_jar);
+        self
+    }
+
+    /// Send requests through the given proxy, unless they have their own [`Request::proxy`].
+    ///
+    /// See [`crate::Proxy`] for details.
+    ///
+    /// Only available when compiling for native.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_proxy(mut self, proxy: crate::Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Use the given TLS options, unless a request has its own [`Request::tls`].
+    ///
+    /// See [`crate::TlsConfig`] for details.
+    ///
+    /// Only available when compiling for native.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_tls(mut self, tls: crate::TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Decide how requests are run in the background.
+    ///
+    /// Streaming requests don't use the executor, see [`crate::Executor`].
+    ///
+    /// Only available when compiling for native.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_executor(mut self, executor: crate::Executor) -> Self {
+        self.backend.executor = executor;
+        self
+    }
+
+    /// Perform requests with the given [`Transport`] instead of the default one.
+    ///
+    /// Useful for faking the network in tests.
+    /// This takes precedence over [`crate::set_global_transport`].
+    /// Note that the cache, cookie jar and executor of the client are only used by the default transport.
+    pub fn with_transport(mut self, transport: impl Transport + 'static) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// The transport set with [`Self::with_transport`], or else with [`crate::set_global_transport`], if any.
+    fn custom_transport(&self) -> Option<Arc<dyn Transport>> {
+        self.transport
+            .clone()
+            .or_else(crate::transport::global_transport)
+    }
+
+    fn transport(&self) -> Arc<dyn Transport> {
+        self.custom_transport()
+            .unwrap_or_else(|| Arc::new(self.backend.clone()))
+    }
+
+    /// Apply the configuration of the client to the request.
+    pub(crate) fn prepare(&self, mut request: Request) -> Request {
+        if let Some(base_url) = &self.base_url {
+            request.url = join_url(base_url, &request.url);
+        }
+
+        let has_header = |key: &str| request.headers.get(key).is_some();
+        let mut missing_headers = Headers::default();
+        for (key, value) in &self.headers {
+            if !has_header(key) {
+                missing_headers.insert(key, value);
+            }
+        }
+        if let Some(user_agent) = &self.user_agent {
+            if !has_header("User-Agent") {
+                missing_headers.insert("User-Agent", user_agent);
+            }
+        }
+        request.headers.headers.extend(missing_headers);
+
+        if request.timeout == Some(Request::DEFAULT_TIMEOUT) {
+            request.timeout = self.timeout;
+        }
+
+        if request.retry.is_none() {
+            request.retry = self.retry.clone();
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if request.proxy.is_none() {
+            request.proxy = self.proxy.clone();
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if request.tls.is_none() {
+            request.tls = self.tls.clone();
+        }
+
+        request
+    }
+
+    /// Register an [`Interceptor`] for all requests made with this client.
+    ///
+    /// Interceptors see requests in the order they were added, and responses in the reverse order.
+    /// Interceptors registered with [`crate::add_global_interceptor`] come before those of the client.
+    pub fn with_interceptor(mut self, interceptor: impl Interceptor + 'static) -> Self {
+        self.interceptors.push(Arc::new(interceptor));
+        self
+    }
+
+    /// Applies the configuration of the client and the [`Interceptor`]s to the request.
+    ///
+    /// Also catches bad URLs, before anything is spawned.
+    /// The URL is checked again after the interceptors, as they may have changed it.
+    fn start(&self, request: Request) -> (Interceptors, Result<Request>) {
+        let mut request = self.prepare(request);
+        let interceptors = Interceptors::new(&self.interceptors);
+        let result = crate::url::normalize(&request.url)
+            .and_then(|url| {
+                request.url = url;
+                interceptors.on_request(&mut request)
+            })
+            .and_then(|()| crate::url::normalize(&request.url))
+            .map(|url| {
+                request.url = url;
+                request
+            });
+        (interceptors, result)
+    }
+
+    /// Performs an HTTP request and calls the given callback when done.
+    ///
+    /// See [`crate::fetch`].
+    pub fn fetch(
+        &self,
+        request: Request,
+        on_done: impl 'static + Send + FnOnce(Result<Response>),
+    ) -> RequestHandle {
+        let handle = RequestHandle::default();
+
+        let (interceptors, request) = self.start(request);
+        let request = match request {
+            Ok(request) => request,
+            Err(err) => {
+                on_done(Err(err));
+                return handle;
+            }
+        };
+        let on_done = interceptors.wrap_on_done(&request, Box::new(on_done));
+        self.transport().fetch(request, handle.clone(), on_done);
+        handle
+    }
+
+    /// Performs an `async` HTTP request.
+    ///
+    /// See [`crate::fetch_async`].
+    #[cfg(any(target_arch = "wasm32", feature = "native-async"))]
+    pub async fn fetch_async(&self, request: Request) -> Result<Response> {
+        self.fetch_async_with_handle(request).1.await
+    }
+
+    /// Like [`Self::fetch_async`], but also returns a [`RequestHandle`] that can be used to abort the request.
+    ///
+    /// See [`crate::fetch_async_with_handle`].
+    #[cfg(any(target_arch = "wasm32", feature = "native-async"))]
+    pub fn fetch_async_with_handle(
+        &self,
+        request: Request,
+    ) -> (
+        RequestHandle,
+        impl std::future::Future<Output = Result<Response>>,
+    ) {
+        let handle = RequestHandle::default();
+        let (interceptors, request) = self.start(request);
+        let future = {
+            let handle = handle.clone();
+            let backend = self.backend.clone();
+            let transport = self.custom_transport();
+            async move {
+                let request = request?;
+                let sent_request = (!interceptors.is_empty()).then(|| request.clone());
+
+                let mut result = if let Some(transport) = transport {
+                    crate::transport::fetch_async(transport, request, handle).await
+                } else {
+                    #[cfg(not(target_arch = "wasm32"))]
+                    {
+                        let executor = backend.executor.clone();
+                        crate::native::fetch_async(&executor, backend, request, handle).await
+                    }
+
+                    #[cfg(target_arch = "wasm32")]
+                    {
+                        crate::web::fetch_async_with_handle(&backend, &request, &handle).await
+                    }
+                };
+
+                if let Some(sent_request) = sent_request {
+                    interceptors.on_response(&sent_request, &mut result);
+                }
+                result
+            }
+        };
+        (handle, future)
+    }
+
+    /// Performs an HTTP request and blocks the thread until it is done.
+    ///
+    /// See [`crate::fetch_blocking`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn fetch_blocking(&self, request: &Request) -> Result<Response> {
+        let (interceptors, request) = self.start(request.clone());
+        let request = request?;
+        let mut result = match self.custom_transport() {
+            Some(transport) => crate::transport::fetch_blocking(
+                &*transport,
+                request.clone(),
+                RequestHandle::default(),
+            ),
+            None => crate::native::fetch_blocking_with(
+                &self.backend,
+                &request,
+                &RequestHandle::default(),
+            ),
+        };
+        interceptors.on_response(&request, &mut result);
+        result
+    }
+
+    /// Performs an HTTP request and calls the given callback once for the initial response,
+    /// and then once for each chunk in the response body.
+    ///
+    /// On native, the request runs on a thread of its own rather than on the [`crate::Executor`],
+    /// since a stream can stay open for a long time.
+    ///
+    /// See [`crate::streaming::fetch`].
+    #[cfg(feature = "streaming")]
+    pub fn fetch_streaming(
+        &self,
+        request: Request,
+        on_data: impl 'static + Send + Fn(Result<crate::streaming::Part>) -> ControlFlow<()>,
+    ) -> RequestHandle {
+        let handle = RequestHandle::default();
+        self.fetch_streaming_with_handle(request, handle.clone(), on_data);
+        handle
+    }
+
+    /// Like [`Self::fetch_streaming`], but the request can be aborted with the given handle.
+    #[cfg(feature = "streaming")]
+    pub(crate) fn fetch_streaming_with_handle(
+        &self,
+        request: Request,
+        handle: RequestHandle,
+        on_data: impl 'static + Send + Fn(Result<crate::streaming::Part>) -> ControlFlow<()>,
+    ) {
+        let (interceptors, request) = self.start(request);
+        let on_data = match &request {
+            Ok(request) => interceptors.wrap_on_data(request, Box::new(on_data)),
+            Err(_) => Box::new(on_data),
+        };
+        let request = match request {
+            Ok(request) => request,
+            Err(err) => {
+                let _ = on_data(Err(err));
+                return;
+            }
+        };
+        self.transport().fetch_streaming(request, handle, on_data);
+    }
+
+    /// Performs a streaming HTTP request, and calls the given callback with each line of the response, parsed as `T`.
+    ///
+    /// See [`crate::ndjson::fetch`].
+    #[cfg(all(feature = "json", feature = "streaming"))]
+    pub fn fetch_ndjson<T: serde::de::DeserializeOwned + 'static>(
+        &self,
+        request: Request,
+        on_part: impl 'static + Send + Fn(Result<crate::ndjson::Part<T>>) -> ControlFlow<()>,
+    ) -> RequestHandle {
+        self.fetch_streaming(request, crate::ndjson::decode(on_part))
+    }
+
+    /// Performs a streaming HTTP request, and returns the lines of the response, parsed as `T`.
+    ///
+    /// See [`crate::ndjson::fetch_stream`].
+    #[cfg(all(feature = "json", feature = "streaming"))]
+    pub fn fetch_ndjson_stream<T: serde::de::DeserializeOwned + Send + 'static>(
+        &self,
+        request: Request,
+    ) -> crate::ndjson::ValueStream<T> {
+        crate::ndjson::ValueStream::fetch(self, request)
+    }
+
+    /// Connects to a stream of Server-Sent Events, and calls the given callback for each event.
+    ///
+    /// See [`crate::sse::fetch`].
+    #[cfg(feature = "streaming")]
+    pub fn fetch_sse(
+        &self,
+        request: Request,
+        on_event: impl 'static + Send + Sync + Fn(Result<crate::sse::Event>) -> ControlFlow<()>,
+    ) -> RequestHandle {
+        crate::sse::connect(
+            self.clone(),
+            request,
+            Box::new(move |event| match event {
+                Some(event) => on_event(event),
+                None => ControlFlow::Break(()),
+            }),
+        )
+    }
+
+    /// Connects to a stream of Server-Sent Events.
+    ///
+    /// See [`crate::sse::fetch_stream`].
+    #[cfg(feature = "streaming")]
+    pub fn fetch_sse_stream(&self, request: Request) -> crate::sse::EventStream {
+        crate::sse::EventStream::connect(self.clone(), request)
+    }
+
+    /// Performs an `async` streaming HTTP request.
+    ///
+    /// See [`crate::streaming::fetch_async_streaming`].
+    #[cfg(all(feature = "streaming", target_arch = "wasm32"))]
+    pub async fn fetch_async_streaming(
+        &self,
+        request: &Request,
+    ) -> Result<impl futures_util::Stream<Item = Result<crate::streaming::Part>>> {
+        use crate::streaming::Part;
+        use futures_util::{future::Either, StreamExt as _};
+
+        let (interceptors, request) = self.start(request.clone());
+        let request = request?;
+
+        let stream = match self.custom_transport() {
+            Some(transport) => crate::transport::fetch_async_streaming(
+                &*transport,
+                request.clone(),
+                RequestHandle::default(),
+            )
+            .await
+            .map(Either::Left),
+            None => crate::streaming::web::fetch_async_streaming_with_handle(
+                &request,
+                &RequestHandle::default(),
+            )
+            .await
+            .map(Either::Right),
+        };
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                let mut result = Err(err);
+                interceptors.on_partial_response(&request, &mut result);
+                // An interceptor may have replaced the error with a response:
+                let response = result?;
+                return Ok(Either::Left(futures_util::stream::once(
+                    futures_util::future::ready(Ok(Part::Response(response))),
+                )));
+            }
+        };
+
+        Ok(Either::Right(stream.map(move |part| match part {
+            Ok(Part::Response(response)) => {
+                let mut result = Ok(response);
+                interceptors.on_partial_response(&request, &mut result);
+                result.map(Part::Response)
+            }
+            part => part,
+        })))
+    }
+
+    /// Performs a streaming HTTP request and blocks the thread until it is done.
+    ///
+    /// See [`crate::streaming::fetch_streaming_blocking`].
+    #[cfg(all(feature = "streaming", not(target_arch = "wasm32")))]
+    pub fn fetch_streaming_blocking(
+        &self,
+        request: Request,
+        on_data: Box<dyn Fn(Result<crate::streaming::Part>) -> ControlFlow<()> + Send>,
+    ) {
+        let (interceptors, request) = self.start(request);
+        let request = match request {
+            Ok(request) => request,
+            Err(err) => {
+                let _ = on_data(Err(err));
+                return;
+            }
+        };
+        let on_data = interceptors.wrap_on_data(&request, on_data);
+        match self.custom_transport() {
+            Some(transport) => crate::transport::fetch_streaming_blocking(
+                &*transport,
+                request,
+                RequestHandle::default(),
+                on_data,
+            ),
+            None => crate::streaming::native::fetch_streaming_blocking_with(
+                &self.backend,
+                request,
+                &RequestHandle::default(),
+                &on_data,
+            ),
+        }
+    }
+}
+
+/// The parts of a [`Client`] that are needed to perform a request.
+#[derive(Clone)]
+pub(crate) struct Backend {
+    /// Keeps connections alive between requests.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub agent: ureq::Agent,
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub cookie_jar: Option<Arc<crate::cookies::CookieJar>>,
+
+    pub cache: Option<Arc<dyn crate::cache::Cache>>,
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub executor: crate::Executor,
+}
+
+/// The default transport, as used by the default client.
+///
+/// Unlike the default client, this ignores [`crate::set_global_transport`].
+#[cfg(all(feature = "cassette", not(target_arch = "wasm32")))]
+pub(crate) fn default_transport() -> Arc<dyn Transport> {
+    Arc::new(default_client().backend.clone())
+}
+
+/// The client used by the free functions, e.g. [`crate::fetch`].
+pub(crate) fn default_client() -> &'static Client {
+    static DEFAULT_CLIENT: std::sync::OnceLock<Client> = std::sync::OnceLock::new();
+    DEFAULT_CLIENT.get_or_init(Client::new)
+}
+
+/// Resolve a relative `url` against `base_url`, see [`Client::with_base_url`].
+fn join_url(base_url: &str, url: &str) -> String {
+    let is_absolute = url.trim().split_once("://").is_some_and(|(scheme, _)| {
+        scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    });
+    if is_absolute || base_url.is_empty() {
+        url.to_owned()
+    } else if url.is_empty() {
+        base_url.to_owned()
+    } else {
+        format!(
+            "{}/{}",
+            base_url.trim_end_matches('/'),
+            url.trim_start_matches('/')
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn join_urls() {
+        let cases = [
+            (
+                "https://example.com/api/v1",
+                "users",
+                "https://example.com/api/v1/users",
+            ),
+            (
+                "https://example.com/api/v1/",
+                "users",
+                "https://example.com/api/v1/users",
+            ),
+            (
+                "https://example.com/api/v1",
+                "/users",
+                "https://example.com/api/v1/users",
+            ),
+            (
+                "https://example.com/api/v1/",
+                "/users/42?x=1",
+                "https://example.com/api/v1/users/42?x=1",
+            ),
+            (
+                "https://example.com/api/v1",
+                "",
+                "https://example.com/api/v1",
+            ),
+            ("", "users", "users"),
+            (
+                "https://example.com/api",
+                "https://other.com/x",
+                "https://other.com/x",
+            ),
+            (
+                "https://example.com/api",
+                "http://other.com",
+                "http://other.com",
+            ),
+            // Not absolute, even though `Url::parse` would take the part before the colon for a scheme:
+            (
+                "https://example.com/api",
+                "users:42",
+                "https://example.com/api/users:42",
+            ),
+            (
+                "https://example.com/api",
+                "localhost:8080/x",
+                "https://example.com/api/localhost:8080/x",
+            ),
+            // Not absolute, even though it contains "://":
+            (
+                "https://example.com/api",
+                "login?next=https://example.com/",
+                "https://example.com/api/login?next=https://example.com/",
+            ),
+        ];
+        for (base_url, url, expected) in cases {
+            assert_eq!(join_url(base_url, url), expected, "{base_url:?} + {url:?}");
+        }
+
+        // `Url::join` resolves like a browser instead:
+        let base_url = crate::Url::parse("https://example.com/api/v1").unwrap();
+        assert_eq!(
+            base_url.join("users").unwrap().as_str(),
+            "https://example.com/api/users"
+        );
+        assert_eq!(
+            base_url.join("/users").unwrap().as_str(),
+            "https://example.com/users"
+        );
+    }
+
+    #[test]
+    fn urls_are_validated_after_interceptors() {
+        struct Rewrite(&'static str);
+
+        impl Interceptor for Rewrite {
+            fn on_request(&self, request: &mut Request) -> Result<()> {
+                request.url = self.0.to_owned();
+                Ok(())
+            }
+        }
+
+        for url in ["file:///etc/passwd", "not a url"] {
+            let client = Client::new().with_interceptor(Rewrite(url));
+            let (_, result) = client.start(Request::get("https://example.com/"));
+            assert!(
+                matches!(result, Err(crate::Error::InvalidUrl(_))),
+                "{:?}",
+                url
+            );
+        }
+
+        let client = Client::new().with_interceptor(Rewrite("HTTPS://Example.com"));
+        let (_, result) = client.start(Request::get("https://example.com/"));
+        assert_eq!(result.unwrap().url, "https://example.com/");
+    }
+}
@@ -0,0 +1,574 @@
+//! A cookie store for native requests.
+//!
+//! On web, the browser takes care of cookies (see `Request::credentials`).
+//! On native, `ehttp` ignores cookies unless you give your [`crate::Client`] a [`CookieJar`]:
+//!
+//! ```
+//! use std::sync::Arc;
+//!
+//! let jar = Arc::new(ehttp::cookies::CookieJar::new());
+//! let client = ehttp::Client::new().with_cookie_jar(jar.clone());
+//!
+//! // … make some requests, then:
+//! # let path = std::env::temp_dir().join("ehttp-doctest-cookies.txt");
+//! jar.save(&path).unwrap();
+//!
+//! // And the next time your app starts:
+//! let jar = Arc::new(ehttp::cookies::CookieJar::load(&path).unwrap());
+//! ```
+//!
+//! Cookies are parsed according to [RFC 6265](https://www.rfc-editor.org/rfc/rfc6265),
+//! except that there is no public suffix list, so it is up to you to only talk to servers you trust.
+//!
+//! Only available when compiling for native.
+
+use std::{
+    path::Path,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The `SameSite` attribute of a [`Cookie`].
+///
+/// It is remembered, but has no effect: a native client has no notion of which site a request comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+/// A cookie, as set by a `Set-Cookie` header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+
+    /// The host (if [`Self::host_only`]) or domain the cookie is sent to, in lowercase.
+    pub domain: String,
+
+    /// Only send the cookie to exactly [`Self::domain`], and not to its subdomains.
+    ///
+    /// This is the case when the `Set-Cookie` header had no `Domain` attribute.
+    pub host_only: bool,
+
+    /// Only send the cookie for requests to this path or below it.
+    pub path: String,
+
+    /// When the cookie expires, or `None` for a session cookie.
+    pub expires: Option<SystemTime>,
+
+    /// Only send the cookie over `https`.
+    pub secure: bool,
+
+    /// Hide the cookie from JavaScript. Has no effect on native.
+    pub http_only: bool,
+
+    pub same_site: Option<SameSite>,
+}
+
+impl Cookie {
+    /// Parse a `Set-Cookie` header value received in a response from `url`.
+    ///
+    /// Returns `None` if the header is invalid, or if `url` is not allowed to set the cookie.
+    pub fn parse(set_cookie: &str, url: &str) -> Option<Self> {
+        let url = UrlParts::parse(url)?;
+
+        let mut attributes = set_cookie.split(';');
+        let (name, value) = attributes.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+
+        let mut cookie = Self {
+            name: name.to_owned(),
+            value: value.trim().to_owned(),
+            domain: url.host.clone(),
+            host_only: true,
+            path: default_path(&url.path),
+            expires: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+        };
+
+        let mut max_age = None;
+        for attribute in attributes {
+            let (key, value) = match attribute.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => (attribute.trim(), ""),
+            };
+            match key.to_ascii_lowercase().as_str() {
+                "expires" => {
+                    if let Some(expires) = crate::http_date::parse(value) {
+                        cookie.expires = Some(expires);
+                    }
+                }
+                "max-age" => {
+                    if let Ok(seconds) = value.parse::<i64>() {
+                        max_age = Some(seconds);
+                    }
+                }
+                "domain" => {
+                    let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                    if !domain.is_empty() {
+                        // Refuse to set cookies for whole top-level domains (e.g. `com`):
+                        if !domain_matches(&url.host, &domain)
+                            || (!domain.contains('.') && domain != url.host)
+                        {
+                            return None;
+                        }
+                        if domain != url.host {
+                            cookie.domain = domain;
+                            cookie.host_only = false;
+                        }
+                    }
+                }
+                "path" => {
+                    if value.starts_with('/') {
+                        cookie.path = value.to_owned();
+                    }
+                }
+                "secure" => cookie.secure = true,
+                "httponly" => cookie.http_only = true,
+                "samesite" => {
+                    cookie.same_site = match value.to_ascii_lowercase().as_str() {
+                        "strict" => Some(SameSite::Strict),
+                        "lax" => Some(SameSite::Lax),
+                        "none" => Some(SameSite::None),
+                        _ => None,
+                    };
+                }
+                _ => {}
+            }
+        }
+
+        // Max-Age takes precedence over Expires:
+        if let Some(max_age) = max_age {
+            let max_age = Duration::from_secs(max_age.max(0) as u64);
+            cookie.expires = Some(saturating_add(crate::http_date::now(), max_age));
+        }
+
+        if cookie.secure && !url.is_secure {
+            // Only secure origins may set secure cookies.
+            return None;
+        }
+
+        Some(cookie)
+    }
+
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    /// Should this cookie be sent with a request to `url`?
+    fn matches(&self, url: &UrlParts) -> bool {
+        let domain_ok = if self.host_only {
+            url.host == self.domain
+        } else {
+            domain_matches(&url.host, &self.domain)
+        };
+        domain_ok && path_matches(&url.path, &self.path) && (url.is_secure || !self.secure)
+    }
+}
+
+/// Stores cookies from `Set-Cookie` headers, and sends them with matching requests.
+///
+/// Use it with [`crate::Client::with_cookie_jar`].
+///
+/// Requests that already have a `Cookie` header are left alone.
+#[derive(Default)]
+pub struct CookieJar {
+    cookies: Mutex<Vec<Cookie>>,
+}
+
+impl std::fmt::Debug for CookieJar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CookieJar")
+            .field("cookies", &self.cookies.lock().unwrap())
+            .finish()
+    }
+}
+
+impl CookieJar {
+    /// An empty jar.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// All cookies that have not yet expired.
+    pub fn cookies(&self) -> Vec<Cookie> {
+        let now = crate::http_date::now();
+        let mut cookies = self.cookies.lock().unwrap();
+        cookies.retain(|cookie| !cookie.is_expired(now));
+        cookies.clone()
+    }
+
+    /// Add a cookie, replacing any cookie with the same name, domain and path.
+    ///
+    /// An expired cookie removes the cookie it replaces.
+    pub fn insert(&self, cookie: Cookie) {
+        let mut cookies = self.cookies.lock().unwrap();
+        cookies.retain(|c| {
+            !(c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path)
+        });
+        if !cookie.is_expired(crate::http_date::now()) {
+            cookies.push(cookie);
+        }
+    }
+
+    /// Remove all cookies.
+    pub fn clear(&self) {
+        self.cookies.lock().unwrap().clear();
+    }
+
+    /// Store the cookie of a `Set-Cookie` header received in a response from `url`.
+    ///
+    /// Invalid cookies are ignored.
+    pub fn set_cookie(&self, url: &str, set_cookie: &str) {
+        if let Some(cookie) = Cookie::parse(set_cookie, url) {
+            self.insert(cookie);
+        }
+    }
+
+    /// The value of the `Cookie` header for a request to `url`,
+    /// or `None` if there are no matching cookies.
+    pub fn cookie_header(&self, url: &str) -> Option<String> {
+        let url = UrlParts::parse(url)?;
+        let mut cookies: Vec<Cookie> = self
+            .cookies()
+            .into_iter()
+            .filter(|cookie| cookie.matches(&url))
+            .collect();
+        if cookies.is_empty() {
+            return None;
+        }
+
+        // Cookies with longer paths go first, as recommended by RFC 6265.
+        // The sort is stable, so otherwise older cookies go first.
+        cookies.sort_by_key(|cookie| std::cmp::Reverse(cookie.path.len()));
+        let pairs: Vec<String> = cookies
+            .iter()
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect();
+        Some(pairs.join("; "))
+    }
+
+    /// Read cookies saved with [`Self::save`].
+    ///
+    /// Expired cookies are skipped.
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        let jar = Self::new();
+        for line in text.lines() {
+            if let Some(cookie) = parse_netscape_line(line) {
+                jar.insert(cookie);
+            }
+        }
+        Ok(jar)
+    }
+
+    /// Save all cookies (including session cookies) to a file,
+    /// in the Netscape `cookies.txt` format also used by `curl` and `wget`.
+    ///
+    /// The `SameSite` attribute is not saved.
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut text = "# Netscape HTTP Cookie File\n".to_owned();
+        for cookie in self.cookies() {
+            let expires = cookie
+                .expires
+                .map(|expires| {
+                    expires
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs()
+                })
+                .unwrap_or(0);
+            let bool_str = |b: bool| if b { "TRUE" } else { "FALSE" };
+            text += &format!(
+                "{}{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                if cookie.http_only { "#HttpOnly_" } else { "" },
+                if cookie.host_only {
+                    cookie.domain.clone()
+                } else {
+                    format!(".{}", cookie.domain)
+                },
+                bool_str(!cookie.host_only),
+                cookie.path,
+                bool_str(cookie.secure),
+                expires,
+                cookie.name,
+                cookie.value,
+            );
+        }
+        std::fs::write(path, text)
+    }
+}
+
+fn parse_netscape_line(line: &str) -> Option<Cookie> {
+    let (line, http_only) = match line.strip_prefix("#HttpOnly_") {
+        Some(line) => (line, true),
+        None => (line, false),
+    };
+    if line.starts_with('#') {
+        return None;
+    }
+
+    let fields: Vec<&str> = line.split('\t').collect();
+    let [domain, include_subdomains, path, secure, expires, name, value] = fields.as_slice() else {
+        return None;
+    };
+    let expires: u64 = expires.parse().ok()?;
+    Some(Cookie {
+        name: (*name).to_owned(),
+        value: (*value).to_owned(),
+        domain: domain.trim_start_matches('.').to_ascii_lowercase(),
+        host_only: *include_subdomains != "TRUE",
+        path: (*path).to_owned(),
+        expires: (expires != 0).then(|| saturating_add(UNIX_EPOCH, Duration::from_secs(expires))),
+        secure: *secure == "TRUE",
+        http_only,
+        same_site: None,
+    })
+}
+
+/// `time + duration`, but at most the end of the year 9999 (the latest `Expires` we can parse),
+/// so that huge values from servers or files can't overflow.
+fn saturating_add(time: SystemTime, duration: Duration) -> SystemTime {
+    let far_future = UNIX_EPOCH + Duration::from_secs(253_402_300_799);
+    time.checked_add(duration)
+        .map_or(far_future, |time| time.min(far_future))
+}
+
+// ----------------------------------------------------------------------------
+
+/// The parts of a URL that matter for cookies.
+struct UrlParts {
+    is_secure: bool,
+    host: String,
+    path: String,
+}
+
+impl UrlParts {
+    fn parse(url: &str) -> Option<Self> {
+        let uri: ureq::http::Uri = url.parse().ok()?;
+        Some(Self {
+            is_secure: uri.scheme_str() == Some("https"),
+            host: uri.host()?.to_ascii_lowercase(),
+            path: uri.path().to_owned(),
+        })
+    }
+}
+
+/// <https://www.rfc-editor.org/rfc/rfc6265#section-5.1.3>
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host.ends_with(domain)
+            && host[..host.len() - domain.len()].ends_with('.')
+            && host.parse::<std::net::IpAddr>().is_err())
+}
+
+/// <https://www.rfc-editor.org/rfc/rfc6265#section-5.1.4>
+fn default_path(request_path: &str) -> String {
+    match request_path.rfind('/') {
+        Some(0) | None => "/".to_owned(),
+        Some(i) => request_path[..i].to_owned(),
+    }
+}
+
+/// <https://www.rfc-editor.org/rfc/rfc6265#section-5.1.4>
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/')))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(set_cookie: &str) -> Option<Cookie> {
+        Cookie::parse(set_cookie, "https://www.example.com/docs/page.html")
+    }
+
+    #[test]
+    fn parse_defaults() {
+        let cookie = parse("id=a3fWa").unwrap();
+        assert_eq!(cookie.name, "id");
+        assert_eq!(cookie.value, "a3fWa");
+        assert_eq!(cookie.domain, "www.example.com");
+        assert!(cookie.host_only);
+        assert_eq!(cookie.path, "/docs");
+        assert_eq!(cookie.expires, None);
+        assert!(!cookie.secure && !cookie.http_only);
+        assert_eq!(cookie.same_site, None);
+
+        assert_eq!(parse(" id = a=b ").unwrap().value, "a=b");
+        assert_eq!(parse("empty=").unwrap().value, "");
+    }
+
+    #[test]
+    fn parse_attributes() {
+        let cookie =
+            parse("id=1; Domain=.Example.com; Path=/; Secure; HttpOnly; SameSite=Lax; Unknown=x")
+                .unwrap();
+        assert_eq!(cookie.domain, "example.com");
+        assert!(!cookie.host_only);
+        assert_eq!(cookie.path, "/");
+        assert!(cookie.secure && cookie.http_only);
+        assert_eq!(cookie.same_site, Some(SameSite::Lax));
+
+        // A relative path is ignored:
+        assert_eq!(parse("id=1; Path=docs").unwrap().path, "/docs");
+
+        // A domain equal to the host keeps the cookie host-only:
+        assert!(parse("id=1; Domain=www.example.com").unwrap().host_only);
+    }
+
+    #[test]
+    fn parse_rejected() {
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("no-equals-sign"), None);
+        assert_eq!(parse("=value"), None);
+        assert_eq!(parse("id=1; Domain=other.com"), None);
+        assert_eq!(parse("id=1; Domain=com"), None);
+        assert_eq!(parse("id=1; Domain=sub.www.example.com"), None);
+        assert_eq!(Cookie::parse("id=1; Secure", "http://example.com/"), None);
+        assert_eq!(Cookie::parse("id=1", "not a url"), None);
+    }
+
+    #[test]
+    fn parse_expiry() {
+        let expires = parse("id=1; Expires=Sun, 06 Nov 1994 08:49:37 GMT")
+            .unwrap()
+            .expires;
+        assert_eq!(expires, Some(UNIX_EPOCH + Duration::from_secs(784_111_777)));
+
+        // Max-Age takes precedence, in either order:
+        let now = crate::http_date::now();
+        for set_cookie in [
+            "id=1; Max-Age=60; Expires=Sun, 06 Nov 1994 08:49:37 GMT",
+            "id=1; Expires=Sun, 06 Nov 1994 08:49:37 GMT; Max-Age=60",
+        ] {
+            let expires = parse(set_cookie).unwrap().expires.unwrap();
+            assert!(expires > now + Duration::from_secs(50), "{}", set_cookie);
+        }
+
+        // Zero and negative Max-Age expire the cookie right away:
+        for set_cookie in ["id=1; Max-Age=0", "id=1; Max-Age=-5"] {
+            let cookie = parse(set_cookie).unwrap();
+            assert!(cookie.is_expired(crate::http_date::now()), "{}", set_cookie);
+        }
+
+        // Invalid values are ignored:
+        assert_eq!(parse("id=1; Max-Age=soon").unwrap().expires, None);
+        assert_eq!(parse("id=1; Expires=tomorrow").unwrap().expires, None);
+    }
+
+    #[test]
+    fn huge_max_age_does_not_overflow() {
+        let cookie = parse("id=1; Max-Age=9223372036854775807").unwrap();
+        assert!(!cookie.is_expired(crate::http_date::now()));
+
+        let cookie =
+            parse_netscape_line("example.com\tFALSE\t/\tFALSE\t18446744073709551615\tid\t1")
+                .unwrap();
+        assert!(!cookie.is_expired(crate::http_date::now()));
+    }
+
+    #[test]
+    fn domain_matching() {
+        assert!(domain_matches("example.com", "example.com"));
+        assert!(domain_matches("www.example.com", "example.com"));
+        assert!(domain_matches("a.b.example.com", "example.com"));
+        assert!(!domain_matches("badexample.com", "example.com"));
+        assert!(!domain_matches("example.com", "www.example.com"));
+        assert!(!domain_matches("1.2.3.4", "2.3.4"));
+        assert!(domain_matches("1.2.3.4", "1.2.3.4"));
+    }
+
+    #[test]
+    fn path_matching() {
+        assert_eq!(default_path(""), "/");
+        assert_eq!(default_path("/"), "/");
+        assert_eq!(default_path("/page"), "/");
+        assert_eq!(default_path("/docs/page"), "/docs");
+        assert_eq!(default_path("/docs/"), "/docs");
+
+        assert!(path_matches("/docs", "/docs"));
+        assert!(path_matches("/docs/page", "/docs"));
+        assert!(path_matches("/docs/page", "/docs/"));
+        assert!(path_matches("/docs/page", "/"));
+        assert!(!path_matches("/docsearch", "/docs"));
+        assert!(!path_matches("/", "/docs"));
+    }
+
+    #[test]
+    fn cookie_header() {
+        let jar = CookieJar::new();
+        jar.set_cookie("https://www.example.com/docs/a", "short=1; Path=/");
+        jar.set_cookie("https://www.example.com/docs/a", "long=2");
+        jar.set_cookie("https://www.example.com/", "domain=3; Domain=example.com");
+        jar.set_cookie("https://www.example.com/", "secure=4; Secure");
+        jar.set_cookie("https://other.com/", "other=5");
+
+        assert_eq!(
+            jar.cookie_header("https://www.example.com/docs/b")
+                .as_deref(),
+            Some("long=2; short=1; domain=3; secure=4")
+        );
+        assert_eq!(
+            jar.cookie_header("http://api.example.com/").as_deref(),
+            Some("domain=3")
+        );
+        assert_eq!(jar.cookie_header("https://example.org/"), None);
+
+        // Same name, domain and path replaces; an expired cookie removes:
+        jar.set_cookie("https://www.example.com/docs/a", "long=changed");
+        jar.set_cookie("https://www.example.com/", "short=; Max-Age=0");
+        assert_eq!(
+            jar.cookie_header("https://www.example.com/docs/b")
+                .as_deref(),
+            Some("long=changed; domain=3; secure=4")
+        );
+    }
+
+    #[test]
+    fn netscape_round_trip() {
+        let jar = CookieJar::new();
+        jar.set_cookie("https://www.example.com/", "session=1; HttpOnly");
+        jar.set_cookie(
+            "https://www.example.com/",
+            "persistent=2; Domain=example.com; Path=/app; Secure; Expires=Fri, 01 Jan 2100 00:00:00 GMT",
+        );
+
+        let path =
+            std::env::temp_dir().join(format!("ehttp-test-cookies-{}.txt", std::process::id()));
+        jar.save(&path).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let loaded = CookieJar::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            text,
+            "# Netscape HTTP Cookie File\n\
+             #HttpOnly_www.example.com\tFALSE\t/\tFALSE\t0\tsession\t1\n\
+             .example.com\tTRUE\t/app\tTRUE\t4102444800\tpersistent\t2\n"
+        );
+        assert_eq!(loaded.cookies(), jar.cookies());
+    }
+
+    #[test]
+    fn netscape_invalid_lines() {
+        for line in [
+            "",
+            "# Netscape HTTP Cookie File",
+            "example.com\tFALSE\t/\tFALSE\t0\tid", // Too few fields
+            "example.com\tFALSE\t/\tFALSE\tnever\tid\t1",
+            "example.com\tFALSE\t/\tFALSE\t-1\tid\t1",
+        ] {
+            assert_eq!(parse_netscape_line(line), None, "{line:?}");
+        }
+    }
+}
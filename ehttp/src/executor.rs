@@ -0,0 +1,309 @@
+use std::{
+    collections::VecDeque,
+    panic::AssertUnwindSafe,
+    sync::{Arc, Condvar, Mutex, OnceLock},
+    time::Duration,
+};
+
+use crate::Error;
+
+/// A request to run in the background.
+///
+/// It is called with `Ok(())` on a worker thread to perform the request,
+/// or with `Err` if the request could not be scheduled (so it can report the error to its callback).
+pub(crate) type Job = Box<dyn FnOnce(crate::Result<()>) + Send>;
+
+/// Decides how native requests are run in the background.
+///
+/// By default, all clients share a pool of up to [`Self::DEFAULT_MAX_CONCURRENCY`] threads.
+/// Use [`crate::Client::with_executor`] to change this.
+///
+/// Only available when compiling for native.
+///
+/// ```
+/// let client = ehttp::Client::new().with_executor(ehttp::Executor::bounded_pool(4, 1000));
+/// ```
+///
+/// Streaming requests (including Server-Sent Events and NDJSON) don't use the executor:
+/// they can stay open for as long as the server likes, so each one gets a thread of its own.
+/// Otherwise a few open event streams could occupy the whole pool, and keep all other requests waiting.
+#[derive(Clone)]
+pub struct Executor {
+    kind: Kind,
+}
+
+#[derive(Clone)]
+enum Kind {
+    ThreadPerRequest,
+    Pool(Arc<Pool>),
+}
+
+impl Default for Executor {
+    /// A pool of up to [`Self::DEFAULT_MAX_CONCURRENCY`] threads, shared by everyone using the default.
+    fn default() -> Self {
+        static DEFAULT_EXECUTOR: OnceLock<Executor> = OnceLock::new();
+        DEFAULT_EXECUTOR
+            .get_or_init(|| Self::pool(Self::DEFAULT_MAX_CONCURRENCY))
+            .clone()
+    }
+}
+
+impl std::fmt::Debug for Executor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            Kind::ThreadPerRequest => f.write_str("Executor::ThreadPerRequest"),
+            Kind::Pool(pool) => f
+                .debug_struct("Executor::Pool")
+                .field("max_concurrency", &pool.max_threads)
+                .field("max_queued", &pool.max_queued)
+                .finish(),
+        }
+    }
+}
+
+impl Executor {
+    /// The number of threads in the default pool.
+    pub const DEFAULT_MAX_CONCURRENCY: usize = 32;
+
+    /// Spawn a new thread for every request.
+    ///
+    /// This was the behavior of `ehttp` before there was an [`Executor`].
+    /// There is no limit on how many requests run at the same time.
+    pub fn thread_per_request() -> Self {
+        Self {
+            kind: Kind::ThreadPerRequest,
+        }
+    }
+
+    /// Run at most `max_concurrency` requests at the same time,
+    /// and queue up the rest.
+    ///
+    /// Threads are started as needed, and shut down after being idle for a while.
+    ///
+    /// Every call creates a new pool, with threads of its own.
+    pub fn pool(max_concurrency: usize) -> Self {
+        Self::bounded_pool(max_concurrency, usize::MAX)
+    }
+
+    /// Like [`Self::pool`], but at most `max_queued` requests can wait in the queue.
+    ///
+    /// Requests that don't fit in the queue fail immediately.
+    pub fn bounded_pool(max_concurrency: usize, max_queued: usize) -> Self {
+        Self {
+            kind: Kind::Pool(Arc::new(Pool {
+                max_threads: max_concurrency.max(1),
+                max_queued,
+                state: Mutex::new(PoolState::default()),
+                condvar: Condvar::new(),
+            })),
+        }
+    }
+
+    /// Run the job in the background.
+    pub(crate) fn spawn(&self, job: Job) {
+        match &self.kind {
+            Kind::ThreadPerRequest => spawn_dedicated_thread(job),
+            Kind::Pool(pool) => pool.spawn(job),
+        }
+    }
+}
+
+/// Run the job on a new thread, e.g. because it may take a very long time.
+pub(crate) fn spawn_dedicated_thread(job: Job) {
+    // `spawn` consumes the closure even when it fails, so we need a way to get the job back.
+    let job = Arc::new(Mutex::new(Some(job)));
+    let result = {
+        let job = job.clone();
+        std::thread::Builder::new()
+            .name("ehttp".to_owned())
+            .spawn(move || {
+                let job = job.lock().unwrap().take();
+                if let Some(job) = job {
+                    // The panic message has already been printed, and there is nobody else to tell:
+                    let _ = std::panic::catch_unwind(AssertUnwindSafe(|| job(Ok(()))));
+                }
+            })
+    };
+
+    if let Err(err) = result {
+        let job = job.lock().unwrap().take();
+        if let Some(job) = job {
+            job(Err(Error::Other(format!(
+                "Failed to spawn ehttp thread: {err}"
+            ))));
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// How long an idle worker thread waits for a new job before shutting down.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Pool {
+    max_threads: usize,
+    max_queued: usize,
+    state: Mutex<PoolState>,
+    condvar: Condvar,
+}
+
+#[derive(Default)]
+struct PoolState {
+    queue: VecDeque<Job>,
+    num_threads: usize,
+    num_idle: usize,
+}
+
+impl Pool {
+    fn spawn(self: &Arc<Self>, job: Job) {
+        let mut state = self.state.lock().unwrap();
+
+        if self.max_queued <= state.queue.len() {
+            drop(state);
+            job(Err(Error::Other(
+                "Too many queued ehttp requests".to_owned(),
+            )));
+            return;
+        }
+
+        state.queue.push_back(job);
+        self.condvar.notify_one();
+
+        if state.queue.len() <= state.num_idle || self.max_threads <= state.num_threads {
+            return; // A worker will pick it up.
+        }
+
+        state.num_threads += 1;
+        drop(state);
+
+        let pool = self.clone();
+        let result = std::thread::Builder::new()
+            .name("ehttp".to_owned())
+            .spawn(move || pool.work());
+
+        if let Err(err) = result {
+            let mut state = self.state.lock().unwrap();
+            state.num_threads -= 1;
+            if state.num_threads == 0 {
+                // Nobody is going to run the queued jobs, so fail them instead of leaving them hanging:
+                let jobs: Vec<Job> = state.queue.drain(..).collect();
+                drop(state);
+                for job in jobs {
+                    job(Err(Error::Other(format!(
+                        "Failed to spawn ehttp thread: {err}"
+                    ))));
+                }
+            }
+        }
+    }
+
+    fn work(&self) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(job) = state.queue.pop_front() {
+                drop(state);
+                // A panicking callback must not take the worker down with it.
+                // The panic message has already been printed, and there is nobody else to tell:
+                let _ = std::panic::catch_unwind(AssertUnwindSafe(|| job(Ok(()))));
+                state = self.state.lock().unwrap();
+                continue;
+            }
+
+            state.num_idle += 1;
+            let (new_state, timeout) = self.condvar.wait_timeout(state, IDLE_TIMEOUT).unwrap();
+            state = new_state;
+            state.num_idle -= 1;
+
+            if timeout.timed_out() && state.queue.is_empty() {
+                state.num_threads -= 1;
+                return;
+            }
+        }
+    }
+}
+
+/// Run `f`, turning a panic into an error, so that the callback of a request always gets called.
+pub(crate) fn catch_panic<T>(f: impl FnOnce() -> crate::Result<T>) -> crate::Result<T> {
+    std::panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|panic| {
+        let message = panic
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown panic");
+        Err(Error::Other(format!("ehttp panicked: {message}")))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+
+    #[test]
+    fn bounded_pool_rejects_when_full() {
+        let executor = Executor::bounded_pool(1, 1);
+        let (release, blocked) = mpsc::channel::<()>();
+        let (done_tx, done_rx) = mpsc::channel();
+
+        // Occupies the only thread:
+        let done = done_tx.clone();
+        executor.spawn(Box::new(move |scheduled| {
+            blocked.recv().unwrap();
+            done.send(("running", scheduled.is_ok())).unwrap();
+        }));
+        // Give the worker a moment to pick it up, so the next one has to wait in the queue:
+        std::thread::sleep(Duration::from_millis(50));
+
+        for name in ["queued", "rejected"] {
+            let done = done_tx.clone();
+            executor.spawn(Box::new(move |scheduled| {
+                done.send((name, scheduled.is_ok())).unwrap();
+            }));
+        }
+
+        assert_eq!(done_rx.recv().unwrap(), ("rejected", false));
+        release.send(()).unwrap();
+        assert_eq!(done_rx.recv().unwrap(), ("running", true));
+        assert_eq!(done_rx.recv().unwrap(), ("queued", true));
+    }
+
+    #[test]
+    fn pools_are_independent() {
+        let Kind::Pool(first) = Executor::pool(2).kind else {
+            unreachable!()
+        };
+        let Kind::Pool(second) = Executor::bounded_pool(2, 5).kind else {
+            unreachable!()
+        };
+        assert!(!Arc::ptr_eq(&first, &second));
+        assert_eq!(first.max_queued, usize::MAX);
+        assert_eq!(second.max_queued, 5);
+    }
+
+    #[test]
+    fn worker_survives_panicking_job() {
+        for executor in [Executor::pool(1), Executor::thread_per_request()] {
+            let (done_tx, done_rx) = mpsc::channel();
+            executor.spawn(Box::new(|_| panic!("test panic in a job")));
+            executor.spawn(Box::new(move |scheduled| {
+                done_tx.send(scheduled.is_ok()).unwrap();
+            }));
+            assert!(done_rx.recv_timeout(Duration::from_secs(5)).unwrap());
+        }
+    }
+
+    #[test]
+    fn catch_panic_reports_the_message() {
+        assert_eq!(catch_panic(|| Ok(42)), Ok(42));
+        assert_eq!(
+            catch_panic::<()>(|| panic!("static message")),
+            Err(Error::Other("ehttp panicked: static message".to_owned()))
+        );
+        let number = 7;
+        assert_eq!(
+            catch_panic::<()>(|| panic!("formatted {}", number)),
+            Err(Error::Other("ehttp panicked: formatted 7".to_owned()))
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// A handle to an in-flight request.
+///
+/// Returned by [`crate::fetch`], [`crate::fetch_async_with_handle`] and `streaming::fetch`.
+///
+/// Call [`Self::abort`] to cancel the request.
+/// The callback will then receive [`crate::Error::Aborted`].
+///
+/// Dropping the handle does NOT abort the request.
+#[derive(Clone, Default)]
+pub struct RequestHandle {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    aborted: AtomicBool,
+
+    /// Used to look up the `AbortController` of the request.
+    #[cfg(target_arch = "wasm32")]
+    id: u64,
+}
+
+impl Default for Inner {
+    fn default() -> Self {
+        #[cfg(target_arch = "wasm32")]
+        static NEXT_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+        Self {
+            aborted: AtomicBool::new(false),
+            #[cfg(target_arch = "wasm32")]
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+}
+
+impl RequestHandle {
+    /// Abort the request.
+    ///
+    /// If the request has already completed, this does nothing.
+    ///
+    /// On native, the request is aborted the next time the body is read from,
+    /// which means a request that is still waiting for the server to respond
+    /// will only notice the abort once the response headers arrive (or it times out).
+    ///
+    /// On web, this uses an [`AbortController`](https://developer.mozilla.org/en-US/docs/Web/API/AbortController)
+    /// and takes effect immediately.
+    pub fn abort(&self) {
+        self.inner.aborted.store(true, Ordering::SeqCst);
+
+        #[cfg(target_arch = "wasm32")]
+        crate::web::abort(self.inner.id);
+    }
+
+    /// Has [`Self::abort`] been called?
+    pub fn is_aborted(&self) -> bool {
+        self.inner.aborted.load(Ordering::SeqCst)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn id(&self) -> u64 {
+        self.inner.id
+    }
+}
+
+impl std::fmt::Debug for RequestHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequestHandle")
+            .field("aborted", &self.is_aborted())
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,769 @@
+//! Capture requests and responses into [HAR](https://w3c.github.io/web-performance/specs/HAR/Overview.html) files
+//! (HTTP Archive 1.2), which can be opened in the network tab of the browser developer tools.
+//!
+//! Requires the `har` feature to be enabled.
+//!
+//! ```
+//! let recorder = ehttp::har::HarRecorder::new();
+//! ehttp::add_global_interceptor(recorder.clone());
+//!
+//! // … make some requests …
+//!
+//! recorder.set_enabled(false); // Pause the capture
+//! let har_file_contents = recorder.har().to_json();
+//! ```
+//!
+//! A HAR file can also be loaded with [`Har::from_json`], and its entries replayed with [`HarEntry::to_request`].
+//!
+//! Bodies that aren't valid UTF-8 are stored as base64.
+//! The URL after redirects ([`Response::url`]) and the [`Response::redirects`] are stored
+//! in the custom `_url` and `_redirects` fields of the response,
+//! and errors in the `_error` field (like the browsers do).
+//!
+//! Only the total time of each request is captured, see [`HarEntry::time`].
+
+use std::{
+    convert::TryFrom as _,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+
+use base64::Engine as _;
+use serde_json::{json, Value};
+
+use crate::{http_date, Error, Headers, Interceptor, Method, Redirect, Request, Response, Result};
+
+#[cfg(feature = "streaming")]
+use crate::PartialResponse;
+
+/// The contents of a HAR file.
+#[derive(Clone, Debug, Default)]
+pub struct Har {
+    /// The captured requests, in the order they completed.
+    pub entries: Vec<HarEntry>,
+}
+
+/// A captured request and its result.
+#[derive(Clone, Debug)]
+pub struct HarEntry {
+    /// When the request was sent.
+    pub started: SystemTime,
+
+    /// How long it took until the request completed (or until the headers arrived, for the [`crate::streaming`] API).
+    ///
+    /// Only this total is captured, not the phases (DNS, connect, send, wait, receive),
+    /// so the HAR `timings` have all of it as `wait`.
+    pub time: Duration,
+
+    /// The method of the request.
+    pub method: Method,
+
+    /// The URL of the request, before any redirects.
+    pub url: String,
+
+    /// The headers of the request.
+    pub headers: Headers,
+
+    /// The body of the request, or `None` if it wasn't in memory (e.g. [`crate::Body::from_file`]).
+    pub body: Option<Vec<u8>>,
+
+    /// The response, or why there is none.
+    ///
+    /// The body of responses of the [`crate::streaming`] API is not captured, so it is empty.
+    pub result: Result<Response>,
+}
+
+impl HarEntry {
+    /// A request like the captured one, e.g. to replay it.
+    ///
+    /// HTTP/2 pseudo-headers (like `:authority`) are left out.
+    pub fn to_request(&self) -> Request {
+        let mut headers = Headers::default();
+        for (key, value) in &self.headers {
+            if !key.starts_with(':') {
+                headers.insert(key, value);
+            }
+        }
+        let request = Request::new(self.method.clone(), &self.url, headers);
+        match &self.body {
+            Some(body) => request.with_body(body.clone()),
+            None => request,
+        }
+    }
+}
+
+impl Har {
+    /// The HAR file, as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        let json = json!({
+            "log": {
+                "version": "1.2",
+                "creator": {
+                    "name": "ehttp",
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+                "entries": self.entries.iter().map(entry_to_json).collect::<Vec<_>>(),
+            }
+        });
+        serde_json::to_string_pretty(&json).expect("Serializing a JSON value can't fail")
+    }
+
+    /// Parse a HAR file, e.g. one exported from the browser developer tools.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let json: Value = serde_json::from_str(json)
+            .map_err(|err| Error::Other(format!("Invalid HAR file: {err}")))?;
+        let entries = array(&json["log"]["entries"])
+            .iter()
+            .map(entry_from_json)
+            .collect::<std::result::Result<_, String>>()
+            .map_err(|err| Error::Other(format!("Invalid HAR file: {err}")))?;
+        Ok(Self { entries })
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// An [`crate::Interceptor`] that captures the requests it sees into a [`Har`].
+///
+/// Register it with [`crate::add_global_interceptor`] to capture all requests,
+/// or with [`crate::Client::with_interceptor`] for the requests of a single client.
+///
+/// The captured requests are the ones that were sent, i.e. after all interceptors have modified them.
+///
+/// Cloning it is cheap, and the clones share the capture.
+#[derive(Clone, Debug, Default)]
+pub struct HarRecorder(Arc<Recorder>);
+
+#[derive(Debug, Default)]
+struct Recorder {
+    disabled: AtomicBool,
+
+    /// Requests that were sent, but haven't completed yet.
+    pending: Mutex<Vec<Pending>>,
+
+    entries: Mutex<Vec<HarEntry>>,
+}
+
+#[derive(Debug)]
+struct Pending {
+    /// See [`crate::interceptor::current_request_id`].
+    request_id: Option<u64>,
+
+    method: Method,
+    url: String,
+    started: SystemTime,
+}
+
+impl HarRecorder {
+    /// A recorder that is enabled, and has captured nothing yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pause or resume the capture.
+    ///
+    /// Requests that were sent while it was enabled are still captured when they complete,
+    /// and requests that were sent while it was disabled are never captured.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.0.disabled.store(!enabled, Ordering::Relaxed);
+    }
+
+    /// Is it capturing?
+    pub fn is_enabled(&self) -> bool {
+        !self.0.disabled.load(Ordering::Relaxed)
+    }
+
+    /// Everything captured so far.
+    pub fn har(&self) -> Har {
+        Har {
+            entries: self.0.entries.lock().unwrap().clone(),
+        }
+    }
+
+    /// Forget everything captured so far.
+    pub fn clear(&self) {
+        self.0.entries.lock().unwrap().clear();
+    }
+
+    fn record(&self, request: &Request, result: Result<Response>) {
+        let now = http_date::now();
+        let started = {
+            let mut pending = self.0.pending.lock().unwrap();
+            // Match by id, since another interceptor may have changed the request after we saw it
+            // (and there may be several requests to the same URL in flight):
+            let request_id = crate::interceptor::current_request_id();
+            let index = pending.iter().position(|pending| match request_id {
+                Some(request_id) => pending.request_id == Some(request_id),
+                None => pending.method == request.method && pending.url == request.url,
+            });
+            match index {
+                Some(index) => pending.remove(index).started,
+                None => return, // Sent while we were disabled
+            }
+        };
+
+        self.0.entries.lock().unwrap().push(HarEntry {
+            started,
+            time: now.duration_since(started).unwrap_or_default(),
+            method: request.method.clone(),
+            url: request.url.clone(),
+            headers: request.headers.clone(),
+            body: request.body.as_bytes().map(<[u8]>::to_vec),
+            result,
+        });
+    }
+}
+
+impl Interceptor for HarRecorder {
+    fn on_request(&self, request: &mut Request) -> Result<()> {
+        if self.is_enabled() {
+            self.0.pending.lock().unwrap().push(Pending {
+                request_id: crate::interceptor::current_request_id(),
+                method: request.method.clone(),
+                url: request.url.clone(),
+                started: http_date::now(),
+            });
+        }
+        Ok(())
+    }
+
+    fn on_response(&self, request: &Request, result: &mut Result<Response>) {
+        self.record(request, result.clone());
+    }
+
+    #[cfg(feature = "streaming")]
+    fn on_partial_response(&self, request: &Request, result: &mut Result<PartialResponse>) {
+        let result = result.clone().map(|response| {
+            let PartialResponse {
+                url,
+                ok,
+                status,
+                status_text,
+                headers,
+                redirects,
+            } = response;
+            Response {
+                url,
+                ok,
+                status,
+                status_text,
+                headers,
+                redirects,
+                bytes: vec![],
+            }
+        });
+        self.record(request, result);
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+fn entry_to_json(entry: &HarEntry) -> Value {
+    let time_ms = (entry.time.as_secs_f64() * 1e6).round() / 1000.0;
+
+    let mut request = json!({
+        "method": entry.method.as_str(),
+        "url": entry.url,
+        "httpVersion": "HTTP/1.1",
+        "cookies": [],
+        "headers": headers_to_json(&entry.headers),
+        "queryString": query_string(&entry.url),
+        "headersSize": -1,
+        "bodySize": entry.body.as_ref().map_or(-1, |body| body.len() as i64),
+    });
+    if let Some(body) = entry.body.as_deref().filter(|body| !body.is_empty()) {
+        let (text, encoding) = encode(body);
+        request["postData"] = json!({
+            "mimeType": entry.headers.get("content-type").unwrap_or_default(),
+            "text": text,
+        });
+        if let Some(encoding) = encoding {
+            request["postData"]["_encoding"] = json!(encoding);
+        }
+    }
+
+    let response = match &entry.result {
+        Ok(response) => {
+            let (text, encoding) = encode(&response.bytes);
+            let mut content = json!({
+                "size": response.bytes.len(),
+                "mimeType": response.headers.get("content-type").unwrap_or_default(),
+                "text": text,
+            });
+            if let Some(encoding) = encoding {
+                content["encoding"] = json!(encoding);
+            }
+            json!({
+                "status": response.status,
+                "statusText": response.status_text,
+                "httpVersion": "HTTP/1.1",
+                "cookies": [],
+                "headers": headers_to_json(&response.headers),
+                "content": content,
+                "redirectURL": response.headers.get("location").unwrap_or_default(),
+                "headersSize": -1,
+                "bodySize": response.bytes.len(),
+                "_url": response.url,
+                "_redirects": response.redirects.iter().map(|redirect| json!({
+                    "url": redirect.url,
+                    "status": redirect.status,
+                    "location": redirect.location,
+                })).collect::<Vec<_>>(),
+            })
+        }
+        Err(err) => json!({
+            "status": 0,
+            "statusText": "",
+            "httpVersion": "",
+            "cookies": [],
+            "headers": [],
+            "content": { "size": 0, "mimeType": "" },
+            "redirectURL": "",
+            "headersSize": -1,
+            "bodySize": -1,
+            "_error": err.to_string(),
+        }),
+    };
+
+    json!({
+        "startedDateTime": http_date::format_rfc3339(entry.started),
+        "time": time_ms,
+        "request": request,
+        "response": response,
+        "cache": {},
+        "timings": { "send": 0, "wait": time_ms, "receive": 0 },
+    })
+}
+
+fn entry_from_json(json: &Value) -> std::result::Result<HarEntry, String> {
+    let started = string(&json["startedDateTime"])?;
+    let started = http_date::parse_rfc3339(started)
+        .ok_or_else(|| format!("Invalid startedDateTime {started:?}"))?;
+    let time = json["time"].as_f64().unwrap_or_default().max(0.0);
+
+    let request = &json["request"];
+    let url = string(&request["url"])?.to_owned();
+    let body = match request.get("postData") {
+        Some(post_data) => Some(decode(
+            string(&post_data["text"]).unwrap_or_default(),
+            post_data["_encoding"].as_str(),
+        )?),
+        None => (request["bodySize"].as_i64() == Some(0)).then(Vec::new),
+    };
+
+    let response = &json["response"];
+    let status = response["status"]
+        .as_u64()
+        .ok_or("Missing response status")?;
+    let result = if let Some(err) = response["_error"].as_str().filter(|err| !err.is_empty()) {
+        Err(Error::Other(err.to_owned()))
+    } else if status == 0 {
+        Err(Error::Other("No response".to_owned()))
+    } else {
+        let status = u16::try_from(status).map_err(|_| format!("Invalid status {status}"))?;
+        let content = &response["content"];
+        let redirects = array(&response["_redirects"])
+            .iter()
+            .map(|redirect| {
+                Ok(Redirect {
+                    url: string(&redirect["url"])?.to_owned(),
+                    status: redirect["status"]
+                        .as_u64()
+                        .and_then(|status| u16::try_from(status).ok())
+                        .ok_or("Invalid redirect status")?,
+                    location: string(&redirect["location"])?.to_owned(),
+                })
+            })
+            .collect::<std::result::Result<_, String>>()?;
+        Ok(Response {
+            url: response["_url"].as_str().unwrap_or(&url).to_owned(),
+            ok: (200..300).contains(&status),
+            status,
+            status_text: response["statusText"]
+                .as_str()
+                .unwrap_or_default()
+                .to_owned(),
+            headers: headers_from_json(&response["headers"])?,
+            redirects,
+            bytes: decode(
+                content["text"].as_str().unwrap_or_default(),
+                content["encoding"].as_str(),
+            )?,
+        })
+    };
+
+    Ok(HarEntry {
+        started,
+        time: Duration::from_secs_f64(time / 1000.0),
+        method: Method::parse(string(&request["method"])?).map_err(|err| err.to_string())?,
+        url,
+        headers: headers_from_json(&request["headers"])?,
+        body,
+        result,
+    })
+}
+
+fn string(json: &Value) -> std::result::Result<&str, String> {
+    json.as_str()
+        .ok_or_else(|| format!("Expected a string, got {json}"))
+}
+
+/// Missing arrays are treated as empty, since many HAR writers leave out the optional ones.
+fn array(json: &Value) -> &[Value] {
+    json.as_array().map_or(&[], Vec::as_slice)
+}
+
+fn headers_to_json(headers: &Headers) -> Value {
+    headers
+        .into_iter()
+        .map(|(name, value)| json!({ "name": name, "value": value }))
+        .collect()
+}
+
+fn headers_from_json(json: &Value) -> std::result::Result<Headers, String> {
+    let mut headers = Headers::default();
+    for header in array(json) {
+        headers.insert(string(&header["name"])?, string(&header["value"])?);
+    }
+    Ok(headers)
+}
+
+/// The (not percent-decoded) query parameters of the URL.
+fn query_string(url: &str) -> Value {
+    let url = url.split_once('#').map_or(url, |(url, _fragment)| url);
+    let Some((_, query)) = url.split_once('?') else {
+        return json!([]);
+    };
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            json!({ "name": name, "value": value })
+        })
+        .collect()
+}
+
+/// Text if possible, otherwise base64 (and the encoding).
+fn encode(bytes: &[u8]) -> (String, Option<&'static str>) {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => (text.to_owned(), None),
+        Err(_) => (
+            base64::engine::general_purpose::STANDARD.encode(bytes),
+            Some("base64"),
+        ),
+    }
+}
+
+fn decode(text: &str, encoding: Option<&str>) -> std::result::Result<Vec<u8>, String> {
+    match encoding {
+        None => Ok(text.as_bytes().to_vec()),
+        Some("base64") => base64::engine::general_purpose::STANDARD
+            .decode(text)
+            .map_err(|err| format!("Invalid base64: {err}")),
+        Some(encoding) => Err(format!("Unknown encoding {encoding:?}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+    use crate::{Client, PartialResponse, RequestHandle, Transport};
+
+    fn response(status: u16, headers: &[(&str, &str)], bytes: &[u8]) -> Response {
+        Response {
+            url: "https://example.com/final".to_owned(),
+            ok: (200..300).contains(&status),
+            status,
+            status_text: "Some Status".to_owned(),
+            headers: Headers::new(headers),
+            redirects: vec![],
+            bytes: bytes.to_vec(),
+        }
+    }
+
+    fn entry(body: Option<&[u8]>, result: Result<Response>) -> HarEntry {
+        HarEntry {
+            started: UNIX_EPOCH + Duration::from_millis(1_700_000_000_123),
+            time: Duration::from_millis(1500),
+            method: Method::POST,
+            url: "https://example.com/search?q=rust&page=2".to_owned(),
+            headers: Headers::new(&[("content-type", "text/plain"), ("x-a", "1"), ("x-a", "2")]),
+            body: body.map(<[u8]>::to_vec),
+            result,
+        }
+    }
+
+    fn assert_same(a: &HarEntry, b: &HarEntry) {
+        assert_eq!(a.started, b.started);
+        assert_eq!(a.time, b.time);
+        assert_eq!(a.method, b.method);
+        assert_eq!(a.url, b.url);
+        assert_eq!(a.headers, b.headers);
+        assert_eq!(a.body, b.body);
+        match (&a.result, &b.result) {
+            (Ok(a), Ok(b)) => {
+                assert_eq!(a.url, b.url);
+                assert_eq!(a.ok, b.ok);
+                assert_eq!(a.status, b.status);
+                assert_eq!(a.status_text, b.status_text);
+                assert_eq!(a.headers, b.headers);
+                assert_eq!(a.redirects, b.redirects);
+                assert_eq!(a.bytes, b.bytes);
+            }
+            (Err(a), Err(b)) => assert_eq!(a.to_string(), b.to_string()),
+            (a, b) => panic!("{:?} vs {:?}", a.is_ok(), b.is_ok()),
+        }
+    }
+
+    #[test]
+    fn to_json_and_from_json() {
+        let mut redirected = response(200, &[("content-type", "text/html")], b"<p>hi</p>");
+        redirected.redirects = vec![Redirect {
+            url: "https://example.com/search?q=rust&page=2".to_owned(),
+            status: 302,
+            location: "https://example.com/final".to_owned(),
+        }];
+
+        let entries = vec![
+            entry(Some(b"hello"), Ok(redirected)),
+            entry(Some(&[0, 159, 146, 150]), Ok(response(201, &[], &[255, 0]))),
+            entry(None, Ok(response(204, &[], b""))),
+            entry(Some(b""), Ok(response(404, &[], "nöt found".as_bytes()))),
+            entry(None, Err(Error::Timeout("Timed out".to_owned()))),
+        ];
+        let har = Har {
+            entries: entries.clone(),
+        };
+        let json = har.to_json();
+        let parsed = Har::from_json(&json).unwrap();
+        assert_eq!(parsed.entries.len(), entries.len());
+        for (parsed, entry) in parsed.entries.iter().zip(&entries) {
+            assert_same(parsed, entry);
+        }
+
+        let json: Value = serde_json::from_str(&json).unwrap();
+        let entry = &json["log"]["entries"][0];
+        assert_eq!(entry["startedDateTime"], "2023-11-14T22:13:20.123Z");
+        assert_eq!(entry["time"], 1500.0);
+        assert_eq!(
+            entry["request"]["queryString"],
+            json!([{ "name": "q", "value": "rust" }, { "name": "page", "value": "2" }])
+        );
+        assert_eq!(entry["response"]["_url"], "https://example.com/final");
+        let binary = &json["log"]["entries"][1];
+        assert_eq!(binary["request"]["postData"]["_encoding"], "base64");
+        assert_eq!(binary["response"]["content"]["encoding"], "base64");
+        assert_eq!(json["log"]["entries"][4]["response"]["_error"], "Timed out");
+    }
+
+    #[test]
+    fn from_browser_json() {
+        // Like browsers write them, without our custom fields:
+        let json = r#"{
+            "log": {
+                "version": "1.2",
+                "entries": [{
+                    "startedDateTime": "2024-02-29T12:00:00.5+01:00",
+                    "time": 12.5,
+                    "request": {
+                        "method": "GET",
+                        "url": "https://example.com/",
+                        "headers": [
+                            { "name": ":authority", "value": "example.com" },
+                            { "name": "accept", "value": "*/*" }
+                        ],
+                        "bodySize": 0
+                    },
+                    "response": {
+                        "status": 200,
+                        "statusText": "",
+                        "headers": [],
+                        "content": { "size": 2, "mimeType": "text/plain", "text": "hi" }
+                    }
+                }, {
+                    "startedDateTime": "2024-02-29T12:00:01Z",
+                    "time": -1,
+                    "request": { "method": "GET", "url": "https://blocked.example.com/" },
+                    "response": { "status": 0 }
+                }]
+            }
+        }"#;
+        let har = Har::from_json(json).unwrap();
+        assert_eq!(har.entries.len(), 2);
+
+        let entry = &har.entries[0];
+        assert_eq!(
+            entry.started,
+            UNIX_EPOCH + Duration::from_millis(1_709_204_400_500)
+        );
+        assert_eq!(entry.time, Duration::from_micros(12_500));
+        assert_eq!(entry.body.as_deref(), Some(&b""[..]));
+        let response = entry.result.as_ref().unwrap();
+        assert_eq!(response.url, "https://example.com/");
+        assert_eq!(response.bytes, b"hi");
+
+        let request = entry.to_request();
+        assert_eq!(request.headers, Headers::new(&[("accept", "*/*")]));
+
+        let entry = &har.entries[1];
+        assert_eq!(entry.time, Duration::ZERO);
+        assert_eq!(entry.body, None);
+        assert!(entry.result.is_err());
+
+        for bad in [
+            "not json",
+            r#"{"log": {"entries": [{"startedDateTime": "yesterday"}]}}"#,
+            r#"{"log": {"entries": [{"startedDateTime": "2024-02-29T12:00:01Z", "request": {}}]}}"#,
+        ] {
+            assert!(Har::from_json(bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    /// Echoes the request body.
+    struct Echo;
+
+    impl Transport for Echo {
+        fn fetch(
+            &self,
+            request: Request,
+            _handle: RequestHandle,
+            on_done: Box<dyn FnOnce(Result<Response>) + Send>,
+        ) {
+            let bytes = request.body.as_bytes().unwrap_or_default().to_vec();
+            on_done(Ok(PartialResponse {
+                url: request.url,
+                ok: true,
+                status: 200,
+                status_text: "OK".to_owned(),
+                headers: Headers::new(&[("content-type", "text/plain")]),
+                redirects: vec![],
+            }
+            .complete(bytes)));
+        }
+    }
+
+    #[test]
+    fn recorder() {
+        let recorder = HarRecorder::new();
+        let client = Client::new()
+            .with_transport(Echo)
+            .with_interceptor(recorder.clone());
+
+        let request =
+            Request::post("https://example.com/echo", b"hello".to_vec()).with_header("X-Test", "1");
+        client.fetch_blocking(&request).unwrap();
+
+        recorder.set_enabled(false);
+        assert!(!recorder.is_enabled());
+        client
+            .fetch_blocking(&Request::get("https://example.com/paused"))
+            .unwrap();
+        recorder.set_enabled(true);
+
+        let har = recorder.har();
+        assert_eq!(har.entries.len(), 1);
+        let entry = &har.entries[0];
+        assert_eq!(entry.method, Method::POST);
+        assert_eq!(entry.url, "https://example.com/echo");
+        assert_eq!(entry.headers.get("x-test"), Some("1"));
+        assert_eq!(entry.body.as_deref(), Some(&b"hello"[..]));
+        assert_eq!(entry.result.as_ref().unwrap().bytes, b"hello");
+
+        // Replaying the captured request gives the same response:
+        let parsed = Har::from_json(&har.to_json()).unwrap();
+        let replayed = client
+            .fetch_blocking(&parsed.entries[0].to_request())
+            .unwrap();
+        assert_eq!(replayed.bytes, b"hello");
+        assert_eq!(recorder.har().entries.len(), 2);
+        assert_same(&recorder.har().entries[1], &{
+            let mut entry = entry.clone();
+            entry.started = recorder.har().entries[1].started;
+            entry.time = recorder.har().entries[1].time;
+            entry
+        });
+
+        recorder.clear();
+        assert!(recorder.har().entries.is_empty());
+    }
+
+    /// Holds on to the requests, until they are completed with [`Self::complete`].
+    #[derive(Clone, Default)]
+    struct Deferred(Arc<Mutex<Vec<(String, OnDone)>>>);
+
+    type OnDone = Box<dyn FnOnce(Result<Response>) + Send>;
+
+    impl Deferred {
+        /// Complete the request with the given index, with its URL as the body.
+        fn complete(&self, index: usize) {
+            let (url, on_done) = self.0.lock().unwrap().remove(index);
+            on_done(Ok(response(200, &[], url.as_bytes())));
+        }
+    }
+
+    impl Transport for Deferred {
+        fn fetch(&self, request: Request, _handle: RequestHandle, on_done: OnDone) {
+            self.0.lock().unwrap().push((request.url, on_done));
+        }
+    }
+
+    #[test]
+    fn requests_sent_while_disabled_are_not_captured() {
+        let recorder = HarRecorder::new();
+        let transport = Deferred::default();
+        let client = Client::new()
+            .with_transport(transport.clone())
+            .with_interceptor(recorder.clone());
+        let fetch = |request: Request| client.fetch(request, |_| {});
+
+        fetch(Request::get("https://example.com/").with_header("X-Request", "enabled"));
+        recorder.set_enabled(false);
+        fetch(Request::get("https://example.com/").with_header("X-Request", "disabled"));
+        fetch(Request::get("https://example.com/other"));
+        recorder.set_enabled(true);
+
+        // The requests sent while disabled complete first, to the same URL:
+        transport.complete(2);
+        transport.complete(1);
+        assert!(recorder.har().entries.is_empty());
+
+        transport.complete(0);
+        let har = recorder.har();
+        assert_eq!(har.entries.len(), 1);
+        assert_eq!(har.entries[0].headers.get("x-request"), Some("enabled"));
+        assert!(recorder.0.pending.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn requests_failed_by_a_later_interceptor_are_captured() {
+        struct Fail;
+
+        impl Interceptor for Fail {
+            fn on_request(&self, _request: &mut Request) -> Result<()> {
+                Err(Error::Other("Not allowed".to_owned()))
+            }
+        }
+
+        let recorder = HarRecorder::new();
+        let client = Client::new()
+            .with_transport(Echo)
+            .with_interceptor(recorder.clone())
+            .with_interceptor(Fail);
+        let result = client.fetch_blocking(&Request::get("https://example.com/"));
+        assert!(result.is_err());
+
+        let har = recorder.har();
+        assert_eq!(har.entries.len(), 1);
+        assert_eq!(
+            har.entries[0].result.as_ref().unwrap_err().to_string(),
+            "Not allowed"
+        );
+        assert!(recorder.0.pending.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,291 @@
+//! Parsing of the dates used in HTTP headers, e.g. `Retry-After`,
+//! and of the RFC 3339 timestamps used in HAR files.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The current time.
+///
+/// `SystemTime::now` panics on `wasm32-unknown-unknown`, so there we ask JavaScript instead.
+pub(crate) fn now() -> SystemTime {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        SystemTime::now()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        UNIX_EPOCH + Duration::from_secs_f64(js_sys::Date::now() / 1000.0)
+    }
+}
+
+/// Parse an [HTTP-date](https://www.rfc-editor.org/rfc/rfc9110#name-date-time-formats).
+///
+/// Accepts the preferred format (`Sun, 06 Nov 1994 08:49:37 GMT`)
+/// as well as the obsolete RFC 850 (`Sunday, 06-Nov-94 08:49:37 GMT`)
+/// and asctime (`Sun Nov  6 08:49:37 1994`) formats.
+pub(crate) fn parse(date: &str) -> Option<SystemTime> {
+    let mut parts: Vec<&str> = date
+        .split([' ', ',', '-'])
+        .filter(|part| !part.is_empty())
+        .collect();
+
+    // Skip the day of the week:
+    if parts.first()?.chars().all(|c| c.is_ascii_alphabetic()) && month(parts.first()?).is_none() {
+        parts.remove(0);
+    }
+
+    let (day, month_name, year, time) = match parts.as_slice() {
+        [day, month_name, year, time, "GMT"] => (*day, *month_name, *year, *time),
+        [month_name, day, time, year] => (*day, *month_name, *year, *time),
+        _ => return None,
+    };
+
+    let day: u64 = day.parse().ok()?;
+    let month = month(month_name)?;
+    let mut year: u64 = year.parse().ok()?;
+    if year < 100 {
+        // RFC 850 has two-digit years:
+        year += if year < 70 { 2000 } else { 1900 };
+    }
+    if year > MAX_YEAR {
+        return None;
+    }
+
+    let mut time = time.split(':').map(|part| part.parse::<u64>().ok());
+    let (hours, minutes, seconds) = (time.next()??, time.next()??, time.next()??);
+    if time.next().is_some()
+        || !(1..=31).contains(&day)
+        || hours > 23
+        || minutes > 59
+        || seconds > 60
+    {
+        return None;
+    }
+
+    let days = days_since_unix_epoch(year, month, day)?;
+    let seconds = days
+        .checked_mul(86_400)?
+        .checked_add(hours * 3_600 + minutes * 60 + seconds)?;
+    UNIX_EPOCH.checked_add(Duration::from_secs(seconds))
+}
+
+/// Format an [RFC 3339](https://www.rfc-editor.org/rfc/rfc3339) timestamp in UTC,
+/// with milliseconds, e.g. `1994-11-06T08:49:37.123Z`.
+#[cfg(feature = "har")]
+pub(crate) fn format_rfc3339(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days(seconds / 86_400);
+    let seconds_of_day = seconds % 86_400;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        seconds_of_day / 3_600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Parse an [RFC 3339](https://www.rfc-editor.org/rfc/rfc3339) timestamp,
+/// e.g. `1994-11-06T08:49:37.123Z` or `1994-11-06T10:49:37+02:00`.
+#[cfg(feature = "har")]
+pub(crate) fn parse_rfc3339(timestamp: &str) -> Option<SystemTime> {
+    use std::convert::TryFrom as _;
+
+    let (date, time) = timestamp.split_once(['T', 't', ' '])?;
+
+    let mut date = date.split('-').map(|part| part.parse::<u64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    if date.next().is_some()
+        || year > MAX_YEAR
+        || !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+    {
+        return None;
+    }
+
+    // The offset from UTC, in seconds:
+    let (time, offset) = if let Some(time) = time.strip_suffix(['Z', 'z']) {
+        (time, 0)
+    } else {
+        let sign_index = time.rfind(['+', '-'])?;
+        let (time, offset) = time.split_at(sign_index);
+        let (sign, offset) = offset.split_at(1);
+        let (hours, minutes) = offset.split_once(':')?;
+        let (hours, minutes) = (hours.parse::<i64>().ok()?, minutes.parse::<i64>().ok()?);
+        if !(0..=23).contains(&hours) || !(0..=59).contains(&minutes) {
+            return None;
+        }
+        let offset = hours * 3_600 + minutes * 60;
+        (time, if sign == "-" { -offset } else { offset })
+    };
+
+    let (time, fraction) = time.split_once('.').unwrap_or((time, ""));
+    let mut time = time.split(':').map(|part| part.parse::<u64>().ok());
+    let (hours, minutes, seconds) = (time.next()??, time.next()??, time.next()??);
+    if time.next().is_some() || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+    let nanos = if fraction.is_empty() {
+        0
+    } else {
+        let digits = &fraction[..fraction.len().min(9)];
+        digits.parse::<u32>().ok()? * 10_u32.pow(9 - digits.len() as u32)
+    };
+
+    let days = days_since_unix_epoch(year, month, day)?;
+    let seconds = days
+        .checked_mul(86_400)?
+        .checked_add(hours * 3_600 + minutes * 60 + seconds)?;
+    let seconds = i64::try_from(seconds).ok()?.checked_sub(offset)?;
+    let seconds = u64::try_from(seconds).ok()?;
+    UNIX_EPOCH.checked_add(Duration::new(seconds, nanos))
+}
+
+/// Later dates are rejected, so that the arithmetic can't overflow.
+const MAX_YEAR: u64 = 9999;
+
+/// 1-based month from its three-letter name.
+fn month(name: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let index = MONTHS.iter().position(|month| *month == name)?;
+    Some(index as u64 + 1)
+}
+
+/// The inverse of [`days_since_unix_epoch`]: `(year, month, day)`.
+///
+/// Based on <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+#[cfg(feature = "har")]
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153; // March is 0
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = (month_index + 2) % 12 + 1;
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Based on <http://howardhinnant.github.io/date_algorithms.html#days_from_civil>.
+///
+/// `None` for dates before 1970 or after [`MAX_YEAR`].
+fn days_since_unix_epoch(year: u64, month: u64, day: u64) -> Option<u64> {
+    if !(1970..=MAX_YEAR).contains(&year) || !(1..=12).contains(&month) || !(1..=31).contains(&day)
+    {
+        return None;
+    }
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    (era * 146_097 + day_of_era).checked_sub(719_468)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unix(seconds: u64) -> Option<SystemTime> {
+        Some(UNIX_EPOCH + Duration::from_secs(seconds))
+    }
+
+    #[test]
+    fn parse_formats() {
+        // 1994-11-06T08:49:37Z, the example of RFC 9110:
+        let expected = unix(784_111_777);
+        assert_eq!(parse("Sun, 06 Nov 1994 08:49:37 GMT"), expected); // IMF-fixdate
+        assert_eq!(parse("Sunday, 06-Nov-94 08:49:37 GMT"), expected); // RFC 850
+        assert_eq!(parse("Sun Nov  6 08:49:37 1994"), expected); // asctime
+        assert_eq!(parse("06 Nov 1994 08:49:37 GMT"), expected); // No day of the week
+
+        assert_eq!(parse("Thu, 01 Jan 1970 00:00:00 GMT"), unix(0));
+        assert_eq!(parse("Thursday, 01-Jan-70 00:00:00 GMT"), unix(0));
+        assert_eq!(parse("Tue, 29 Feb 2000 23:59:59 GMT"), unix(951_868_799));
+        assert_eq!(
+            parse("Fri, 31 Dec 9999 23:59:59 GMT"),
+            unix(253_402_300_799)
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for date in [
+            "",
+            "GMT",
+            "Sun, 06 Nov 1994 08:49:37",        // No time zone
+            "Sun, 06 Nov 1994 08:49:37 PST",    // Wrong time zone
+            "Sun, 06 Foo 1994 08:49:37 GMT",    // Unknown month
+            "Sun, 32 Nov 1994 08:49:37 GMT",    // Day out of range
+            "Sun, 00 Nov 1994 08:49:37 GMT",    // Day out of range
+            "Sun, 06 Nov 1994 24:00:00 GMT",    // Hour out of range
+            "Sun, 06 Nov 1994 08:60:00 GMT",    // Minute out of range
+            "Sun, 06 Nov 1994 08:49:61 GMT",    // Second out of range
+            "Sun, 06 Nov 1994 08:49 GMT",       // No seconds
+            "Sun, 06 Nov 1994 08:49:37:00 GMT", // Too many time parts
+            "Sun, 06 Nov 1969 08:49:37 GMT",    // Before the Unix epoch
+            "Sun, 06 Nov 1994 08:-9:37 GMT",
+        ] {
+            assert_eq!(parse(date), None, "{date:?}");
+        }
+    }
+
+    #[test]
+    fn parse_huge_years() {
+        for date in [
+            "Sat, 01 Jan 10000 00:00:00 GMT",
+            "Sun, 06 Nov 18446744073709551615 08:49:37 GMT",
+            "Sun, 06 Nov 99999999999999999999 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 18446744073709551615",
+            "Sun, 06 Nov 1994 18446744073709551615:49:37 GMT",
+        ] {
+            assert_eq!(parse(date), None, "{date:?}");
+        }
+    }
+
+    #[cfg(feature = "har")]
+    #[test]
+    fn rfc3339_round_trip() {
+        let time = UNIX_EPOCH + Duration::from_millis(784_111_777_123);
+        assert_eq!(format_rfc3339(time), "1994-11-06T08:49:37.123Z");
+        assert_eq!(parse_rfc3339("1994-11-06T08:49:37.123Z"), Some(time));
+        assert_eq!(parse_rfc3339("1994-11-06T10:49:37.123+02:00"), Some(time));
+        assert_eq!(parse_rfc3339("1994-11-06T06:19:37.123-02:30"), Some(time));
+        assert_eq!(parse_rfc3339("1994-11-06t08:49:37.123z"), Some(time));
+        assert_eq!(parse_rfc3339("1994-11-06T08:49:37Z"), unix(784_111_777));
+        assert_eq!(
+            parse_rfc3339("1994-11-06T08:49:37.123456789Z"),
+            Some(UNIX_EPOCH + Duration::new(784_111_777, 123_456_789))
+        );
+
+        let time = UNIX_EPOCH + Duration::from_millis(253_402_300_799_999);
+        assert_eq!(format_rfc3339(time), "9999-12-31T23:59:59.999Z");
+        assert_eq!(parse_rfc3339(&format_rfc3339(time)), Some(time));
+    }
+
+    #[cfg(feature = "har")]
+    #[test]
+    fn rfc3339_invalid() {
+        for timestamp in [
+            "",
+            "1994-11-06",
+            "1994-11-06T08:49:37",       // No offset
+            "1994-13-06T08:49:37Z",      // Month out of range
+            "1994-11-32T08:49:37Z",      // Day out of range
+            "1994-11-06T24:49:37Z",      // Hour out of range
+            "1994-11-06T08:49:37+24:00", // Offset out of range
+            "1994-11-06T08:49:37+99999999999999999:00",
+            "1970-01-01T00:00:00+01:00", // Before the Unix epoch
+            "10000-01-01T00:00:00Z",
+            "18446744073709551615-11-06T08:49:37Z",
+        ] {
+            assert_eq!(parse_rfc3339(timestamp), None, "{timestamp:?}");
+        }
+    }
+}
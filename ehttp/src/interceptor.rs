@@ -0,0 +1,205 @@
+use std::{
+    cell::Cell,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use crate::{Request, Response, Result};
+
+#[cfg(feature = "streaming")]
+use crate::{streaming::Part, PartialResponse};
+
+/// Inspects and modifies requests before they are sent, and their results once they complete.
+///
+/// Use it to add auth headers, log requests, rewrite URLs, post-process responses, …
+///
+/// Register it for all requests with [`add_global_interceptor`],
+/// or for the requests of a single client with [`crate::Client::with_interceptor`].
+///
+/// ```
+/// struct Auth(String);
+///
+/// impl ehttp::Interceptor for Auth {
+///     fn on_request(&self, request: &mut ehttp::Request) -> ehttp::Result<()> {
+///         request.headers.insert("Authorization", format!("Bearer {}", self.0));
+///         Ok(())
+///     }
+/// }
+///
+/// let client = ehttp::Client::new().with_interceptor(Auth("my-secret-token".to_owned()));
+/// ```
+pub trait Interceptor: Send + Sync {
+    /// Called before the request is sent.
+    ///
+    /// Return an error to fail the request without sending it.
+    /// The interceptors that have already seen the request then get the error in [`Self::on_response`].
+    fn on_request(&self, request: &mut Request) -> Result<()> {
+        let _ = request;
+        Ok(())
+    }
+
+    /// Called when a request completes, before the result is handed to the caller.
+    ///
+    /// `request` is the request as it was sent, i.e. after all [`Self::on_request`] calls.
+    ///
+    /// For the `streaming` API, [`Self::on_partial_response`] is called instead.
+    fn on_response(&self, request: &Request, result: &mut Result<Response>) {
+        let _ = (request, result);
+    }
+
+    /// Called when the status and headers of a streaming request have been received
+    /// (or the request failed before that).
+    ///
+    /// The chunks of the body are passed on unchanged.
+    #[cfg(feature = "streaming")]
+    fn on_partial_response(&self, request: &Request, result: &mut Result<PartialResponse>) {
+        let _ = (request, result);
+    }
+}
+
+static GLOBAL_INTERCEPTORS: RwLock<Vec<Arc<dyn Interceptor>>> = RwLock::new(Vec::new());
+
+/// Register an [`Interceptor`] that is used for all requests, by all clients.
+///
+/// Global interceptors see the request before the interceptors of the [`crate::Client`],
+/// and see the response after them.
+pub fn add_global_interceptor(interceptor: impl Interceptor + 'static) {
+    GLOBAL_INTERCEPTORS
+        .write()
+        .unwrap()
+        .push(Arc::new(interceptor));
+}
+
+/// Remove all interceptors registered with [`add_global_interceptor`].
+pub fn clear_global_interceptors() {
+    GLOBAL_INTERCEPTORS.write().unwrap().clear();
+}
+
+thread_local! {
+    /// The id of the request whose interceptors are being called on this thread, if any.
+    static CURRENT_REQUEST_ID: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// Identifies the request an [`Interceptor`] is called for,
+/// so that e.g. a response can be matched with its request even if the URL is the same.
+///
+/// `None` outside of interceptor calls.
+#[cfg(feature = "har")]
+pub(crate) fn current_request_id() -> Option<u64> {
+    CURRENT_REQUEST_ID.get()
+}
+
+/// The interceptors that apply to a single request, in the order they see the request.
+#[derive(Clone)]
+pub(crate) struct Interceptors {
+    interceptors: Vec<Arc<dyn Interceptor>>,
+
+    /// See [`current_request_id`].
+    request_id: u64,
+}
+
+impl Interceptors {
+    /// The global interceptors followed by those of the client.
+    pub fn new(client_interceptors: &[Arc<dyn Interceptor>]) -> Self {
+        static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(0);
+
+        let mut interceptors = GLOBAL_INTERCEPTORS.read().unwrap().clone();
+        interceptors.extend(client_interceptors.iter().cloned());
+        Self {
+            interceptors,
+            request_id: NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.interceptors.is_empty()
+    }
+
+    /// Call `f` with [`current_request_id`] set.
+    fn with_request_id<R>(&self, f: impl FnOnce() -> R) -> R {
+        let previous = CURRENT_REQUEST_ID.replace(Some(self.request_id));
+        let result = f();
+        CURRENT_REQUEST_ID.set(previous);
+        result
+    }
+
+    pub fn on_request(&self, request: &mut Request) -> Result<()> {
+        self.with_request_id(|| {
+            for (index, interceptor) in self.interceptors.iter().enumerate() {
+                if let Err(err) = interceptor.on_request(request) {
+                    // Let the interceptors that saw the request know that it won't be sent:
+                    let mut result = Err(err.clone());
+                    for interceptor in self.interceptors[..index].iter().rev() {
+                        interceptor.on_response(request, &mut result);
+                    }
+                    return Err(err);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    pub fn on_response(&self, request: &Request, result: &mut Result<Response>) {
+        self.with_request_id(|| {
+            for interceptor in self.interceptors.iter().rev() {
+                interceptor.on_response(request, result);
+            }
+        });
+    }
+
+    /// Calls [`Self::on_response`] before `on_done`.
+    pub fn wrap_on_done(
+        self,
+        request: &Request,
+        on_done: Box<dyn FnOnce(Result<Response>) + Send>,
+    ) -> Box<dyn FnOnce(Result<Response>) + Send> {
+        if self.is_empty() {
+            return on_done;
+        }
+
+        let request = request.clone();
+        Box::new(move |mut result| {
+            self.on_response(&request, &mut result);
+            on_done(result);
+        })
+    }
+
+    /// Calls [`Interceptor::on_partial_response`] for the response (or the error before it) before `on_data`.
+    #[cfg(feature = "streaming")]
+    pub fn wrap_on_data(
+        self,
+        request: &Request,
+        on_data: Box<dyn Fn(Result<Part>) -> std::ops::ControlFlow<()> + Send>,
+    ) -> Box<dyn Fn(Result<Part>) -> std::ops::ControlFlow<()> + Send> {
+        if self.is_empty() {
+            return on_data;
+        }
+
+        let request = request.clone();
+        let has_response = std::sync::atomic::AtomicBool::new(false);
+        Box::new(move |part| {
+            use std::sync::atomic::Ordering;
+
+            let mut partial_response = match part {
+                Ok(Part::Response(response)) => Ok(response),
+                Err(err) if !has_response.load(Ordering::Relaxed) => Err(err),
+                part => return on_data(part),
+            };
+            has_response.store(true, Ordering::Relaxed);
+
+            self.on_partial_response(&request, &mut partial_response);
+            on_data(partial_response.map(Part::Response))
+        })
+    }
+
+    #[cfg(feature = "streaming")]
+    pub fn on_partial_response(&self, request: &Request, result: &mut Result<PartialResponse>) {
+        self.with_request_id(|| {
+            for interceptor in self.interceptors.iter().rev() {
+                interceptor.on_partial_response(request, result);
+            }
+        });
+    }
+}
@@ -37,12 +37,14 @@
 /// * The initial GET which returned HTML contained CSP headers to block access to the resource
 /// * A browser extension blocked the request (e.g. ad blocker)
 /// * …
-pub fn fetch(request: Request, on_done: impl 'static + Send + FnOnce(Result<Response>)) {
-    #[cfg(not(target_arch = "wasm32"))]
-    native::fetch(request, Box::new(on_done));
-
-    #[cfg(target_arch = "wasm32")]
-    web::fetch(request, Box::new(on_done));
+///
+/// The returned [`RequestHandle`] can be used to abort the request,
+/// in which case `on_done` is called with [`Error::Aborted`].
+pub fn fetch(
+    request: Request,
+    on_done: impl 'static + Send + FnOnce(Result<Response>),
+) -> RequestHandle {
+    client::default_client().fetch(request, on_done)
 }
 
 /// Performs an `async` HTTP request.
@@ -67,37 +69,125 @@ pub fn fetch(request: Request, on_done: impl 'static + Send + FnOnce(Result<Resp
 /// * …
 #[cfg(any(target_arch = "wasm32", feature = "native-async"))]
 pub async fn fetch_async(request: Request) -> Result<Response> {
-    #[cfg(not(target_arch = "wasm32"))]
-    return native::fetch_async(request).await;
+    client::default_client().fetch_async(request).await
+}
 
-    #[cfg(target_arch = "wasm32")]
-    return web::fetch_async(&request).await;
+/// Like [`fetch_async`], but also returns a [`RequestHandle`] that can be used to abort the request.
+///
+/// The request is not sent until the returned future is polled.
+/// If it is aborted, the future resolves to [`Error::Aborted`].
+///
+/// ```
+/// # async fn example() {
+/// let (handle, response) = ehttp::fetch_async_with_handle(ehttp::Request::get("https://www.example.com"));
+/// handle.abort(); // Changed our mind
+/// assert!(matches!(response.await, Err(ehttp::Error::Aborted)));
+/// # }
+/// ```
+#[cfg(any(target_arch = "wasm32", feature = "native-async"))]
+pub fn fetch_async_with_handle(
+    request: Request,
+) -> (
+    RequestHandle,
+    impl std::future::Future<Output = Result<Response>>,
+) {
+    client::default_client().fetch_async_with_handle(request)
 }
 
+mod body;
+pub use body::Body;
+
+pub mod cache;
+
+mod client;
+pub use client::Client;
+
+mod handle;
+pub use handle::RequestHandle;
+
+mod http_date;
+
+mod interceptor;
+pub use interceptor::{add_global_interceptor, clear_global_interceptors, Interceptor};
+
+mod progress;
+pub use progress::{Progress, ProgressCallback};
+
+mod redirect;
+pub use redirect::{Redirect, RedirectPolicy};
+
+mod retry;
+pub use retry::RetryPolicy;
+
+mod status;
+pub use status::StatusCode;
+
+mod transport;
+pub use transport::{clear_global_transport, set_global_transport, Transport};
+
 mod types;
-pub use types::{Error, Headers, Method, PartialResponse, Request, Response, Result};
+pub use types::{Error, Headers, Method, PartialResponse, Request, Response, Result, StatusError};
+
+mod url;
+pub use crate::url::Url;
 
 #[cfg(target_arch = "wasm32")]
 pub use types::Credentials;
 #[cfg(target_arch = "wasm32")]
 pub use types::Mode;
 
+#[cfg(all(feature = "cassette", not(target_arch = "wasm32")))]
+pub mod cassette;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cookies;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod executor;
+#[cfg(not(target_arch = "wasm32"))]
+pub use executor::Executor;
+
 #[cfg(not(target_arch = "wasm32"))]
 mod native;
 #[cfg(not(target_arch = "wasm32"))]
 pub use native::fetch_blocking;
 
+#[cfg(not(target_arch = "wasm32"))]
+mod proxy;
+#[cfg(not(target_arch = "wasm32"))]
+pub use proxy::Proxy;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod tls;
+#[cfg(not(target_arch = "wasm32"))]
+pub use tls::{RootCertificates, TlsConfig};
+
 #[cfg(target_arch = "wasm32")]
 mod web;
 #[cfg(target_arch = "wasm32")]
 pub use web::spawn_future;
 
+#[cfg(feature = "har")]
+pub mod har;
+
+#[cfg(feature = "mock")]
+pub mod mock;
+
+#[cfg(all(feature = "json", feature = "streaming"))]
+pub mod ndjson;
+
+#[cfg(feature = "streaming")]
+pub mod sse;
+
 #[cfg(feature = "streaming")]
 pub mod streaming;
 
 #[cfg(feature = "multipart")]
 pub mod multipart;
 
+#[cfg(feature = "websocket")]
+pub mod ws;
+
 #[deprecated = "Use ehttp::Headers::new"]
 pub fn headers(headers: &[(&str, &str)]) -> Headers {
     Headers::new(headers)
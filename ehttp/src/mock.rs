@@ -0,0 +1,753 @@
+//! Fake the network in tests.
+//!
+//! Requires the `mock` feature to be enabled.
+//!
+//! While a [`MockServer`] is alive, requests go to it instead of the network.
+//! That includes [`crate::fetch`], [`crate::fetch_blocking`], [`crate::fetch_async`], [`crate::streaming::fetch`],
+//! and the methods of any [`crate::Client`] that doesn't have its own [`crate::Transport`].
+//!
+//! It works by setting the [`crate::set_global_transport`] while it is alive.
+//!
+//! ```
+//! use ehttp::mock::{Mock, MockResponse, MockServer};
+//!
+//! let server = MockServer::start();
+//! let users = server.mock(
+//!     Mock::get("https://api.example.com/users/*")
+//!         .with_header("Authorization", "Bearer my-secret-token")
+//!         .respond_with(MockResponse::new(200).with_body(r#"{"name":"Ferris"}"#))
+//!         .expect(1),
+//! );
+//!
+//! # #[cfg(not(target_arch = "wasm32"))]
+//! # {
+//! let request = ehttp::Request::get("https://api.example.com/users/42")
+//!     .with_header("Authorization", "Bearer my-secret-token");
+//! let response = ehttp::fetch_blocking(&request).unwrap();
+//! assert_eq!(response.text(), Some(r#"{"name":"Ferris"}"#));
+//! assert_eq!(users.hits(), 1);
+//! # }
+//!
+//! // Dropping the server panics if an expectation wasn't met,
+//! // or if a request didn't match any mock.
+//! drop(server);
+//! ```
+//!
+//! Mocks are tried in the order they were added, and the first one that matches the request is used.
+//! Requests that match no mock fail with an [`Error::Other`].
+//!
+//! Because the global transport is shared by the whole process,
+//! only one [`MockServer::start`]ed server can be active at a time.
+//! On native, [`MockServer::start`] waits for the previous one to be dropped,
+//! so tests that use it don't interfere with each other when run in parallel.
+//! But the lock only covers tests that use [`MockServer::start`]:
+//! a test running at the same time that doesn't use it also gets its requests answered by the mocks
+//! (and fails if they match none).
+//!
+//! To avoid that, use [`MockServer::new`] instead, which only answers the requests of clients
+//! that you give its [`MockServer::transport`] to:
+//!
+//! ```
+//! use ehttp::mock::{Mock, MockResponse, MockServer};
+//!
+//! let server = MockServer::new();
+//! server.mock(Mock::get("https://api.example.com/*").respond_with(MockResponse::new(204)));
+//! let client = ehttp::Client::new().with_transport(server.transport());
+//!
+//! # #[cfg(not(target_arch = "wasm32"))]
+//! # {
+//! let response = client.fetch_blocking(&ehttp::Request::get("https://api.example.com/ping"));
+//! assert_eq!(response.unwrap().status, 204);
+//! # }
+//! ```
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    time::Duration,
+};
+
+use crate::{
+    types::PartialResponse, Error, Headers, Method, Request, RequestHandle, Response, Result,
+    StatusCode, Transport,
+};
+
+#[cfg(feature = "streaming")]
+use {crate::streaming::Part, std::ops::ControlFlow};
+
+/// Held by the active [`MockServer`].
+static ONE_AT_A_TIME: Mutex<()> = Mutex::new(());
+
+/// Answers all requests while it is alive, according to the [`Mock`]s added to it.
+///
+/// See the [module-level docs](self).
+pub struct MockServer {
+    routes: Arc<Routes>,
+
+    /// Only set if we are the global transport.
+    global: Option<MutexGuard<'static, ()>>,
+}
+
+impl MockServer {
+    /// Start intercepting all requests, by setting the global transport.
+    ///
+    /// Requests are intercepted until the server is dropped.
+    /// On native, this waits until any other server started this way is dropped
+    /// (see the [module-level docs](self)).
+    pub fn start() -> Self {
+        // A test that panicked while holding the lock is no reason to fail the next one:
+        let lock = ONE_AT_A_TIME.lock().unwrap_or_else(PoisonError::into_inner);
+        let routes = Arc::new(Routes::default());
+        crate::transport::replace_global_transport(Some(routes.clone()));
+        Self {
+            routes,
+            global: Some(lock),
+        }
+    }
+
+    /// A server that only answers the requests of the clients you give its [`Self::transport`] to.
+    ///
+    /// Unlike [`Self::start`], this doesn't affect any other requests,
+    /// so any number of them can be used at the same time.
+    pub fn new() -> Self {
+        Self {
+            routes: Arc::new(Routes::default()),
+            global: None,
+        }
+    }
+
+    /// The transport that answers requests with the mocks of this server,
+    /// for [`crate::Client::with_transport`].
+    ///
+    /// Requests it gets after the server is dropped still use the mocks,
+    /// but are no longer verified.
+    pub fn transport(&self) -> Arc<dyn Transport> {
+        self.routes.clone()
+    }
+
+    /// Answer the requests that match the given [`Mock`].
+    ///
+    /// The returned handle tells you how many requests it answered.
+    pub fn mock(&self, mock: Mock) -> MockHandle {
+        let registered = Arc::new(Registered {
+            mock,
+            hits: AtomicUsize::new(0),
+        });
+        self.routes.mocks.lock().unwrap().push(registered.clone());
+        MockHandle(registered)
+    }
+
+    /// All the requests received so far, matched or not, in order.
+    pub fn received_requests(&self) -> Vec<Request> {
+        self.routes.received.lock().unwrap().clone()
+    }
+
+    /// Panics if a [`Mock::expect`]ation isn't met, or if a request didn't match any mock.
+    ///
+    /// This is also done when the server is dropped.
+    pub fn verify(&self) {
+        let mut failures = vec![];
+        for registered in self.routes.mocks.lock().unwrap().iter() {
+            let hits = registered.hits.load(Ordering::SeqCst);
+            if let Some(expected_hits) = registered.mock.expected_hits {
+                if hits != expected_hits {
+                    failures.push(format!(
+                        "Expected {expected_hits} request(s) to {}, but got {hits}",
+                        registered.mock.describe()
+                    ));
+                }
+            }
+        }
+        for unmatched in self.routes.unmatched.lock().unwrap().iter() {
+            failures.push(format!("No mock matched {unmatched}"));
+        }
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}
+
+impl Default for MockServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if self.global.is_some() {
+            crate::transport::replace_global_transport(None);
+        }
+        if !std::thread::panicking() {
+            self.verify();
+        }
+    }
+}
+
+impl std::fmt::Debug for MockServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockServer")
+            .field("mocks", &self.routes.mocks.lock().unwrap().len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Returned by [`MockServer::mock`].
+#[derive(Clone)]
+pub struct MockHandle(Arc<Registered>);
+
+impl MockHandle {
+    /// How many requests the mock has answered so far.
+    pub fn hits(&self) -> usize {
+        self.0.hits.load(Ordering::SeqCst)
+    }
+}
+
+impl std::fmt::Debug for MockHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockHandle")
+            .field("mock", &self.0.mock.describe())
+            .field("hits", &self.hits())
+            .finish()
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Which requests to answer, and how.
+///
+/// By default, matching requests get an empty `200 OK` response.
+#[derive(Clone)]
+pub struct Mock {
+    /// `None` matches any method.
+    method: Option<Method>,
+
+    /// `*` matches any number of characters.
+    url: String,
+
+    headers: Vec<(String, String)>,
+    body: Option<Vec<u8>>,
+
+    #[expect(clippy::type_complexity)]
+    matchers: Vec<Arc<dyn Fn(&Request) -> bool + Send + Sync>>,
+
+    reply: Reply,
+    delay: Duration,
+    expected_hits: Option<usize>,
+}
+
+#[derive(Clone)]
+enum Reply {
+    Response(MockResponse),
+    Error(Error),
+}
+
+impl Mock {
+    /// Match requests with the given method and URL.
+    ///
+    /// A `*` in the URL matches any number of characters,
+    /// e.g. `"https://example.com/users/*"` matches `"https://example.com/users/42?details=true"`.
+    ///
+    /// The URL is normalized like the URLs of requests are (see [`crate::Url`]),
+    /// so `"https://example.com"` matches requests to `"https://example.com/"`.
+    pub fn new(method: Method, url: impl ToString) -> Self {
+        Self {
+            method: Some(method),
+            ..Self::any(url)
+        }
+    }
+
+    /// Match requests with the given URL and any method.
+    ///
+    /// See [`Self::new`].
+    pub fn any(url: impl ToString) -> Self {
+        let url = url.to_string();
+        Self {
+            method: None,
+            url: crate::url::normalize(&url).unwrap_or(url),
+            headers: vec![],
+            body: None,
+            matchers: vec![],
+            reply: Reply::Response(MockResponse::new(200)),
+            delay: Duration::ZERO,
+            expected_hits: None,
+        }
+    }
+
+    /// Match `GET` requests with the given URL. See [`Self::new`].
+    pub fn get(url: impl ToString) -> Self {
+        Self::new(Method::GET, url)
+    }
+
+    /// Match `POST` requests with the given URL. See [`Self::new`].
+    pub fn post(url: impl ToString) -> Self {
+        Self::new(Method::POST, url)
+    }
+
+    /// Match `PUT` requests with the given URL. See [`Self::new`].
+    pub fn put(url: impl ToString) -> Self {
+        Self::new(Method::PUT, url)
+    }
+
+    /// Match `DELETE` requests with the given URL. See [`Self::new`].
+    pub fn delete(url: impl ToString) -> Self {
+        Self::new(Method::DELETE, url)
+    }
+
+    /// Only match requests that have this header (the key is case-insensitive).
+    pub fn with_header(mut self, key: impl ToString, value: impl ToString) -> Self {
+        self.headers.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Only match requests with exactly this body.
+    ///
+    /// Requests with a streamed [`crate::Body`] never match.
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    /// Only match requests for which the given function returns `true`.
+    pub fn with_matcher(
+        mut self,
+        matcher: impl Fn(&Request) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.matchers.push(Arc::new(matcher));
+        self
+    }
+
+    /// Answer with this response.
+    pub fn respond_with(mut self, response: MockResponse) -> Self {
+        self.reply = Reply::Response(response);
+        self
+    }
+
+    /// Fail the request with this error, e.g. [`Error::Timeout`].
+    pub fn fail_with(mut self, error: Error) -> Self {
+        self.reply = Reply::Error(error);
+        self
+    }
+
+    /// Wait this long before answering.
+    ///
+    /// The request can be aborted while waiting.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Expect exactly this many requests to match, which [`MockServer::verify`] checks.
+    pub fn expect(mut self, hits: usize) -> Self {
+        self.expected_hits = Some(hits);
+        self
+    }
+
+    fn matches(&self, request: &Request) -> bool {
+        self.method
+            .as_ref()
+            .is_none_or(|method| *method == request.method)
+            && wildcard_match(&self.url, &request.url)
+            && self
+                .headers
+                .iter()
+                .all(|(key, value)| request.headers.get_all(key).any(|v| v == value))
+            && self
+                .body
+                .as_ref()
+                .is_none_or(|body| request.body.as_bytes() == Some(body.as_slice()))
+            && self.matchers.iter().all(|matcher| matcher(request))
+    }
+
+    fn describe(&self) -> String {
+        match &self.method {
+            Some(method) => format!("{} {}", method.as_str(), self.url),
+            None => self.url.clone(),
+        }
+    }
+}
+
+impl std::fmt::Debug for Mock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Mock")
+            .field("method", &self.method)
+            .field("url", &self.url)
+            .field("headers", &self.headers)
+            .field("expected_hits", &self.expected_hits)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A canned response for a [`Mock`].
+#[derive(Clone, Debug)]
+pub struct MockResponse {
+    status: u16,
+    headers: Headers,
+    chunks: Vec<Vec<u8>>,
+}
+
+impl MockResponse {
+    /// An empty response with the given status code.
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: Headers::default(),
+            chunks: vec![],
+        }
+    }
+
+    /// Add a header.
+    pub fn with_header(mut self, key: impl ToString, value: impl ToString) -> Self {
+        self.headers.insert(key, value);
+        self
+    }
+
+    /// Set the body.
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.chunks = vec![body.into()];
+        self
+    }
+
+    /// Set the body to the given value as JSON, and the `Content-Type` to `application/json`.
+    #[cfg(feature = "json")]
+    pub fn with_json<T: serde::Serialize + ?Sized>(self, body: &T) -> serde_json::Result<Self> {
+        Ok(self
+            .with_header("Content-Type", "application/json")
+            .with_body(serde_json::to_vec(body)?))
+    }
+
+    /// Set the body, which the [`crate::streaming`] API receives as these separate chunks.
+    ///
+    /// Everything else gets them joined together.
+    pub fn with_chunks(mut self, chunks: impl IntoIterator<Item = impl Into<Vec<u8>>>) -> Self {
+        self.chunks = chunks.into_iter().map(Into::into).collect();
+        self
+    }
+
+    fn partial_response(&self, url: &str) -> PartialResponse {
+        let mut headers = self.headers.clone();
+        headers.sort(); // Like the real backends
+        PartialResponse {
+            url: url.to_owned(),
+            ok: (200..300).contains(&self.status),
+            status: self.status,
+            status_text: StatusCode::from(self.status)
+                .canonical_reason()
+                .unwrap_or_default()
+                .to_owned(),
+            headers,
+            redirects: vec![],
+        }
+    }
+}
+
+/// Does `text` match `pattern`, where `*` matches any number of characters?
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = parts.peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            // The last part must be at the very end:
+            return rest.len() >= part.len() && rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.is_empty() // There was no `*`
+}
+
+// ----------------------------------------------------------------------------
+
+#[derive(Default)]
+struct Routes {
+    mocks: Mutex<Vec<Arc<Registered>>>,
+    received: Mutex<Vec<Request>>,
+
+    /// Descriptions of the requests that matched no mock.
+    unmatched: Mutex<Vec<String>>,
+}
+
+struct Registered {
+    mock: Mock,
+    hits: AtomicUsize,
+}
+
+impl Routes {
+    /// Find the reply for the request, and how long to wait before sending it.
+    fn route(&self, request: &Request) -> (Reply, Duration) {
+        self.received.lock().unwrap().push(request.clone());
+
+        let mocks = self.mocks.lock().unwrap();
+        if let Some(registered) = mocks.iter().find(|r| r.mock.matches(request)) {
+            registered.hits.fetch_add(1, Ordering::SeqCst);
+            return (registered.mock.reply.clone(), registered.mock.delay);
+        }
+
+        let description = format!("{} {}", request.method.as_str(), request.url);
+        let error = Error::Other(format!("No mock matched {description}"));
+        self.unmatched.lock().unwrap().push(description);
+        (Reply::Error(error), Duration::ZERO)
+    }
+}
+
+impl Transport for Routes {
+    fn fetch(
+        &self,
+        request: Request,
+        handle: RequestHandle,
+        on_done: Box<dyn FnOnce(Result<Response>) + Send>,
+    ) {
+        let (reply, delay) = self.route(&request);
+        after(delay, handle, move |handle| {
+            on_done(match (handle, reply) {
+                (Err(err), _) => Err(err),
+                (Ok(handle), _) if handle.is_aborted() => Err(Error::Aborted),
+                (Ok(_), Reply::Response(response)) => Ok(response
+                    .partial_response(&request.url)
+                    .complete(response.chunks.concat())),
+                (Ok(_), Reply::Error(err)) => Err(err),
+            });
+        });
+    }
+
+    #[cfg(feature = "streaming")]
+    fn fetch_streaming(
+        &self,
+        request: Request,
+        handle: RequestHandle,
+        on_data: Box<dyn Fn(Result<Part>) -> ControlFlow<()> + Send>,
+    ) {
+        let (reply, delay) = self.route(&request);
+        after(delay, handle, move |handle| {
+            let handle = match handle {
+                Ok(handle) => handle,
+                Err(err) => {
+                    let _ = on_data(Err(err));
+                    return;
+                }
+            };
+            let response = match reply {
+                _ if handle.is_aborted() => {
+                    let _ = on_data(Err(Error::Aborted));
+                    return;
+                }
+                Reply::Response(response) => response,
+                Reply::Error(err) => {
+                    let _ = on_data(Err(err));
+                    return;
+                }
+            };
+            let partial_response = response.partial_response(&request.url);
+            if on_data(Ok(Part::Response(partial_response))).is_break() {
+                return;
+            }
+            for chunk in response.chunks {
+                if handle.is_aborted() {
+                    let _ = on_data(Err(Error::Aborted));
+                    return;
+                }
+                if !chunk.is_empty() && on_data(Ok(Part::Chunk(chunk))).is_break() {
+                    return;
+                }
+            }
+            let _ = on_data(Ok(Part::Chunk(vec![])));
+        });
+    }
+}
+
+/// Call `f` after the delay: right away if there is none, and otherwise in the background.
+///
+/// `f` gets an error instead of the handle if we couldn't wait in the background.
+fn after(
+    delay: Duration,
+    handle: RequestHandle,
+    f: impl FnOnce(Result<RequestHandle>) + Send + 'static,
+) {
+    if delay.is_zero() {
+        f(Ok(handle));
+        return;
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    crate::executor::spawn_dedicated_thread(Box::new(move |spawned| {
+        f(spawned.map(|()| {
+            // Wakes up early if the request is aborted, which `f` then reports:
+            let _ = crate::native::sleep(delay, &handle);
+            handle
+        }));
+    }));
+
+    #[cfg(target_arch = "wasm32")]
+    crate::web::spawn_future(async move {
+        crate::web::sleep(delay).await;
+        f(Ok(handle));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcards() {
+        let cases = [
+            ("https://a.com/", "https://a.com/", true),
+            ("https://a.com/", "https://a.com/x", false),
+            ("https://a.com/x", "https://a.com/", false),
+            ("*", "", true),
+            ("*", "anything", true),
+            ("**", "anything", true),
+            ("*/users", "https://a.com/users", true),
+            ("*/users", "https://a.com/users/42", false),
+            ("https://a.com/*", "https://a.com/", true),
+            ("https://a.com/*", "https://a.com/users/42?x=1", true),
+            ("https://a.com/*", "https://b.com/", false),
+            ("https://*.com/*/42", "https://a.com/users/42", true),
+            ("https://*.com/*/42", "https://a.com/users/43", false),
+            ("https://a.com/**/42", "https://a.com/users/42", true),
+            ("*a*", "bab", true),
+            ("*a*", "bbb", false),
+            ("a*a", "a", false), // The parts can't overlap
+            ("a*a", "aa", true),
+            ("ab*b*b", "abbb", true),
+            ("ab*b*b", "abb", false),
+            ("a*b*c", "acb", false),
+        ];
+        for (pattern, text, expected) in cases {
+            assert_eq!(
+                wildcard_match(pattern, text),
+                expected,
+                "{pattern:?} {text:?}"
+            );
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn matching() {
+        let server = MockServer::start();
+        let with_body = server.mock(
+            Mock::post("https://a.com/users")
+                .with_body("ferris")
+                .respond_with(MockResponse::new(201)),
+        );
+        let with_header = server.mock(
+            Mock::any("https://a.com/*")
+                .with_header("authorization", "secret")
+                .respond_with(MockResponse::new(200).with_body("authorized")),
+        );
+        let with_matcher = server.mock(
+            Mock::get("https://a.com/*")
+                .with_matcher(|request| request.url.ends_with("?page=2"))
+                .respond_with(MockResponse::new(200).with_body("page 2")),
+        );
+        let fallback =
+            server.mock(Mock::any("https://a.com/*").respond_with(MockResponse::new(404)));
+
+        let fetch = |request: Request| crate::fetch_blocking(&request).unwrap();
+
+        assert_eq!(
+            fetch(Request::post("https://a.com/users", b"ferris".to_vec())).status,
+            201
+        );
+        // Same URL but another body or method, so only the header mock could match:
+        let response = fetch(
+            Request::post("https://a.com/users", b"crab".to_vec())
+                .with_header("Authorization", "secret"),
+        );
+        assert_eq!(response.text(), Some("authorized"));
+        assert_eq!(fetch(Request::get("https://a.com/users")).status, 404);
+
+        // The first matching mock wins:
+        let response =
+            fetch(Request::get("https://a.com/?page=2").with_header("Authorization", "secret"));
+        assert_eq!(response.text(), Some("authorized"));
+        let response = fetch(Request::get("https://a.com/?page=2"));
+        assert_eq!(response.text(), Some("page 2"));
+        assert_eq!(fetch(Request::get("HTTPS://A.COM")).status, 404);
+
+        assert_eq!(with_body.hits(), 1);
+        assert_eq!(with_header.hits(), 2);
+        assert_eq!(with_matcher.hits(), 1);
+        assert_eq!(fallback.hits(), 2);
+        assert_eq!(server.received_requests().len(), 6);
+        assert_eq!(server.received_requests()[5].url, "https://a.com/");
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn errors_and_delays() {
+        let server = MockServer::start();
+        server
+            .mock(Mock::get("https://a.com/timeout").fail_with(Error::Timeout("slow".to_owned())));
+        server.mock(Mock::get("https://a.com/slow").with_delay(Duration::from_secs(60)));
+
+        let result = crate::fetch_blocking(&Request::get("https://a.com/timeout"));
+        assert!(matches!(result, Err(Error::Timeout(_))), "{:?}", result);
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let handle =
+            crate::Client::new().fetch(Request::get("https://a.com/slow"), move |result| {
+                tx.send(result).unwrap();
+            });
+        handle.abort();
+        let result = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(matches!(result, Err(Error::Aborted)), "{:?}", result);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn servers_per_client() {
+        // Not global, so these can run at the same time as the other tests:
+        let first = MockServer::new();
+        first.mock(Mock::get("https://a.com/").respond_with(MockResponse::new(200).with_body("1")));
+        let second = MockServer::new();
+        second.mock(
+            Mock::get("https://a.com/")
+                .respond_with(MockResponse::new(200).with_body("2"))
+                .with_delay(Duration::from_millis(10))
+                .expect(1),
+        );
+
+        let request = Request::get("https://a.com/");
+        let fetch = |server: &MockServer| {
+            crate::Client::new()
+                .with_transport(server.transport())
+                .fetch_blocking(&request)
+                .unwrap()
+        };
+        assert_eq!(fetch(&first).text(), Some("1"));
+        assert_eq!(fetch(&second).text(), Some("2"));
+        assert_eq!(first.received_requests().len(), 1);
+        assert_eq!(second.received_requests().len(), 1);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    #[should_panic = "No mock matched GET https://a.com/other"]
+    fn unmatched_requests_fail() {
+        let server = MockServer::start();
+        server.mock(Mock::get("https://a.com/"));
+
+        let result = crate::fetch_blocking(&Request::get("https://a.com/other"));
+        assert!(matches!(result, Err(Error::Other(_))), "{:?}", result);
+        drop(server);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    #[should_panic = "Expected 2 request(s) to GET https://a.com/, but got 1"]
+    fn unmet_expectations_fail() {
+        let server = MockServer::start();
+        server.mock(Mock::get("https://a.com").expect(2));
+
+        crate::fetch_blocking(&Request::get("https://a.com/")).unwrap();
+        server.verify();
+    }
+}
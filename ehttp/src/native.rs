@@ -1,4 +1,6 @@
-use crate::{Method, Request, Response};
+use std::time::Instant;
+
+use crate::{client::Backend, Error, Executor, Method, Redirect, Request, RequestHandle, Response};
 
 #[cfg(feature = "native-async")]
 use async_channel::{Receiver, Sender};
@@ -24,7 +26,79 @@ use async_channel::{Receiver, Sender};
 /// * A browser extension blocked the request (e.g. ad blocker)
 /// * …
 pub fn fetch_blocking(request: &Request) -> crate::Result<Response> {
-    let mut resp = request.fetch_raw_native(true)?;
+    crate::client::default_client().fetch_blocking(request)
+}
+
+/// Like [`fetch_blocking`], but uses the given backend,
+/// and stops reading the body once the request is aborted.
+pub(crate) fn fetch_blocking_with(
+    backend: &Backend,
+    request: &Request,
+    handle: &RequestHandle,
+) -> crate::Result<Response> {
+    let Some(cache) = &backend.cache else {
+        return fetch_with_retries(backend, request, handle);
+    };
+
+    match crate::cache::lookup(&**cache, request) {
+        crate::cache::Lookup::Fresh(response) => Ok(response),
+        crate::cache::Lookup::Send(conditional, pending) => {
+            let to_send = conditional.as_deref().unwrap_or(request);
+            let result = fetch_with_retries(backend, to_send, handle);
+            pending.finish(&**cache, request, result)
+        }
+    }
+}
+
+/// Retries according to [`Request::retry`].
+fn fetch_with_retries(
+    backend: &Backend,
+    request: &Request,
+    handle: &RequestHandle,
+) -> crate::Result<Response> {
+    let Some(retry) = &request.retry else {
+        return fetch_once(backend, request, handle);
+    };
+
+    let mut request = request.clone();
+    retry.prepare(&mut request);
+
+    let mut attempt = 1;
+    loop {
+        let result = fetch_once(backend, &request, handle);
+        let Some(delay) = retry.delay_before_retry(&request, attempt, &result) else {
+            return result;
+        };
+        sleep(delay, handle)?;
+        attempt += 1;
+    }
+}
+
+/// Sleep, but wake up early if the request is aborted.
+pub(crate) fn sleep(duration: std::time::Duration, handle: &RequestHandle) -> crate::Result<()> {
+    let deadline = std::time::Instant::now() + duration;
+    loop {
+        if handle.is_aborted() {
+            return Err(Error::Aborted);
+        }
+        let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+        if remaining.is_zero() {
+            return Ok(());
+        }
+        std::thread::sleep(remaining.min(std::time::Duration::from_millis(100)));
+    }
+}
+
+fn fetch_once(
+    backend: &Backend,
+    request: &Request,
+    handle: &RequestHandle,
+) -> crate::Result<Response> {
+    if handle.is_aborted() {
+        return Err(Error::Aborted);
+    }
+
+    let (mut resp, redirects) = send(backend, request, true)?;
 
     let ok = resp.status().is_success();
     use ureq::ResponseExt as _;
@@ -39,30 +113,41 @@ pub fn fetch_blocking(request: &Request) -> crate::Result<Response> {
     for (k, v) in resp.headers().iter() {
         headers.insert(
             k,
-            v.to_str()
-                .map_err(|e| format!("Failed to convert header value to string: {e}"))?,
+            v.to_str().map_err(|e| {
+                Error::Other(format!("Failed to convert header value to string: {e}"))
+            })?,
         );
     }
     headers.sort(); // It reads nicer, and matches web backend.
 
+    let mut progress = request.download_progress.clone().map(|callback| {
+        crate::progress::ProgressTracker::start(callback, crate::progress::expected_size(&headers))
+    });
+
     let mut reader = resp.body_mut().as_reader();
     let mut bytes = vec![];
-    use std::io::Read as _;
-    if let Err(err) = reader.read_to_end(&mut bytes) {
-        if err.kind() == std::io::ErrorKind::Other && request.method == Method::HEAD {
-            match err.downcast::<ureq::Error>() {
-                Ok(ureq::Error::Decompress(_, io_err))
-                    if io_err.kind() == std::io::ErrorKind::UnexpectedEof =>
-                {
-                    // We don't really expect a body for HEAD requests, so this is fine.
+    let mut buf = vec![0; 16 * 1024];
+    loop {
+        if handle.is_aborted() {
+            return Err(Error::Aborted);
+        }
+
+        use std::io::Read as _;
+        match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                bytes.extend_from_slice(&buf[..n]);
+                if let Some(progress) = &mut progress {
+                    progress.add(n as u64);
                 }
-                Ok(err_inner) => return Err(format!("Failed to read response body: {err_inner}")),
-                Err(err) => {
-                    return Err(format!("Failed to read response body: {err}"));
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(err) => {
+                if let Some(err) = body_read_error(err, &request.method) {
+                    return Err(err);
                 }
+                break;
             }
-        } else {
-            return Err(format!("Failed to read response body: {err}"));
         }
     }
 
@@ -72,30 +157,317 @@ pub fn fetch_blocking(request: &Request) -> crate::Result<Response> {
         status,
         status_text,
         headers,
+        redirects,
         bytes,
     };
     Ok(response)
 }
 
+/// Sends the request with the agent of the backend,
+/// following redirects according to [`Request::redirect_policy`].
+///
+/// Also returns the redirects that were followed.
+pub(crate) fn send(
+    backend: &Backend,
+    request: &Request,
+    with_timeout: bool,
+) -> crate::Result<(ureq::http::Response<ureq::Body>, Vec<Redirect>)> {
+    // The timeout is for all the redirects together, not for each of them:
+    let deadline = request
+        .timeout
+        .filter(|_| with_timeout)
+        .map(|timeout| (timeout, Instant::now() + timeout));
+
+    let mut redirects: Vec<Redirect> = vec![];
+    let mut next_request: Option<Request> = None;
+    loop {
+        let request = next_request.as_ref().unwrap_or(request);
+        let resp = send_once(backend, request, with_timeout)?;
+
+        let status = resp.status().as_u16();
+        let location = resp
+            .headers()
+            .get(ureq::http::header::LOCATION)
+            .and_then(|location| location.to_str().ok());
+        let location = match (status, location) {
+            (301 | 302 | 303 | 307 | 308, Some(location)) => location,
+            _ => return Ok((resp, redirects)),
+        };
+
+        let redirect = Redirect {
+            url: request.url.clone(),
+            status,
+            location: crate::redirect::resolve_location(&request.url, location)?,
+        };
+        if !request
+            .redirect_policy
+            .should_follow(&redirect, &redirects)?
+        {
+            return Ok((resp, redirects));
+        }
+        let mut next = crate::redirect::follow(request, &redirect);
+        if let Some((timeout, deadline)) = deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(Error::Timeout(format!(
+                    "Timed out after {timeout:?} while following redirects"
+                )));
+            }
+            next.timeout = Some(remaining);
+        }
+        if !next.body.is_empty() && !next.body.can_be_resent() {
+            // The body has already been consumed, so we can't send it along:
+            return Ok((resp, redirects));
+        }
+        next_request = Some(next);
+        redirects.push(redirect);
+    }
+}
+
+/// Sends a single request (without following redirects), and handles cookies.
+fn send_once(
+    backend: &Backend,
+    request: &Request,
+    with_timeout: bool,
+) -> crate::Result<ureq::http::Response<ureq::Body>> {
+    let Some(cookie_jar) = &backend.cookie_jar else {
+        return request.fetch_raw_native_with_agent(&backend.agent, with_timeout);
+    };
+
+    let cookie_header = cookie_jar.cookie_header(&request.url);
+    let resp = match cookie_header {
+        Some(cookie_header) if request.headers.get("Cookie").is_none() => {
+            let mut request = request.clone();
+            request.headers.insert("Cookie", cookie_header);
+            request.fetch_raw_native_with_agent(&backend.agent, with_timeout)?
+        }
+        _ => request.fetch_raw_native_with_agent(&backend.agent, with_timeout)?,
+    };
+
+    for set_cookie in resp.headers().get_all(ureq::http::header::SET_COOKIE) {
+        if let Ok(set_cookie) = set_cookie.to_str() {
+            cookie_jar.set_cookie(&request.url, set_cookie);
+        }
+    }
+
+    Ok(resp)
+}
+
 // ----------------------------------------------------------------------------
 
-pub(crate) fn fetch(request: Request, on_done: Box<dyn FnOnce(crate::Result<Response>) + Send>) {
-    std::thread::Builder::new()
-        .name("ehttp".to_owned())
-        .spawn(move || on_done(fetch_blocking(&request)))
-        .expect("Failed to spawn ehttp thread");
+pub(crate) fn fetch(
+    executor: &Executor,
+    backend: Backend,
+    request: Request,
+    handle: RequestHandle,
+    on_done: Box<dyn FnOnce(crate::Result<Response>) + Send>,
+) {
+    executor.spawn(Box::new(move |scheduled| match scheduled {
+        Ok(()) => on_done(crate::executor::catch_panic(|| {
+            fetch_blocking_with(&backend, &request, &handle)
+        })),
+        Err(err) => on_done(Err(err)),
+    }));
 }
 
 #[cfg(feature = "native-async")]
-pub(crate) async fn fetch_async(request: Request) -> crate::Result<Response> {
+pub(crate) async fn fetch_async(
+    executor: &Executor,
+    backend: Backend,
+    request: Request,
+    handle: RequestHandle,
+) -> crate::Result<Response> {
     let (tx, rx): (
         Sender<crate::Result<Response>>,
         Receiver<crate::Result<Response>>,
     ) = async_channel::bounded(1);
 
     fetch(
+        executor,
+        backend,
         request,
+        handle,
         Box::new(move |received| tx.send_blocking(received).unwrap()),
     );
-    rx.recv().await.map_err(|err| err.to_string())?
+    rx.recv()
+        .await
+        .map_err(|err| Error::Other(err.to_string()))?
+}
+
+// ----------------------------------------------------------------------------
+
+/// Converts an error from reading the response body into an [`Error`].
+///
+/// Returns `None` if the error can be ignored.
+pub(crate) fn body_read_error(err: std::io::Error, method: &Method) -> Option<Error> {
+    if err.kind() == std::io::ErrorKind::TimedOut {
+        return Some(Error::Timeout(format!(
+            "Failed to read response body: {err}"
+        )));
+    }
+    if err.kind() != std::io::ErrorKind::Other {
+        return Some(Error::BodyRead(format!(
+            "Failed to read response body: {err}"
+        )));
+    }
+    match err.downcast::<ureq::Error>() {
+        Ok(ureq::Error::Decompress(_, io_err))
+            if *method == Method::HEAD && io_err.kind() == std::io::ErrorKind::UnexpectedEof =>
+        {
+            // We don't really expect a body for HEAD requests, so this is fine.
+            None
+        }
+        Ok(err @ ureq::Error::Timeout(_)) => Some(Error::Timeout(format!(
+            "Failed to read response body: {err}"
+        ))),
+        Ok(err) => Some(Error::BodyRead(format!(
+            "Failed to read response body: {err}"
+        ))),
+        Err(err) => Some(Error::BodyRead(format!(
+            "Failed to read response body: {err}"
+        ))),
+    }
+}
+
+impl From<ureq::Error> for Error {
+    fn from(err: ureq::Error) -> Self {
+        let msg = err.to_string();
+        match err {
+            ureq::Error::Rustls(ref tls_err) => {
+                crate::tls::certificate_error(tls_err).map_or(Self::Tls(msg), Self::Certificate)
+            }
+            ureq::Error::Io(ref io_err) => {
+                // Rejected certificates are reported as I/O errors:
+                match io_err
+                    .get_ref()
+                    .and_then(|inner| crate::tls::certificate_error(inner))
+                {
+                    Some(certificate_err) => Self::Certificate(certificate_err),
+                    None => io_error(io_err, msg),
+                }
+            }
+            ureq::Error::Timeout(_) => Self::Timeout(msg),
+            ureq::Error::HostNotFound => Self::Dns(msg),
+            ureq::Error::BadUri(_) | ureq::Error::RequireHttpsOnly(_) => Self::InvalidUrl(msg),
+            ureq::Error::ConnectionFailed
+            | ureq::Error::ConnectProxyFailed(_)
+            | ureq::Error::InvalidProxyUrl => Self::Connect(msg),
+            ureq::Error::Tls(_) | ureq::Error::Pem(_) | ureq::Error::TlsRequired => Self::Tls(msg),
+            ureq::Error::Decompress(..) => Self::BodyRead(msg),
+            ureq::Error::Protocol(_) | ureq::Error::LargeResponseHeader(..) => Self::Network(msg),
+            _ => Self::Other(msg),
+        }
+    }
+}
+
+fn io_error(err: &std::io::Error, msg: String) -> Error {
+    use std::io::ErrorKind;
+
+    match err.kind() {
+        ErrorKind::TimedOut => Error::Timeout(msg),
+        ErrorKind::ConnectionRefused
+        | ErrorKind::ConnectionReset
+        | ErrorKind::ConnectionAborted
+        | ErrorKind::NotConnected
+        | ErrorKind::AddrNotAvailable
+        | ErrorKind::HostUnreachable
+        | ErrorKind::NetworkUnreachable => Error::Connect(msg),
+        _ => Error::Network(msg),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead as _, BufReader, Write as _},
+        net::TcpListener,
+        time::Duration,
+    };
+
+    use super::*;
+
+    /// Serves endless redirects, each of which takes `delay`.
+    fn redirect_server(delay: Duration) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for (hop, stream) in listener.incoming().enumerate() {
+                let Ok(mut stream) = stream else { return };
+                let mut line = String::new();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                while reader.read_line(&mut line).is_ok_and(|n| n > 2) {
+                    line.clear();
+                }
+                std::thread::sleep(delay);
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 302 Found\r\nLocation: /{}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    hop + 1
+                );
+            }
+        });
+        format!("http://{addr}/0")
+    }
+
+    #[test]
+    fn rejected_certificates() {
+        // Not through a `Client`, which would use the `MockServer` of a concurrent test:
+        let backend = Backend {
+            agent: ureq::Agent::config_builder()
+                .max_redirects(0)
+                .build()
+                .into(),
+            cookie_jar: None,
+            cache: None,
+            executor: Executor::default(),
+        };
+        let fetch = |request: Request| {
+            let address =
+                crate::tls::tests::tls_server("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n");
+            let request = Request {
+                url: format!("https://localhost:{}/", address.port()),
+                ..request
+            };
+            fetch_blocking_with(&backend, &request, &RequestHandle::default())
+        };
+
+        // Self-signed for another host:
+        let result = fetch(Request::get(""));
+        assert!(
+            matches!(&result, Err(Error::Certificate(_))),
+            "{:?}",
+            result.map(|response| response.status)
+        );
+
+        let tls = crate::TlsConfig::new().danger_accept_invalid_certificates();
+        assert_eq!(fetch(Request::get("").with_tls(tls)).unwrap().status, 200);
+    }
+
+    #[test]
+    fn timeout_covers_all_redirects() {
+        let url = redirect_server(Duration::from_millis(150));
+        let request = Request::get(url)
+            .with_timeout(Some(Duration::from_millis(500)))
+            .with_redirect_policy(crate::RedirectPolicy::Limit(100));
+
+        // Not through a `Client`, which would use the `MockServer` of a concurrent test:
+        let backend = Backend {
+            agent: ureq::Agent::config_builder()
+                .max_redirects(0)
+                .build()
+                .into(),
+            cookie_jar: None,
+            cache: None,
+            executor: Executor::default(),
+        };
+
+        let start = Instant::now();
+        let result = fetch_blocking_with(&backend, &request, &RequestHandle::default());
+        assert!(
+            matches!(result, Err(Error::Timeout(_))),
+            "{:?}",
+            result.map(|response| response.redirects.len())
+        );
+        assert!(start.elapsed() < Duration::from_millis(1000));
+    }
 }
@@ -0,0 +1,302 @@
+//! Decode a stream of [newline-delimited JSON](https://github.com/ndjson/ndjson-spec)
+//! (also known as [JSON Lines](https://jsonlines.org/)), one value at a time.
+//!
+//! Requires the `json` and `streaming` features to be enabled.
+//!
+//! ```
+//! let request = ehttp::Request::get("https://www.example.com/logs");
+//! ehttp::ndjson::fetch(request, |result: ehttp::Result<ehttp::ndjson::Part<serde_json::Value>>| {
+//!     match result {
+//!         Ok(ehttp::ndjson::Part::Response(response)) => {
+//!             if !response.ok {
+//!                 return std::ops::ControlFlow::Break(());
+//!             }
+//!         }
+//!         Ok(ehttp::ndjson::Part::Value(value)) => println!("{value}"),
+//!         Ok(ehttp::ndjson::Part::Invalid(err)) => eprintln!("Skipping a bad line: {err}"),
+//!         Ok(ehttp::ndjson::Part::End) => println!("Done"),
+//!         Err(err) => eprintln!("Failed: {err}"),
+//!     }
+//!     std::ops::ControlFlow::Continue(())
+//! });
+//! ```
+//!
+//! Lines may be split across chunks in any way, and may end with `\n` or `\r\n`.
+//! Blank lines are skipped, and the last line doesn't need a trailing newline.
+
+use std::{
+    marker::PhantomData,
+    ops::ControlFlow,
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll},
+};
+
+use serde::de::DeserializeOwned;
+
+use crate::{streaming::Queue, types::PartialResponse, Client, Request, RequestHandle, Result};
+
+/// A piece of a newline-delimited JSON stream.
+#[derive(Debug)]
+pub enum Part<T> {
+    /// The header of the response. Always the first part.
+    ///
+    /// Its body is parsed no matter the status code,
+    /// so check [`PartialResponse::ok`] if you only expect values from successful responses.
+    Response(PartialResponse),
+
+    /// A successfully parsed line.
+    Value(T),
+
+    /// A line that could not be parsed. The lines after it are still parsed.
+    Invalid(LineError),
+
+    /// The end of the stream. Only given to callbacks; a [`ValueStream`] just ends instead.
+    End,
+}
+
+/// A line that could not be parsed as JSON of the expected type.
+#[derive(Debug)]
+pub struct LineError {
+    /// The line, without the newline.
+    pub line: String,
+
+    /// Why it could not be parsed.
+    pub error: serde_json::Error,
+}
+
+impl std::fmt::Display for LineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Failed to parse {:?}: {}", self.line, self.error)
+    }
+}
+
+impl std::error::Error for LineError {}
+
+/// Performs a streaming HTTP request, and calls the given callback with each line of the response, parsed as `T`.
+///
+/// The callback is first called with a [`Part::Response`], then for each line,
+/// and finally with a [`Part::End`] (or an error).
+/// Return [`ControlFlow::Break`] to stop early.
+///
+/// Uses the default [`Client`]; see [`Client::fetch_ndjson`].
+pub fn fetch<T: DeserializeOwned + 'static>(
+    request: Request,
+    on_part: impl 'static + Send + Fn(Result<Part<T>>) -> ControlFlow<()>,
+) -> RequestHandle {
+    crate::client::default_client().fetch_ndjson(request, on_part)
+}
+
+/// Performs a streaming HTTP request, and returns the lines of the response, parsed as `T`,
+/// as an async [`futures_util::Stream`].
+///
+/// The stream starts with a [`Part::Response`], and ends after the last line (or an error).
+/// Dropping it aborts the request.
+///
+/// Uses the default [`Client`]; see [`Client::fetch_ndjson_stream`].
+pub fn fetch_stream<T: DeserializeOwned + Send + 'static>(request: Request) -> ValueStream<T> {
+    crate::client::default_client().fetch_ndjson_stream(request)
+}
+
+/// The stream of values returned by [`fetch_stream`].
+pub struct ValueStream<T> {
+    queue: Queue<Result<Part<T>>>,
+    handle: RequestHandle,
+}
+
+impl<T: DeserializeOwned + Send + 'static> ValueStream<T> {
+    pub(crate) fn fetch(client: &Client, request: Request) -> Self {
+        let queue = Queue::default();
+        let handle = client.fetch_ndjson(request, {
+            let queue = queue.clone();
+            move |part: Result<Part<T>>| {
+                match part {
+                    Ok(Part::End) => queue.close(),
+                    Ok(part) => queue.push(Ok(part)),
+                    Err(err) => {
+                        queue.push(Err(err));
+                        queue.close();
+                    }
+                }
+                ControlFlow::Continue(())
+            }
+        });
+        Self { queue, handle }
+    }
+}
+
+impl<T> ValueStream<T> {
+    /// Can be used to abort the request without dropping the stream.
+    pub fn handle(&self) -> &RequestHandle {
+        &self.handle
+    }
+}
+
+impl<T> futures_util::Stream for ValueStream<T> {
+    type Item = Result<Part<T>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.queue.poll_next(cx)
+    }
+}
+
+impl<T> Drop for ValueStream<T> {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+impl<T> std::fmt::Debug for ValueStream<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ValueStream")
+            .field("handle", &self.handle)
+            .finish_non_exhaustive()
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Turns the `on_part` callback for NDJSON into an `on_data` callback for [`crate::streaming`].
+pub(crate) fn decode<T: DeserializeOwned + 'static>(
+    on_part: impl 'static + Send + Fn(Result<Part<T>>) -> ControlFlow<()>,
+) -> impl 'static + Send + Fn(Result<crate::streaming::Part>) -> ControlFlow<()> {
+    let decoder = Mutex::new(Decoder::<T>::new());
+    move |part| {
+        let parts = match part {
+            Ok(crate::streaming::Part::Response(response)) => {
+                return on_part(Ok(Part::Response(response)));
+            }
+            Ok(crate::streaming::Part::Chunk(chunk)) if chunk.is_empty() => {
+                let mut decoder = decoder.lock().unwrap();
+                decoder.finish().into_iter().chain([Part::End]).collect()
+            }
+            Ok(crate::streaming::Part::Chunk(chunk)) => decoder.lock().unwrap().feed(&chunk),
+            Err(err) => return on_part(Err(err)),
+        };
+        for part in parts {
+            on_part(Ok(part))?;
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// Splits chunks into lines, and parses them.
+struct Decoder<T> {
+    /// The current, incomplete line.
+    line: Vec<u8>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> Decoder<T> {
+    fn new() -> Self {
+        Self {
+            line: vec![],
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the lines completed by this chunk.
+    fn feed(&mut self, chunk: &[u8]) -> Vec<Part<T>> {
+        let mut parts = vec![];
+        let mut rest = chunk;
+        while let Some(newline) = rest.iter().position(|&b| b == b'\n') {
+            self.line.extend_from_slice(&rest[..newline]);
+            rest = &rest[newline + 1..];
+            let line = std::mem::take(&mut self.line);
+            parts.extend(parse(&line));
+        }
+        self.line.extend_from_slice(rest);
+        parts
+    }
+
+    /// The last line, if it didn't end with a newline.
+    fn finish(&mut self) -> Option<Part<T>> {
+        let line = std::mem::take(&mut self.line);
+        parse(&line)
+    }
+}
+
+fn parse<T: DeserializeOwned>(line: &[u8]) -> Option<Part<T>> {
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    if line.iter().all(|b| b.is_ascii_whitespace()) {
+        return None;
+    }
+    Some(match serde_json::from_slice(line) {
+        Ok(value) => Part::Value(value),
+        Err(error) => Part::Invalid(LineError {
+            line: String::from_utf8_lossy(line).into_owned(),
+            error,
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The values, with `None` for invalid lines.
+    fn values(parts: Vec<Part<u32>>) -> Vec<Option<u32>> {
+        parts
+            .into_iter()
+            .map(|part| match part {
+                Part::Value(value) => Some(value),
+                Part::Invalid(_) => None,
+                other => panic!("Unexpected {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lines_split_across_chunks() {
+        let mut decoder = Decoder::<u32>::new();
+        assert!(values(decoder.feed(b"1")).is_empty());
+        assert_eq!(values(decoder.feed(b"2\n3")), [Some(12)]);
+        assert_eq!(values(decoder.feed(b"4\r")), []);
+        assert_eq!(
+            values(decoder.feed(b"\n\n  \n5\n6\n")),
+            [Some(34), Some(5), Some(6)]
+        );
+        assert!(decoder.finish().is_none());
+    }
+
+    #[test]
+    fn last_line_without_newline() {
+        let mut decoder = Decoder::<u32>::new();
+        assert_eq!(values(decoder.feed(b"1\n2")), [Some(1)]);
+        assert_eq!(values(decoder.finish().into_iter().collect()), [Some(2)]);
+        assert!(decoder.finish().is_none());
+    }
+
+    #[test]
+    fn invalid_lines_are_skipped() {
+        let mut decoder = Decoder::<u32>::new();
+        let mut parts = decoder.feed(b"1\n\"one\"\n{\n2\n");
+        assert_eq!(parts.len(), 4);
+        match parts.remove(1) {
+            Part::Invalid(err) => assert_eq!(err.line, "\"one\""),
+            other => panic!("Expected an invalid line, got {:?}", other),
+        }
+        assert_eq!(values(parts), [Some(1), None, Some(2)]);
+    }
+
+    #[test]
+    fn decode_ends_with_the_last_line() {
+        let parts = std::sync::Arc::new(Mutex::new(vec![]));
+        let on_data = decode::<u32>({
+            let parts = parts.clone();
+            move |part| {
+                parts.lock().unwrap().push(match part.unwrap() {
+                    Part::Value(value) => value.to_string(),
+                    Part::End => "end".to_owned(),
+                    other => panic!("Unexpected {:?}", other),
+                });
+                ControlFlow::Continue(())
+            }
+        });
+        for chunk in ["1\n2", "2\n", "3", ""] {
+            let flow = on_data(Ok(crate::streaming::Part::Chunk(chunk.as_bytes().to_vec())));
+            assert!(flow.is_continue());
+        }
+        assert_eq!(*parts.lock().unwrap(), ["1", "22", "3", "end"]);
+    }
+}
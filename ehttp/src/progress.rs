@@ -0,0 +1,269 @@
+use std::{sync::Arc, time::Duration};
+
+/// How far along an upload or download is.
+///
+/// See [`crate::Request::with_upload_progress`] and [`crate::Request::with_download_progress`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+    /// How many bytes have been transferred so far.
+    pub bytes: u64,
+
+    /// How many bytes there are in total, if known.
+    pub total: Option<u64>,
+
+    /// How long the transfer has been going on.
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// How much of the transfer is done, in `[0, 1]`, if the total is known.
+    pub fn fraction(&self) -> Option<f32> {
+        let total = self.total?;
+        if total == 0 {
+            Some(1.0)
+        } else {
+            Some((self.bytes as f64 / total as f64).min(1.0) as f32)
+        }
+    }
+
+    /// The average throughput so far.
+    pub fn bytes_per_second(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.bytes as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+/// A callback that is called as a transfer progresses.
+///
+/// Cloning it is cheap.
+#[derive(Clone)]
+pub struct ProgressCallback(Arc<dyn Fn(Progress) + Send + Sync>);
+
+impl ProgressCallback {
+    /// Wrap a closure.
+    pub fn new(callback: impl Fn(Progress) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(callback))
+    }
+
+    /// Call the callback.
+    pub fn call(&self, progress: Progress) {
+        (self.0)(progress);
+    }
+}
+
+impl std::fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Keeps track of the bytes transferred, and reports them to a [`ProgressCallback`].
+pub(crate) struct ProgressTracker {
+    callback: ProgressCallback,
+    bytes: u64,
+    total: Option<u64>,
+    stopwatch: Stopwatch,
+}
+
+impl ProgressTracker {
+    /// Reports that nothing has been transferred yet.
+    pub fn start(callback: ProgressCallback, total: Option<u64>) -> Self {
+        let tracker = Self {
+            callback,
+            bytes: 0,
+            total,
+            stopwatch: Stopwatch::start(),
+        };
+        tracker.report();
+        tracker
+    }
+
+    /// Reports that `bytes` more bytes have been transferred.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn add(&mut self, bytes: u64) {
+        self.bytes += bytes;
+        self.report();
+    }
+
+    /// Reports the total number of bytes transferred so far.
+    #[cfg(target_arch = "wasm32")]
+    pub fn set(&mut self, bytes: u64, total: Option<u64>) {
+        self.bytes = bytes;
+        self.total = total.or(self.total);
+        self.report();
+    }
+
+    fn report(&self) {
+        self.callback.call(Progress {
+            bytes: self.bytes,
+            total: self.total,
+            elapsed: self.stopwatch.elapsed(),
+        });
+    }
+}
+
+/// The size of the body of a response, if known.
+///
+/// The `Content-Length` of a compressed response is the compressed size,
+/// but we get the decompressed body, so then the size is unknown.
+pub(crate) fn expected_size(headers: &crate::Headers) -> Option<u64> {
+    let encoding = headers.get("content-encoding").unwrap_or("identity");
+    if encoding.eq_ignore_ascii_case("identity") {
+        headers.get("content-length")?.trim().parse().ok()
+    } else {
+        None
+    }
+}
+
+/// `std::time::Instant` panics on `wasm32-unknown-unknown`, so there we ask JavaScript instead.
+struct Stopwatch {
+    #[cfg(not(target_arch = "wasm32"))]
+    start: std::time::Instant,
+
+    /// Milliseconds since the epoch.
+    #[cfg(target_arch = "wasm32")]
+    start: f64,
+}
+
+impl Stopwatch {
+    fn start() -> Self {
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            start: std::time::Instant::now(),
+            #[cfg(target_arch = "wasm32")]
+            start: js_sys::Date::now(),
+        }
+    }
+
+    fn elapsed(&self) -> Duration {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.start.elapsed()
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            Duration::from_secs_f64(((js_sys::Date::now() - self.start) / 1000.0).max(0.0))
+        }
+    }
+}
+
+/// Reports the bytes read through it.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct ProgressReader<R> {
+    inner: R,
+    tracker: ProgressTracker,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<R> ProgressReader<R> {
+    pub fn new(inner: R, tracker: ProgressTracker) -> Self {
+        Self { inner, tracker }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<R: std::io::Read> std::io::Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n > 0 {
+            self.tracker.add(n as u64);
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::Headers;
+
+    fn progress(bytes: u64, total: Option<u64>, elapsed: Duration) -> Progress {
+        Progress {
+            bytes,
+            total,
+            elapsed,
+        }
+    }
+
+    #[test]
+    fn fraction_and_throughput() {
+        let second = Duration::from_secs(1);
+        assert_eq!(progress(50, Some(200), second).fraction(), Some(0.25));
+        assert_eq!(progress(50, None, second).fraction(), None);
+        assert_eq!(progress(0, Some(0), second).fraction(), Some(1.0));
+        // The server sent more than it said it would:
+        assert_eq!(progress(300, Some(200), second).fraction(), Some(1.0));
+
+        assert_eq!(
+            progress(300, None, Duration::from_secs(2)).bytes_per_second(),
+            150.0
+        );
+        assert_eq!(progress(300, None, Duration::ZERO).bytes_per_second(), 0.0);
+    }
+
+    #[test]
+    fn expected_size() {
+        let size = |headers: &[(&str, &str)]| super::expected_size(&Headers::new(headers));
+        assert_eq!(size(&[("Content-Length", "42")]), Some(42));
+        assert_eq!(
+            size(&[("Content-Length", " 42 "), ("Content-Encoding", "identity")]),
+            Some(42)
+        );
+        assert_eq!(
+            size(&[("Content-Length", "42"), ("Content-Encoding", "gzip")]),
+            None
+        );
+        assert_eq!(size(&[("Content-Length", "lots")]), None);
+        assert_eq!(size(&[]), None);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn reader_reports_increasing_byte_counts() {
+        use std::io::Read as _;
+
+        let reported = Arc::new(Mutex::new(vec![]));
+        let callback = ProgressCallback::new({
+            let reported = reported.clone();
+            move |progress| reported.lock().unwrap().push(progress)
+        });
+
+        let body = vec![7; 10];
+        let mut reader = ProgressReader::new(
+            &body[..],
+            ProgressTracker::start(callback, Some(body.len() as u64)),
+        );
+        let mut buf = [0; 4];
+        let mut read = vec![];
+        loop {
+            let n = reader.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            read.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(read, body);
+
+        let reported = reported.lock().unwrap();
+        let bytes: Vec<u64> = reported.iter().map(|progress| progress.bytes).collect();
+        assert_eq!(
+            bytes,
+            [0, 4, 8, 10],
+            "the end of the body isn't reported again"
+        );
+        assert!(reported.iter().all(|progress| progress.total == Some(10)));
+        assert!(reported
+            .windows(2)
+            .all(|pair| pair[0].elapsed <= pair[1].elapsed));
+        assert_eq!(reported.last().unwrap().fraction(), Some(1.0));
+    }
+}
@@ -0,0 +1,269 @@
+use std::sync::Arc;
+
+/// A redirect that was followed (or is about to be).
+///
+/// See [`crate::Response::redirects`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Redirect {
+    /// The URL that responded with the redirect.
+    pub url: String,
+
+    /// The redirect status code, e.g. `301` or `302`.
+    pub status: u16,
+
+    /// Where the redirect points to (the `Location` header, resolved against [`Self::url`]).
+    pub location: String,
+}
+
+/// Decides which redirects are followed.
+///
+/// Set it with [`crate::Request::with_redirect_policy`].
+///
+/// On native, the `Authorization` and `Cookie` headers are only sent along to the same origin
+/// (cookies from a [`crate::cookies::CookieJar`] are added for each hop, as usual).
+///
+/// On web, the browser follows redirects,
+/// so [`Self::Limit`] and [`Self::Custom`] both just follow them (up to the browser's limit of 20),
+/// and [`Self::None`] (or a limit of zero) gives you an opaque response with status `0`.
+#[derive(Clone)]
+pub enum RedirectPolicy {
+    /// Don't follow redirects. You get the redirect response itself.
+    None,
+
+    /// Follow at most this many redirects, and fail with an error if there are more.
+    Limit(usize),
+
+    /// Called for each redirect with the redirect and the redirects followed before it.
+    ///
+    /// Return `true` to follow it, or `false` to get the redirect response instead.
+    ///
+    /// Use [`Self::custom`] to create this.
+    #[expect(clippy::type_complexity)]
+    Custom(Arc<dyn Fn(&Redirect, &[Redirect]) -> bool + Send + Sync>),
+}
+
+impl Default for RedirectPolicy {
+    /// Follow up to 10 redirects.
+    fn default() -> Self {
+        Self::Limit(10)
+    }
+}
+
+impl std::fmt::Debug for RedirectPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => f.write_str("None"),
+            Self::Limit(limit) => f.debug_tuple("Limit").field(limit).finish(),
+            Self::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+impl RedirectPolicy {
+    /// Decide for each redirect whether to follow it.
+    ///
+    /// ```
+    /// // Never leave example.com:
+    /// let policy = ehttp::RedirectPolicy::custom(|redirect, previous| {
+    ///     previous.len() < 10 && redirect.location.starts_with("https://example.com/")
+    /// });
+    /// ```
+    pub fn custom(
+        should_follow: impl Fn(&Redirect, &[Redirect]) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self::Custom(Arc::new(should_follow))
+    }
+
+    /// Should we follow `redirect`, after having followed `previous`?
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn should_follow(
+        &self,
+        redirect: &Redirect,
+        previous: &[Redirect],
+    ) -> crate::Result<bool> {
+        match self {
+            Self::None => Ok(false),
+            Self::Limit(limit) => {
+                if previous.len() < *limit {
+                    Ok(true)
+                } else {
+                    Err(crate::Error::Other(format!(
+                        "Too many redirects (more than {limit})"
+                    )))
+                }
+            }
+            Self::Custom(should_follow) => Ok(should_follow(redirect, previous)),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl From<&RedirectPolicy> for web_sys::RequestRedirect {
+    fn from(policy: &RedirectPolicy) -> Self {
+        match policy {
+            RedirectPolicy::None | RedirectPolicy::Limit(0) => Self::Manual,
+            RedirectPolicy::Limit(_) | RedirectPolicy::Custom(_) => Self::Follow,
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// The request to send to follow the redirect.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn follow(request: &crate::Request, redirect: &Redirect) -> crate::Request {
+    use crate::Method;
+
+    let mut next = request.clone();
+    next.url = redirect.location.clone();
+
+    // Like browsers, we change POST to GET for 301 and 302, even though the spec says not to:
+    let change_to_get = match redirect.status {
+        303 => next.method != Method::HEAD,
+        301 | 302 => next.method == Method::POST,
+        _ => false,
+    };
+    let mut removed_headers = vec![];
+    if change_to_get {
+        next.method = Method::GET;
+        next.body = crate::Body::default();
+        removed_headers.extend(["Content-Type", "Content-Length"]);
+    }
+    if !same_origin(&request.url, &next.url) {
+        removed_headers.extend(["Authorization", "Cookie"]);
+    }
+    next.headers.headers.retain(|(key, _)| {
+        !removed_headers
+            .iter()
+            .any(|removed| key.eq_ignore_ascii_case(removed))
+    });
+
+    next
+}
+
+/// Resolve the `Location` of a redirect against the URL it came from.
+#[cfg(not(target_arch = "wasm32"))]
+///
+/// Fails if the result is not a valid `http://` or `https://` URL.
+pub(crate) fn resolve_location(url: &str, location: &str) -> crate::Result<String> {
+    crate::Url::parse(url)
+        .and_then(|url| url.join(location))
+        .map(String::from)
+}
+
+/// Do the two URLs have the same scheme, host and port?
+///
+/// URLs that can't be parsed don't have the same origin as anything.
+#[cfg(not(target_arch = "wasm32"))]
+fn same_origin(a: &str, b: &str) -> bool {
+    match (::url::Url::parse(a), ::url::Url::parse(b)) {
+        (Ok(a), Ok(b)) => a.origin().is_tuple() && a.origin() == b.origin(),
+        _ => false,
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::{Method, Request};
+
+    #[test]
+    fn resolve() {
+        let base = "https://example.com/a/b/c?q=1#frag";
+        let cases = [
+            ("https://other.com/x", "https://other.com/x"),
+            ("//other.com/x", "https://other.com/x"),
+            ("/x", "https://example.com/x"),
+            ("x", "https://example.com/a/b/x"),
+            ("../x", "https://example.com/a/x"),
+            ("../../../../x", "https://example.com/x"),
+            ("?q=2", "https://example.com/a/b/c?q=2"),
+            ("#other", "https://example.com/a/b/c?q=1#other"),
+            ("", "https://example.com/a/b/c?q=1"),
+            ("x y", "https://example.com/a/b/x%20y"),
+            ("HTTP://OTHER.COM", "http://other.com/"),
+        ];
+        for (location, expected) in cases {
+            assert_eq!(
+                resolve_location(base, location).unwrap(),
+                expected,
+                "{location:?}"
+            );
+        }
+
+        assert!(resolve_location(base, "http://[::1").is_err());
+        assert!(resolve_location(base, "file:///etc/passwd").is_err());
+        assert!(resolve_location(base, "javascript:alert(1)").is_err());
+        assert!(resolve_location("not a url", "/x").is_err());
+    }
+
+    #[test]
+    fn origins() {
+        assert!(same_origin("https://a.com/x", "https://a.com/y?z"));
+        assert!(same_origin("https://a.com", "https://A.COM:443/"));
+        assert!(same_origin("http://a.com", "http://a.com:80"));
+        assert!(!same_origin("https://a.com", "https://a.com:8443"));
+        assert!(!same_origin("https://a.com", "http://a.com"));
+        assert!(!same_origin("https://a.com", "https://b.a.com"));
+        assert!(!same_origin("https://a.com", "not a url"));
+        assert!(!same_origin("data:text/plain,x", "data:text/plain,x"));
+    }
+
+    fn redirect(status: u16, location: &str) -> Redirect {
+        Redirect {
+            url: "https://example.com/from".to_owned(),
+            status,
+            location: location.to_owned(),
+        }
+    }
+
+    #[test]
+    fn follow_changes_method() {
+        let post = Request::post("https://example.com/from", b"body".to_vec())
+            .with_header("Content-Type", "text/plain");
+        for (status, method) in [
+            (301, Method::GET),
+            (302, Method::GET),
+            (303, Method::GET),
+            (307, Method::POST),
+            (308, Method::POST),
+        ] {
+            let next = follow(&post, &redirect(status, "https://example.com/to"));
+            assert_eq!(next.url, "https://example.com/to");
+            assert_eq!(next.method, method, "{status}");
+            assert_eq!(next.body.is_empty(), method == Method::GET, "{status}");
+            assert_eq!(
+                next.headers.get("content-type").is_none(),
+                method == Method::GET,
+                "{status}"
+            );
+        }
+
+        let head = Request::head("https://example.com/from");
+        let next = follow(&head, &redirect(303, "https://example.com/to"));
+        assert_eq!(next.method, Method::HEAD);
+    }
+
+    #[test]
+    fn follow_strips_credentials_across_origins() {
+        let request = Request::get("https://example.com/from")
+            .with_header("Authorization", "Bearer secret")
+            .with_header("cookie", "a=1")
+            .with_header("Accept", "*/*");
+
+        let next = follow(&request, &redirect(302, "https://example.com:443/to"));
+        assert_eq!(next.headers.get("authorization"), Some("Bearer secret"));
+        assert_eq!(next.headers.get("cookie"), Some("a=1"));
+
+        for location in [
+            "https://other.com/to",
+            "http://example.com/to",
+            "https://example.com:8443/to",
+        ] {
+            let next = follow(&request, &redirect(302, location));
+            assert_eq!(next.headers.get("authorization"), None, "{location}");
+            assert_eq!(next.headers.get("cookie"), None, "{location}");
+            assert_eq!(next.headers.get("accept"), Some("*/*"), "{location}");
+        }
+    }
+}
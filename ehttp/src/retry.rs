@@ -0,0 +1,525 @@
+use std::time::Duration;
+
+use crate::{Error, Method, Request, Response, Result};
+
+/// Decides if and when a failed request is tried again.
+///
+/// Set it for a single request with [`Request::with_retry`],
+/// or for all requests of a client with [`crate::Client::with_retry`].
+///
+/// ```
+/// use std::time::Duration;
+///
+/// let request = ehttp::Request::get("https://www.example.com").with_retry(
+///     ehttp::RetryPolicy::default()
+///         .with_max_attempts(5)
+///         .with_initial_backoff(Duration::from_millis(100)),
+/// );
+/// ```
+///
+/// A request is retried if it failed with a transport error (see [`Self::retry_transport_errors`])
+/// or got a response with one of the [`Self::retry_statuses`],
+/// until it has been tried [`Self::max_attempts`] times.
+/// The result of the last attempt is what you get back.
+///
+/// Between attempts we wait for an exponentially increasing backoff,
+/// or for as long as the server asks us to with a `Retry-After` header.
+///
+/// [`Request::timeout`] applies to each attempt separately.
+///
+/// `POST` and `PATCH` requests are not idempotent, so they are only retried if you opt in
+/// with [`Self::with_retry_non_idempotent`].
+///
+/// Streaming requests, and requests with a [`crate::Body`] that can only be read once, are never retried.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// How many times to try, including the first attempt.
+    ///
+    /// `1` means no retries.
+    pub max_attempts: u32,
+
+    /// How long to wait before the first retry.
+    ///
+    /// The backoff doubles with every retry.
+    pub initial_backoff: Duration,
+
+    /// The backoff never grows beyond this.
+    ///
+    /// If the server asks us to wait longer than this (with `Retry-After`), we give up instead.
+    pub max_backoff: Duration,
+
+    /// Wait a random duration between half the backoff and the full backoff,
+    /// so that many clients failing at once don't retry in lockstep.
+    pub jitter: bool,
+
+    /// Retry responses with these status codes.
+    pub retry_statuses: Vec<u16>,
+
+    /// Retry requests that failed without a response,
+    /// i.e. with [`Error::Timeout`], [`Error::Connect`], [`Error::Dns`], [`Error::Network`] or [`Error::BodyRead`].
+    pub retry_transport_errors: bool,
+
+    /// Also retry `POST` and `PATCH` requests.
+    ///
+    /// Only enable this if the server can deal with receiving the same request twice.
+    pub retry_non_idempotent: bool,
+
+    /// Add a random `Idempotency-Key` header to retried `POST` and `PATCH` requests,
+    /// unless they already have one.
+    ///
+    /// The same key is sent with every attempt, so the server can tell they are the same request.
+    pub idempotency_key: bool,
+}
+
+impl Default for RetryPolicy {
+    /// Three attempts, starting with a backoff of half a second,
+    /// retrying transport errors and the statuses 429, 502, 503 and 504.
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            jitter: true,
+            retry_statuses: vec![429, 502, 503, 504],
+            retry_transport_errors: true,
+            retry_non_idempotent: false,
+            idempotency_key: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Set how many times to try, including the first attempt.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Set how long to wait before the first retry.
+    pub fn with_initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    /// Set the longest we are willing to wait between two attempts.
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Randomize the backoff or not.
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Set which status codes to retry.
+    pub fn with_retry_statuses(mut self, retry_statuses: Vec<u16>) -> Self {
+        self.retry_statuses = retry_statuses;
+        self
+    }
+
+    /// Retry requests that failed without a response or not.
+    pub fn with_retry_transport_errors(mut self, retry_transport_errors: bool) -> Self {
+        self.retry_transport_errors = retry_transport_errors;
+        self
+    }
+
+    /// Retry `POST` and `PATCH` requests or not.
+    ///
+    /// See also [`Self::with_idempotency_key`].
+    pub fn with_retry_non_idempotent(mut self, retry_non_idempotent: bool) -> Self {
+        self.retry_non_idempotent = retry_non_idempotent;
+        self
+    }
+
+    /// Add an `Idempotency-Key` header to retried `POST` and `PATCH` requests or not.
+    pub fn with_idempotency_key(mut self, idempotency_key: bool) -> Self {
+        self.idempotency_key = idempotency_key;
+        self
+    }
+
+    /// Can this request be retried at all?
+    fn applies_to(&self, request: &Request) -> bool {
+        self.max_attempts > 1
+            && (self.retry_non_idempotent
+                || !matches!(request.method, Method::POST | Method::PATCH))
+    }
+
+    /// Adds the `Idempotency-Key` header to the request, if needed.
+    pub(crate) fn prepare(&self, request: &mut Request) {
+        if self.applies_to(request)
+            && self.idempotency_key
+            && matches!(request.method, Method::POST | Method::PATCH)
+            && request.headers.get("Idempotency-Key").is_none()
+        {
+            request.headers.insert("Idempotency-Key", idempotency_key());
+        }
+    }
+
+    /// How long to wait before trying again, or `None` if we should not retry.
+    ///
+    /// `attempt` is the number of attempts made so far (starting at 1).
+    pub(crate) fn delay_before_retry(
+        &self,
+        request: &Request,
+        attempt: u32,
+        result: &Result<Response>,
+    ) -> Option<Duration> {
+        if attempt >= self.max_attempts
+            || !self.applies_to(request)
+            || !request.body.can_be_resent()
+        {
+            return None;
+        }
+
+        match result {
+            Ok(response) => {
+                if !self.retry_statuses.contains(&response.status) {
+                    return None;
+                }
+                if let Some(retry_after) = response.headers.get("Retry-After") {
+                    if let Some(delay) = parse_retry_after(retry_after) {
+                        return (delay <= self.max_backoff).then_some(delay);
+                    }
+                }
+            }
+            Err(err) => {
+                let is_transport_error = matches!(
+                    err,
+                    Error::Timeout(_)
+                        | Error::Connect(_)
+                        | Error::Dns(_)
+                        | Error::Network(_)
+                        | Error::BodyRead(_)
+                );
+                if !self.retry_transport_errors || !is_transport_error {
+                    return None;
+                }
+            }
+        }
+
+        Some(self.backoff(attempt))
+    }
+
+    /// The backoff after the given (1-based) attempt.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2_u32.saturating_pow(attempt - 1);
+        let backoff = self
+            .initial_backoff
+            .checked_mul(factor)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+
+        if self.jitter {
+            let random = (random_u64() >> 11) as f64 / (1_u64 << 53) as f64; // in [0, 1)
+            backoff.mul_f64(0.5 + 0.5 * random)
+        } else {
+            backoff
+        }
+    }
+}
+
+/// `Retry-After` is either a number of seconds or an HTTP-date.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = crate::http_date::parse(value)?;
+    Some(
+        date.duration_since(crate::http_date::now())
+            .unwrap_or_default(),
+    )
+}
+
+/// A random version 4 UUID.
+fn idempotency_key() -> String {
+    let high = random_u64();
+    let low = random_u64();
+    format!(
+        "{:08x}-{:04x}-4{:03x}-{:04x}-{:012x}",
+        high >> 32,
+        (high >> 16) & 0xffff,
+        high & 0x0fff,
+        (low >> 48) & 0x3fff | 0x8000,
+        low & 0xffff_ffff_ffff
+    )
+}
+
+/// Not cryptographically secure, but good enough for jitter and idempotency keys.
+fn random_u64() -> u64 {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        use std::{
+            hash::{BuildHasher as _, Hasher as _},
+            sync::atomic::{AtomicU64, Ordering},
+        };
+
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        if let Ok(since_epoch) = crate::http_date::now().duration_since(std::time::UNIX_EPOCH) {
+            hasher.write_u128(since_epoch.as_nanos());
+        }
+        hasher.finish()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        let random_u32 = || (js_sys::Math::random() * f64::from(u32::MAX)) as u64;
+        random_u32() << 32 | random_u32()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Body, Headers};
+
+    const URL: &str = "https://example.com/";
+
+    fn response(status: u16, headers: &[(&str, &str)]) -> Result<Response> {
+        Ok(Response {
+            url: URL.to_owned(),
+            ok: (200..300).contains(&status),
+            status,
+            status_text: String::new(),
+            headers: Headers::new(headers),
+            redirects: vec![],
+            bytes: vec![],
+        })
+    }
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy::default()
+            .with_initial_backoff(Duration::from_secs(1))
+            .with_jitter(false)
+    }
+
+    #[test]
+    fn retryable_statuses() {
+        let policy = policy();
+        let get = Request::get(URL);
+        for status in [429, 502, 503, 504] {
+            assert_eq!(
+                policy.delay_before_retry(&get, 1, &response(status, &[])),
+                Some(Duration::from_secs(1)),
+                "{status}"
+            );
+        }
+        for status in [200, 304, 400, 404, 500, 501] {
+            assert_eq!(
+                policy.delay_before_retry(&get, 1, &response(status, &[])),
+                None,
+                "{status}"
+            );
+        }
+
+        let policy = policy.with_retry_statuses(vec![500]);
+        assert!(policy
+            .delay_before_retry(&get, 1, &response(500, &[]))
+            .is_some());
+        assert!(policy
+            .delay_before_retry(&get, 1, &response(503, &[]))
+            .is_none());
+    }
+
+    #[test]
+    fn transport_errors() {
+        let policy = policy();
+        let get = Request::get(URL);
+        let retried = [
+            Error::Timeout(String::new()),
+            Error::Connect(String::new()),
+            Error::Dns(String::new()),
+            Error::Network(String::new()),
+            Error::BodyRead(String::new()),
+        ];
+        for err in retried {
+            assert!(
+                policy
+                    .delay_before_retry(&get, 1, &Err(err.clone()))
+                    .is_some(),
+                "{:?}",
+                err
+            );
+            assert!(
+                policy
+                    .clone()
+                    .with_retry_transport_errors(false)
+                    .delay_before_retry(&get, 1, &Err(err.clone()))
+                    .is_none(),
+                "{:?}",
+                err
+            );
+        }
+
+        let other = Err(Error::Other("Something else".to_owned()));
+        assert!(policy.delay_before_retry(&get, 1, &other).is_none());
+    }
+
+    #[test]
+    fn post_and_patch_are_opt_in() {
+        let policy = policy();
+        let post = Request::post(URL, b"body".to_vec());
+        let mut patch = Request::post(URL, b"body".to_vec());
+        patch.method = Method::PATCH;
+        let put = Request::put(URL, b"body".to_vec());
+
+        let unavailable = response(503, &[]);
+        assert!(policy.delay_before_retry(&post, 1, &unavailable).is_none());
+        assert!(policy.delay_before_retry(&patch, 1, &unavailable).is_none());
+        assert!(policy.delay_before_retry(&put, 1, &unavailable).is_some());
+
+        let policy = policy.with_retry_non_idempotent(true);
+        assert!(policy.delay_before_retry(&post, 1, &unavailable).is_some());
+        assert!(policy.delay_before_retry(&patch, 1, &unavailable).is_some());
+    }
+
+    #[test]
+    fn bodies_that_can_only_be_read_once_are_not_retried() {
+        let policy = policy();
+        let unavailable = response(503, &[]);
+        let request = Request::put(URL, vec![]).with_body(Body::from_reader(std::io::empty()));
+        assert!(policy
+            .delay_before_retry(&request, 1, &unavailable)
+            .is_none());
+    }
+
+    #[test]
+    fn max_attempts() {
+        let policy = policy().with_max_attempts(3);
+        let get = Request::get(URL);
+        let unavailable = response(503, &[]);
+        assert!(policy.delay_before_retry(&get, 1, &unavailable).is_some());
+        assert!(policy.delay_before_retry(&get, 2, &unavailable).is_some());
+        assert!(policy.delay_before_retry(&get, 3, &unavailable).is_none());
+
+        let policy = policy.with_max_attempts(1);
+        assert!(policy.delay_before_retry(&get, 1, &unavailable).is_none());
+    }
+
+    #[test]
+    fn retry_after() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after(" 0 "), Some(Duration::ZERO));
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(Duration::ZERO),
+            "dates in the past mean right away"
+        );
+        let future = parse_retry_after("Fri, 01 Jan 2100 00:00:00 GMT").unwrap();
+        assert!(
+            future > Duration::from_secs(60 * 60 * 24 * 365),
+            "{:?}",
+            future
+        );
+        assert_eq!(parse_retry_after("soon"), None);
+        assert_eq!(parse_retry_after("-1"), None);
+
+        // The server knows best, even if it is shorter than our backoff:
+        let policy = policy().with_max_backoff(Duration::from_secs(30));
+        let get = Request::get(URL);
+        let retry_after = |value| response(503, &[("Retry-After", value)]);
+        assert_eq!(
+            policy.delay_before_retry(&get, 1, &retry_after("0")),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(
+            policy.delay_before_retry(&get, 1, &retry_after("30")),
+            Some(Duration::from_secs(30))
+        );
+
+        // Longer than we are willing to wait:
+        assert_eq!(policy.delay_before_retry(&get, 1, &retry_after("31")), None);
+        assert_eq!(
+            policy.delay_before_retry(&get, 1, &retry_after("Fri, 01 Jan 2100 00:00:00 GMT")),
+            None
+        );
+
+        // Garbage is ignored:
+        assert_eq!(
+            policy.delay_before_retry(&get, 1, &retry_after("soon")),
+            Some(Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn backoff_doubles() {
+        let policy = policy().with_max_backoff(Duration::from_secs(5));
+        let backoffs: Vec<_> = (1..=5).map(|attempt| policy.backoff(attempt)).collect();
+        assert_eq!(
+            backoffs,
+            [1, 2, 4, 5, 5].map(Duration::from_secs),
+            "capped at max_backoff"
+        );
+
+        // No overflow:
+        assert_eq!(policy.backoff(100), Duration::from_secs(5));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn jitter() {
+        let policy = policy().with_jitter(true);
+        let backoffs: Vec<_> = (0..100).map(|_| policy.backoff(2)).collect();
+        for backoff in &backoffs {
+            assert!(
+                Duration::from_secs(1) <= *backoff && *backoff < Duration::from_secs(2),
+                "{:?}",
+                backoff
+            );
+        }
+        assert!(
+            backoffs.iter().any(|backoff| *backoff != backoffs[0]),
+            "{:?}",
+            backoffs
+        );
+    }
+
+    #[test]
+    fn idempotency_keys_are_uuids() {
+        let key = idempotency_key();
+        let groups: Vec<&str> = key.split('-').collect();
+        assert_eq!(
+            groups.iter().map(|group| group.len()).collect::<Vec<_>>(),
+            [8, 4, 4, 4, 12],
+            "{key}"
+        );
+        assert!(
+            key.chars()
+                .all(|c| matches!(c, '-' | '0'..='9' | 'a'..='f')),
+            "{:?}",
+            key
+        );
+        assert!(groups[2].starts_with('4'), "version 4: {:?}", key);
+        assert!(
+            groups[3].starts_with(['8', '9', 'a', 'b']),
+            "RFC 4122 variant: {:?}",
+            key
+        );
+
+        assert_ne!(idempotency_key(), idempotency_key());
+    }
+
+    #[test]
+    fn idempotency_key_header() {
+        let policy = policy()
+            .with_retry_non_idempotent(true)
+            .with_idempotency_key(true);
+
+        let mut post = Request::post(URL, vec![]);
+        policy.prepare(&mut post);
+        assert!(post.headers.get("Idempotency-Key").is_some());
+
+        let mut own_key = Request::post(URL, vec![]).with_header("Idempotency-Key", "mine");
+        policy.prepare(&mut own_key);
+        assert_eq!(own_key.headers.get("Idempotency-Key"), Some("mine"));
+
+        let mut get = Request::get(URL);
+        policy.prepare(&mut get);
+        assert!(get.headers.get("Idempotency-Key").is_none());
+    }
+}
@@ -0,0 +1,558 @@
+//! A client for [Server-Sent Events](https://html.spec.whatwg.org/multipage/server-sent-events.html)
+//! (`text/event-stream`), built on top of [`crate::streaming`].
+//!
+//! Requires the `streaming` feature to be enabled.
+//!
+//! ```
+//! let request = ehttp::Request::get("https://www.example.com/events");
+//! let handle = ehttp::sse::fetch(request, |result: ehttp::Result<ehttp::sse::Event>| {
+//!     match result {
+//!         Ok(event) => println!("{}: {}", event.event, event.data),
+//!         Err(err) => eprintln!("Lost the connection, reconnecting: {err}"),
+//!     }
+//!     std::ops::ControlFlow::Continue(())
+//! });
+//!
+//! // Later, when you no longer want any events:
+//! handle.abort();
+//! ```
+//!
+//! Like an `EventSource` in the browser, the connection is automatically re-established when it is lost,
+//! after the delay the server asked for with a `retry:` field (or 3 seconds),
+//! and the ID of the last received event is sent along in a `Last-Event-ID` header.
+//!
+//! The connection is closed for good (without calling the callback again) if:
+//! * the callback returns [`ControlFlow::Break`]
+//! * the [`RequestHandle`] is aborted
+//! * the server responds with anything but a `200` with `Content-Type: text/event-stream`
+//!   (e.g. a `204 No Content`, which is how a server tells the client to stop reconnecting)
+//!   in which case the callback gets an error first
+//! * the request fails for any other reason than a network error (e.g. an invalid URL)
+//!
+//! Unless you give the request a timeout of your own, it has none, since event streams are long-lived.
+//! On native, each connection has a thread of its own (outside of the [`crate::Executor`]).
+
+use std::{
+    ops::ControlFlow,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use crate::{
+    streaming::{Part, Queue},
+    Client, Error, Request, RequestHandle, Result,
+};
+
+/// An event received from the server.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    /// The `event:` field, or `"message"` if there was none.
+    pub event: String,
+
+    /// The `data:` fields, joined by newlines.
+    pub data: String,
+
+    /// The last `id:` the server sent, on this or an earlier event (even before a reconnect).
+    ///
+    /// `None` if there was none, or if the server reset it with an empty `id:`.
+    pub id: Option<String>,
+}
+
+/// Connects to a stream of Server-Sent Events, and calls the given callback for each event.
+///
+/// The callback is also called with the error every time the connection is lost,
+/// after which it is re-established, unless you return [`ControlFlow::Break`].
+/// See [the module docs](self) for details.
+///
+/// Uses the default [`Client`]; see [`Client::fetch_sse`].
+pub fn fetch(
+    request: Request,
+    on_event: impl 'static + Send + Sync + Fn(Result<Event>) -> ControlFlow<()>,
+) -> RequestHandle {
+    crate::client::default_client().fetch_sse(request, on_event)
+}
+
+/// Connects to a stream of Server-Sent Events, and returns them as an async [`futures_util::Stream`].
+///
+/// Like [`fetch`], the stream yields an error every time the connection is lost,
+/// after which it is re-established.
+/// The stream ends when the connection is closed for good.
+/// Dropping it closes the connection.
+///
+/// Uses the default [`Client`]; see [`Client::fetch_sse_stream`].
+pub fn fetch_stream(request: Request) -> EventStream {
+    crate::client::default_client().fetch_sse_stream(request)
+}
+
+/// The stream of events returned by [`fetch_stream`].
+pub struct EventStream {
+    queue: Queue<Result<Event>>,
+    handle: RequestHandle,
+}
+
+impl EventStream {
+    pub(crate) fn connect(client: Client, request: Request) -> Self {
+        let queue = Queue::default();
+        let handle = connect(client, request, {
+            let queue = queue.clone();
+            Box::new(move |event| {
+                match event {
+                    Some(event) => queue.push(event),
+                    None => queue.close(),
+                }
+                ControlFlow::Continue(())
+            })
+        });
+        Self { queue, handle }
+    }
+
+    /// Can be used to close the connection without dropping the stream.
+    pub fn handle(&self) -> &RequestHandle {
+        &self.handle
+    }
+}
+
+impl futures_util::Stream for EventStream {
+    type Item = Result<Event>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.queue.poll_next(cx)
+    }
+}
+
+impl Drop for EventStream {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+impl std::fmt::Debug for EventStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventStream")
+            .field("handle", &self.handle)
+            .finish_non_exhaustive()
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Called with each event, and then with `None` once the connection is closed for good.
+pub(crate) type OnEvent = Box<dyn Fn(Option<Result<Event>>) -> ControlFlow<()> + Send + Sync>;
+
+/// How long to wait before reconnecting, unless the server says otherwise.
+const DEFAULT_RETRY: Duration = Duration::from_secs(3);
+
+/// Everything the connections to one event stream share.
+struct EventSource {
+    client: Client,
+    request: Request,
+    handle: RequestHandle,
+    on_event: OnEvent,
+    state: Mutex<State>,
+}
+
+struct State {
+    last_event_id: Option<String>,
+    retry: Duration,
+    closed: bool,
+}
+
+pub(crate) fn connect(client: Client, mut request: Request, on_event: OnEvent) -> RequestHandle {
+    if request.timeout == Some(Request::DEFAULT_TIMEOUT) {
+        request.timeout = None;
+    }
+    // Replace the `Accept: */*` of `Request::get`:
+    if request
+        .headers
+        .get("Accept")
+        .is_none_or(|accept| accept == "*/*")
+    {
+        request
+            .headers
+            .headers
+            .retain(|(key, _)| !key.eq_ignore_ascii_case("Accept"));
+        request.headers.insert("Accept", "text/event-stream");
+    }
+    if request.headers.get("Cache-Control").is_none() {
+        request.headers.insert("Cache-Control", "no-store");
+    }
+
+    let handle = RequestHandle::default();
+    let source = Arc::new(EventSource {
+        client,
+        request,
+        handle: handle.clone(),
+        on_event,
+        state: Mutex::new(State {
+            last_event_id: None,
+            retry: DEFAULT_RETRY,
+            closed: false,
+        }),
+    });
+    source.connect();
+    handle
+}
+
+impl EventSource {
+    fn connect(self: Arc<Self>) {
+        if self.handle.is_aborted() {
+            self.close();
+            return;
+        }
+
+        let mut request = self.request.clone();
+        let last_event_id = self.state.lock().unwrap().last_event_id.clone();
+        if let Some(last_event_id) = &last_event_id {
+            request.headers.insert("Last-Event-ID", last_event_id);
+        }
+
+        let parser = Mutex::new(Parser::new(last_event_id));
+        let source = self.clone();
+        let client = self.client.clone();
+        let handle = self.handle.clone();
+        client.fetch_streaming_with_handle(request, handle, move |part| match part {
+            Ok(Part::Response(response)) => {
+                let content_type = response.headers.get("content-type").unwrap_or_default();
+                let is_event_stream = content_type
+                    .split(';')
+                    .next()
+                    .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("text/event-stream"));
+                if response.status == 200 && is_event_stream {
+                    ControlFlow::Continue(())
+                } else {
+                    source.fail(Error::Other(format!(
+                        "Expected an event stream, got {} {} with Content-Type {content_type:?}",
+                        response.status, response.status_text
+                    )));
+                    ControlFlow::Break(())
+                }
+            }
+            Ok(Part::Chunk(chunk)) if chunk.is_empty() => {
+                source.clone().reconnect(None);
+                ControlFlow::Break(())
+            }
+            Ok(Part::Chunk(chunk)) => {
+                let mut parser = parser.lock().unwrap();
+                let events = parser.feed(&chunk);
+                {
+                    let mut state = source.state.lock().unwrap();
+                    state.last_event_id = parser.last_event_id.clone();
+                    state.retry = parser.retry.unwrap_or(state.retry);
+                }
+                for event in events {
+                    if source.emit(Ok(event)).is_break() {
+                        source.handle.abort();
+                        source.close();
+                        return ControlFlow::Break(());
+                    }
+                }
+                ControlFlow::Continue(())
+            }
+            Err(Error::Aborted) => {
+                source.close();
+                ControlFlow::Break(())
+            }
+            Err(
+                err @ (Error::Timeout(_)
+                | Error::Connect(_)
+                | Error::Dns(_)
+                | Error::Network(_)
+                | Error::BodyRead(_)),
+            ) => {
+                source.clone().reconnect(Some(err));
+                ControlFlow::Break(())
+            }
+            Err(err) => {
+                source.fail(err);
+                ControlFlow::Break(())
+            }
+        });
+    }
+
+    /// The connection was lost, so try again after a while.
+    fn reconnect(self: Arc<Self>, err: Option<Error>) {
+        if let Some(err) = err {
+            if self.emit(Err(err)).is_break() {
+                self.close();
+                return;
+            }
+        }
+        let delay = self.state.lock().unwrap().retry;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        crate::executor::spawn_dedicated_thread(Box::new(move |spawned| match spawned {
+            Ok(()) => {
+                // Wakes up early if we are aborted in the meantime:
+                let _ = crate::native::sleep(delay, &self.handle);
+                self.connect();
+            }
+            Err(err) => self.fail(err),
+        }));
+
+        #[cfg(target_arch = "wasm32")]
+        crate::web::spawn_future(async move {
+            crate::web::sleep(delay).await;
+            self.connect();
+        });
+    }
+
+    /// Close the connection for good, because of an error.
+    fn fail(&self, err: Error) {
+        let _ = self.emit(Err(err));
+        self.close();
+    }
+
+    fn emit(&self, event: Result<Event>) -> ControlFlow<()> {
+        if self.state.lock().unwrap().closed {
+            return ControlFlow::Break(());
+        }
+        (self.on_event)(Some(event))
+    }
+
+    fn close(&self) {
+        let was_closed = std::mem::replace(&mut self.state.lock().unwrap().closed, true);
+        if !was_closed {
+            let _ = (self.on_event)(None);
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Parses the `text/event-stream` format, chunk by chunk.
+///
+/// <https://html.spec.whatwg.org/multipage/server-sent-events.html#event-stream-interpretation>
+struct Parser {
+    /// The current, incomplete line.
+    line: Vec<u8>,
+
+    /// The previous chunk ended with `\r`, so skip a `\n` at the start of the next one.
+    skip_newline: bool,
+
+    /// Have we seen the first line yet? It may start with a byte order mark.
+    started: bool,
+
+    event: String,
+    data: String,
+    last_event_id: Option<String>,
+
+    /// The last `retry:` the server sent.
+    retry: Option<Duration>,
+}
+
+impl Parser {
+    fn new(last_event_id: Option<String>) -> Self {
+        Self {
+            line: vec![],
+            skip_newline: false,
+            started: false,
+            event: String::new(),
+            data: String::new(),
+            last_event_id,
+            retry: None,
+        }
+    }
+
+    /// Returns the events completed by this chunk.
+    fn feed(&mut self, chunk: &[u8]) -> Vec<Event> {
+        let mut events = vec![];
+        for &byte in chunk {
+            if std::mem::take(&mut self.skip_newline) && byte == b'\n' {
+                continue;
+            }
+            match byte {
+                b'\r' => {
+                    self.skip_newline = true;
+                    self.end_line(&mut events);
+                }
+                b'\n' => self.end_line(&mut events),
+                _ => self.line.push(byte),
+            }
+        }
+        events
+    }
+
+    fn end_line(&mut self, events: &mut Vec<Event>) {
+        let line = std::mem::take(&mut self.line);
+        let line = String::from_utf8_lossy(&line);
+        let mut line: &str = &line;
+        if !std::mem::replace(&mut self.started, true) {
+            line = line.strip_prefix('\u{feff}').unwrap_or(line);
+        }
+
+        if line.is_empty() {
+            events.extend(self.dispatch());
+            return;
+        }
+        if line.starts_with(':') {
+            return; // A comment, e.g. a keep-alive.
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => value.clone_into(&mut self.event),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            }
+            "id" => {
+                if !value.contains('\0') {
+                    self.last_event_id = (!value.is_empty()).then(|| value.to_owned());
+                }
+            }
+            "retry" => {
+                if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) {
+                    if let Ok(millis) = value.parse() {
+                        self.retry = Some(Duration::from_millis(millis));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn dispatch(&mut self) -> Option<Event> {
+        let event = std::mem::take(&mut self.event);
+        let mut data = std::mem::take(&mut self.data);
+        if data.is_empty() {
+            return None;
+        }
+        data.pop(); // The trailing newline
+        Some(Event {
+            event: if event.is_empty() {
+                "message".to_owned()
+            } else {
+                event
+            },
+            data,
+            id: self.last_event_id.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(event: &str, data: &str, id: Option<&str>) -> Event {
+        Event {
+            event: event.to_owned(),
+            data: data.to_owned(),
+            id: id.map(str::to_owned),
+        }
+    }
+
+    /// Feed the chunks one by one, and collect all events.
+    fn parse(parser: &mut Parser, chunks: &[&[u8]]) -> Vec<Event> {
+        chunks.iter().flat_map(|chunk| parser.feed(chunk)).collect()
+    }
+
+    #[test]
+    fn line_endings() {
+        let expected = vec![event("message", "a", None), event("message", "b", None)];
+        for stream in [
+            "data: a\n\ndata: b\n\n",
+            "data: a\r\n\r\ndata: b\r\n\r\n",
+            "data: a\r\rdata: b\r\r",
+            "data: a\r\n\rdata: b\n\r\n",
+        ] {
+            assert_eq!(
+                parse(&mut Parser::new(None), &[stream.as_bytes()]),
+                expected,
+                "{stream:?}"
+            );
+
+            // Split the stream in every possible place, including between `\r` and `\n`:
+            for split in 0..=stream.len() {
+                let (a, b) = stream.as_bytes().split_at(split);
+                assert_eq!(
+                    parse(&mut Parser::new(None), &[a, b]),
+                    expected,
+                    "{stream:?} split at {split}"
+                );
+            }
+        }
+
+        // Byte by byte:
+        let stream = b"data: a\r\n\r\ndata: b\r\n\r\n";
+        let chunks: Vec<&[u8]> = stream.chunks(1).collect();
+        assert_eq!(parse(&mut Parser::new(None), &chunks), expected);
+    }
+
+    #[test]
+    fn fields() {
+        let stream = "\u{feff}: a comment\n\
+            event: greeting\n\
+            data: first line\n\
+            data\n\
+            data:no space\n\
+            data:  two spaces\n\
+            unknown: field\n\
+            \n\
+            event: no data\n\
+            \n\
+            data: {\"json\": true}\n\
+            \n";
+        assert_eq!(
+            parse(&mut Parser::new(None), &[stream.as_bytes()]),
+            vec![
+                event("greeting", "first line\n\nno space\n two spaces", None),
+                event("message", "{\"json\": true}", None),
+            ]
+        );
+
+        // An incomplete event is not dispatched:
+        assert_eq!(parse(&mut Parser::new(None), &[b"data: a\n"]), vec![]);
+    }
+
+    #[test]
+    fn ids() {
+        let mut parser = Parser::new(Some("0".to_owned()));
+        let stream = "data: a\n\n\
+            id: 1\ndata: b\n\n\
+            data: c\n\n\
+            id: 2\0two\ndata: d\n\n\
+            id\ndata: e\n\n\
+            id: 3\n\n";
+        assert_eq!(
+            parse(&mut parser, &[stream.as_bytes()]),
+            vec![
+                event("message", "a", Some("0")),
+                event("message", "b", Some("1")),
+                event("message", "c", Some("1")),
+                event("message", "d", Some("1")), // An ID with a NUL is ignored
+                event("message", "e", None),      // An empty ID resets it
+            ]
+        );
+        // Set, even without an event:
+        assert_eq!(parser.last_event_id.as_deref(), Some("3"));
+    }
+
+    #[test]
+    fn retry() {
+        let cases = [
+            ("retry: 1500\n", Some(1500)),
+            ("retry:0\n", Some(0)),
+            ("retry: 1500\nretry: 20\n", Some(20)),
+            ("retry: 1500\nretry: x\n", Some(1500)),
+            ("retry: 1.5\n", None),
+            ("retry: -1\n", None),
+            ("retry: \n", None),
+            ("retry: 99999999999999999999999\n", None),
+        ];
+        for (stream, expected) in cases {
+            let mut parser = Parser::new(None);
+            assert_eq!(parse(&mut parser, &[stream.as_bytes()]), vec![]);
+            assert_eq!(
+                parser.retry,
+                expected.map(Duration::from_millis),
+                "{stream:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,259 @@
+/// An HTTP response [status code](https://developer.mozilla.org/en-US/docs/Web/HTTP/Reference/Status),
+/// e.g. `404` for "Not Found".
+///
+/// ```
+/// use ehttp::StatusCode;
+///
+/// let status = StatusCode::from(404);
+/// assert_eq!(status, StatusCode::NOT_FOUND);
+/// assert!(status.is_client_error());
+/// assert_eq!(status.canonical_reason(), Some("Not Found"));
+/// assert_eq!(status.to_string(), "404 Not Found");
+/// ```
+///
+/// Get it from a response with [`crate::Response::status_code`].
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StatusCode(u16);
+
+impl StatusCode {
+    pub const CONTINUE: Self = Self(100);
+    pub const SWITCHING_PROTOCOLS: Self = Self(101);
+
+    pub const OK: Self = Self(200);
+    pub const CREATED: Self = Self(201);
+    pub const ACCEPTED: Self = Self(202);
+    pub const NO_CONTENT: Self = Self(204);
+    pub const PARTIAL_CONTENT: Self = Self(206);
+
+    pub const MOVED_PERMANENTLY: Self = Self(301);
+    pub const FOUND: Self = Self(302);
+    pub const SEE_OTHER: Self = Self(303);
+    pub const NOT_MODIFIED: Self = Self(304);
+    pub const TEMPORARY_REDIRECT: Self = Self(307);
+    pub const PERMANENT_REDIRECT: Self = Self(308);
+
+    pub const BAD_REQUEST: Self = Self(400);
+    pub const UNAUTHORIZED: Self = Self(401);
+    pub const FORBIDDEN: Self = Self(403);
+    pub const NOT_FOUND: Self = Self(404);
+    pub const METHOD_NOT_ALLOWED: Self = Self(405);
+    pub const NOT_ACCEPTABLE: Self = Self(406);
+    pub const REQUEST_TIMEOUT: Self = Self(408);
+    pub const CONFLICT: Self = Self(409);
+    pub const GONE: Self = Self(410);
+    pub const PRECONDITION_FAILED: Self = Self(412);
+    pub const PAYLOAD_TOO_LARGE: Self = Self(413);
+    pub const UNSUPPORTED_MEDIA_TYPE: Self = Self(415);
+    pub const RANGE_NOT_SATISFIABLE: Self = Self(416);
+    pub const UNPROCESSABLE_ENTITY: Self = Self(422);
+    pub const TOO_MANY_REQUESTS: Self = Self(429);
+
+    pub const INTERNAL_SERVER_ERROR: Self = Self(500);
+    pub const NOT_IMPLEMENTED: Self = Self(501);
+    pub const BAD_GATEWAY: Self = Self(502);
+    pub const SERVICE_UNAVAILABLE: Self = Self(503);
+    pub const GATEWAY_TIMEOUT: Self = Self(504);
+
+    /// The status code as a number.
+    pub fn as_u16(self) -> u16 {
+        self.0
+    }
+
+    /// `1xx`: the request was received, and the server is continuing to process it.
+    pub fn is_informational(self) -> bool {
+        (100..200).contains(&self.0)
+    }
+
+    /// `2xx`: the request was successful.
+    ///
+    /// This is what [`crate::Response::ok`] says.
+    pub fn is_success(self) -> bool {
+        (200..300).contains(&self.0)
+    }
+
+    /// `3xx`: further action is needed, e.g. following a redirect.
+    pub fn is_redirection(self) -> bool {
+        (300..400).contains(&self.0)
+    }
+
+    /// `4xx`: there was something wrong with the request, e.g. the resource was not found.
+    pub fn is_client_error(self) -> bool {
+        (400..500).contains(&self.0)
+    }
+
+    /// `5xx`: the server failed to handle a valid request.
+    pub fn is_server_error(self) -> bool {
+        (500..600).contains(&self.0)
+    }
+
+    /// The standard reason phrase, e.g. "Not Found" for `404`, if known.
+    pub fn canonical_reason(self) -> Option<&'static str> {
+        Some(match self.0 {
+            100 => "Continue",
+            101 => "Switching Protocols",
+            200 => "OK",
+            201 => "Created",
+            202 => "Accepted",
+            203 => "Non-Authoritative Information",
+            204 => "No Content",
+            205 => "Reset Content",
+            206 => "Partial Content",
+            300 => "Multiple Choices",
+            301 => "Moved Permanently",
+            302 => "Found",
+            303 => "See Other",
+            304 => "Not Modified",
+            307 => "Temporary Redirect",
+            308 => "Permanent Redirect",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            402 => "Payment Required",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            406 => "Not Acceptable",
+            407 => "Proxy Authentication Required",
+            408 => "Request Timeout",
+            409 => "Conflict",
+            410 => "Gone",
+            411 => "Length Required",
+            412 => "Precondition Failed",
+            413 => "Payload Too Large",
+            414 => "URI Too Long",
+            415 => "Unsupported Media Type",
+            416 => "Range Not Satisfiable",
+            417 => "Expectation Failed",
+            418 => "I'm a teapot",
+            421 => "Misdirected Request",
+            422 => "Unprocessable Entity",
+            425 => "Too Early",
+            426 => "Upgrade Required",
+            428 => "Precondition Required",
+            429 => "Too Many Requests",
+            431 => "Request Header Fields Too Large",
+            451 => "Unavailable For Legal Reasons",
+            500 => "Internal Server Error",
+            501 => "Not Implemented",
+            502 => "Bad Gateway",
+            503 => "Service Unavailable",
+            504 => "Gateway Timeout",
+            505 => "HTTP Version Not Supported",
+            507 => "Insufficient Storage",
+            511 => "Network Authentication Required",
+            _ => return None,
+        })
+    }
+}
+
+impl From<u16> for StatusCode {
+    fn from(status: u16) -> Self {
+        Self(status)
+    }
+}
+
+impl From<StatusCode> for u16 {
+    fn from(status: StatusCode) -> Self {
+        status.0
+    }
+}
+
+impl PartialEq<u16> for StatusCode {
+    fn eq(&self, other: &u16) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<StatusCode> for u16 {
+    fn eq(&self, other: &StatusCode) -> bool {
+        *self == other.0
+    }
+}
+
+impl std::fmt::Debug for StatusCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+/// E.g. `404 Not Found`.
+impl std::fmt::Display for StatusCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.canonical_reason() {
+            Some(reason) => write!(f, "{} {reason}", self.0),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Error, Headers, Response};
+
+    #[test]
+    fn classes() {
+        let class = |status: u16| {
+            let status = StatusCode::from(status);
+            [
+                status.is_informational(),
+                status.is_success(),
+                status.is_redirection(),
+                status.is_client_error(),
+                status.is_server_error(),
+            ]
+            .iter()
+            .position(|&is| is)
+        };
+        let cases = [
+            (99, None),
+            (100, Some(0)),
+            (199, Some(0)),
+            (200, Some(1)),
+            (299, Some(1)),
+            (300, Some(2)),
+            (399, Some(2)),
+            (400, Some(3)),
+            (499, Some(3)),
+            (500, Some(4)),
+            (599, Some(4)),
+            (600, None),
+        ];
+        for (status, expected) in cases {
+            assert_eq!(class(status), expected, "{status}");
+        }
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(StatusCode::NOT_FOUND.to_string(), "404 Not Found");
+        assert_eq!(StatusCode::from(299).to_string(), "299");
+        assert_eq!(format!("{:?}", StatusCode::OK), "200");
+        assert_eq!(StatusCode::from(418), 418);
+        assert_eq!(u16::from(StatusCode::GONE), 410);
+    }
+
+    #[test]
+    fn error_for_status() {
+        let response = |status: u16| Response {
+            url: "https://example.com/".to_owned(),
+            ok: (200..300).contains(&status),
+            status,
+            status_text: StatusCode::from(status)
+                .canonical_reason()
+                .unwrap_or_default()
+                .to_owned(),
+            headers: Headers::default(),
+            redirects: vec![],
+            bytes: b"body".to_vec(),
+        };
+        for status in [100, 200, 204, 299, 304, 399, 600] {
+            assert!(response(status).error_for_status().is_ok(), "{}", status);
+        }
+        for status in [400, 404, 499, 500, 503, 599] {
+            match response(status).error_for_status() {
+                Err(Error::Status(err)) => assert_eq!(err.status, status),
+                other => panic!("{}: expected a status error, got {:?}", status, other),
+            }
+        }
+    }
+}
@@ -46,33 +46,34 @@
 
 use std::ops::ControlFlow;
 
-use crate::Request;
+use crate::{Request, RequestHandle};
 
 /// Performs a HTTP requests and calls the given callback once for the initial response,
 /// and then once for each chunk in the response body.
 ///
-/// You can abort the fetch by returning [`ControlFlow::Break`] from the callback.
+/// You can abort the fetch by returning [`ControlFlow::Break`] from the callback,
+/// or by calling [`RequestHandle::abort`] on the returned handle,
+/// in which case the callback receives [`crate::Error::Aborted`].
 pub fn fetch(
     request: Request,
     on_data: impl 'static + Send + Fn(crate::Result<types::Part>) -> ControlFlow<()>,
-) {
-    #[cfg(not(target_arch = "wasm32"))]
-    native::fetch_streaming(request, Box::new(on_data));
-
-    #[cfg(target_arch = "wasm32")]
-    web::fetch_streaming(request, Box::new(on_data));
+) -> RequestHandle {
+    crate::client::default_client().fetch_streaming(request, on_data)
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-mod native;
+pub(crate) mod native;
 #[cfg(not(target_arch = "wasm32"))]
 pub use native::fetch_streaming_blocking;
 
 #[cfg(target_arch = "wasm32")]
-mod web;
+pub(crate) mod web;
 #[cfg(target_arch = "wasm32")]
 pub use web::fetch_async_streaming;
 
+mod queue;
+pub(crate) use queue::Queue;
+
 mod types;
 
 pub use self::types::Part;
@@ -1,6 +1,7 @@
 use std::ops::ControlFlow;
 
-use crate::{Method, Request};
+use crate::native::body_read_error;
+use crate::{client::Backend, executor::spawn_dedicated_thread, Error, Request, RequestHandle};
 
 use super::Part;
 use crate::types::PartialResponse;
@@ -9,12 +10,28 @@ pub fn fetch_streaming_blocking(
     request: Request,
     on_data: Box<dyn Fn(crate::Result<Part>) -> ControlFlow<()> + Send>,
 ) {
-    let resp = request.fetch_raw_native(false);
+    crate::client::default_client().fetch_streaming_blocking(request, on_data);
+}
+
+/// Like [`fetch_streaming_blocking`], but uses the given backend,
+/// and stops reading the body once the request is aborted.
+pub(crate) fn fetch_streaming_blocking_with(
+    backend: &Backend,
+    request: Request,
+    handle: &RequestHandle,
+    on_data: &dyn Fn(crate::Result<Part>) -> ControlFlow<()>,
+) {
+    if handle.is_aborted() {
+        let _ = on_data(Err(Error::Aborted));
+        return;
+    }
 
-    let mut resp = match resp {
+    let resp = crate::native::send(backend, &request, false);
+
+    let (mut resp, redirects) = match resp {
         Ok(t) => t,
         Err(e) => {
-            let _ = on_data(Err(e.to_string()));
+            let _ = on_data(Err(e));
             return;
         }
     };
@@ -35,7 +52,9 @@ pub fn fetch_streaming_blocking(
             match v.to_str() {
                 Ok(t) => t,
                 Err(e) => {
-                    let _ = on_data(Err(e.to_string()));
+                    let _ = on_data(Err(Error::Other(format!(
+                        "Failed to convert header value to string: {e}"
+                    ))));
                     break;
                 }
             },
@@ -49,13 +68,23 @@ pub fn fetch_streaming_blocking(
         status,
         status_text,
         headers,
+        redirects,
     };
+    if handle.is_aborted() {
+        let _ = on_data(Err(Error::Aborted));
+        return;
+    }
     if on_data(Ok(Part::Response(response))).is_break() {
         return;
     };
 
     let mut reader = resp.body_mut().as_reader();
     loop {
+        if handle.is_aborted() {
+            let _ = on_data(Err(Error::Aborted));
+            return;
+        }
+
         let mut buf = vec![0; 2048];
         use std::io::Read;
         match reader.read(&mut buf) {
@@ -71,40 +100,36 @@ pub fn fetch_streaming_blocking(
                 break;
             }
             Err(err) => {
-                if err.kind() == std::io::ErrorKind::Other && request.method == Method::HEAD {
-                    match err.downcast::<ureq::Error>() {
-                        Ok(ureq::Error::Decompress(_, io_err))
-                            if io_err.kind() == std::io::ErrorKind::UnexpectedEof =>
-                        {
-                            // We don't really expect a body for HEAD requests, so this is fine.
-                            let _ = on_data(Ok(Part::Chunk(vec![])));
-                            break;
-                        }
-                        Ok(err_inner) => {
-                            let _ =
-                                on_data(Err(format!("Failed to read response body: {err_inner}")));
-                            return;
-                        }
-                        Err(err) => {
-                            let _ = on_data(Err(format!("Failed to read response body: {err}")));
-                            return;
-                        }
-                    }
-                } else {
-                    let _ = on_data(Err(format!("Failed to read response body: {err}")));
+                if let Some(err) = body_read_error(err, &request.method) {
+                    let _ = on_data(Err(err));
                     return;
                 }
+
+                // We don't really expect a body for HEAD requests, so this is fine.
+                let _ = on_data(Ok(Part::Chunk(vec![])));
+                break;
             }
         };
     }
 }
 
+/// Streaming requests can stay open for as long as the server likes,
+/// so they get a thread of their own instead of occupying one of the [`crate::Executor`].
 pub(crate) fn fetch_streaming(
+    backend: Backend,
     request: Request,
+    handle: RequestHandle,
     on_data: Box<dyn Fn(crate::Result<Part>) -> ControlFlow<()> + Send>,
 ) {
-    std::thread::Builder::new()
-        .name("ehttp".to_owned())
-        .spawn(move || fetch_streaming_blocking(request, on_data))
-        .expect("Failed to spawn ehttp thread");
+    spawn_dedicated_thread(Box::new(move |scheduled| {
+        let result = scheduled.and_then(|()| {
+            crate::executor::catch_panic(|| {
+                fetch_streaming_blocking_with(&backend, request, &handle, &on_data);
+                Ok(())
+            })
+        });
+        if let Err(err) = result {
+            let _ = on_data(Err(err));
+        }
+    }));
 }
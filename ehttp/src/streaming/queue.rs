@@ -0,0 +1,67 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+/// Items pushed from a callback, to be polled as a [`futures_util::Stream`].
+pub(crate) struct Queue<T> {
+    inner: Arc<Mutex<Inner<T>>>,
+}
+
+struct Inner<T> {
+    items: VecDeque<T>,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+impl<T> Clone for Queue<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> Default for Queue<T> {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                items: VecDeque::new(),
+                closed: false,
+                waker: None,
+            })),
+        }
+    }
+}
+
+impl<T> Queue<T> {
+    pub fn push(&self, item: T) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.items.push_back(item);
+        if let Some(waker) = inner.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// No more items will be pushed, so end the stream once the queued items have been polled.
+    pub fn close(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.closed = true;
+        if let Some(waker) = inner.waker.take() {
+            waker.wake();
+        }
+    }
+
+    pub fn poll_next(&self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(item) = inner.items.pop_front() {
+            Poll::Ready(Some(item))
+        } else if inner.closed {
+            Poll::Ready(None)
+        } else {
+            inner.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
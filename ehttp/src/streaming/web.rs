@@ -4,8 +4,10 @@ use futures_util::Stream;
 use futures_util::StreamExt;
 use wasm_bindgen::prelude::*;
 
-use crate::web::{fetch_base, get_response_base, spawn_future, string_from_fetch_error};
-use crate::Request;
+use crate::web::{
+    body_read_error, error_from_fetch_error, fetch_base, get_response_base, spawn_future,
+};
+use crate::{Error, Request, RequestHandle};
 
 use super::types::Part;
 
@@ -17,47 +19,51 @@ use super::types::Part;
 pub async fn fetch_async_streaming(
     request: &Request,
 ) -> crate::Result<impl Stream<Item = crate::Result<Part>>> {
-    let stream = fetch_jsvalue_stream(request)
+    crate::client::default_client()
+        .fetch_async_streaming(request)
         .await
-        .map_err(string_from_fetch_error)?;
-    Ok(stream.map(|result| result.map_err(string_from_fetch_error)))
 }
 
-#[cfg(feature = "streaming")]
-async fn fetch_jsvalue_stream(
+pub(crate) async fn fetch_async_streaming_with_handle(
     request: &Request,
-) -> Result<impl Stream<Item = Result<Part, JsValue>>, JsValue> {
+    handle: &RequestHandle,
+) -> crate::Result<impl Stream<Item = crate::Result<Part>>> {
     use js_sys::Uint8Array;
 
-    let response = fetch_base(request).await?;
-    let body = wasm_streams::ReadableStream::from_raw(
-        response.body().ok_or("response has no body")?.dyn_into()?,
-    );
+    let (response, abort_registration) = fetch_base(request, handle).await?;
+    let body = response
+        .body()
+        .ok_or_else(|| Error::BodyRead("response has no body".to_owned()))?;
+    let body = wasm_streams::ReadableStream::from_raw(body.unchecked_into());
+    let base = get_response_base(&response).map_err(error_from_fetch_error)?;
 
     // returns a `Part::Response` followed by all the chunks in `body` as `Part::Chunk`
     Ok(
-        futures_util::stream::once(futures_util::future::ready(Ok(Part::Response(
-            get_response_base(&response)?,
-        ))))
-        .chain(
-            body.into_stream()
-                .map(|value| value.map(|value| Part::Chunk(Uint8Array::new(&value).to_vec()))),
+        futures_util::stream::once(futures_util::future::ready(Ok(Part::Response(base)))).chain(
+            body.into_stream().map(move |value| {
+                let _ = &abort_registration; // Keep the request abortable until the stream is done.
+                value
+                    .map(|value| Part::Chunk(Uint8Array::new(&value).to_vec()))
+                    .map_err(body_read_error)
+            }),
         ),
     )
 }
 
 pub(crate) fn fetch_streaming(
     request: Request,
+    handle: RequestHandle,
     on_data: Box<dyn Fn(crate::Result<Part>) -> ControlFlow<()> + Send>,
 ) {
     spawn_future(async move {
-        let mut stream = match fetch_jsvalue_stream(&request).await {
+        let stream = match fetch_async_streaming_with_handle(&request, &handle).await {
             Ok(stream) => stream,
             Err(e) => {
-                let _ = on_data(Err(string_from_fetch_error(e)));
+                let _ = on_data(Err(e));
                 return;
             }
         };
+        let mut stream = std::pin::pin!(stream);
 
         while let Some(chunk) = stream.next().await {
             match chunk {
@@ -67,7 +73,7 @@ pub(crate) fn fetch_streaming(
                     }
                 }
                 Err(e) => {
-                    let _ = on_data(Err(string_from_fetch_error(e)));
+                    let _ = on_data(Err(e));
                     return;
                 }
             }
@@ -0,0 +1,311 @@
+//! ureq's TLS can't check pins, so pinned configurations get a TLS connector of their own.
+
+use std::{convert::TryFrom as _, sync::Arc};
+
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    pki_types::{CertificateDer, ServerName, UnixTime},
+    CertificateError, DigitallySignedStruct, SignatureScheme,
+};
+use ureq::unversioned::transport::{
+    Buffers, ConnectProxyConnector, ConnectionDetails, Connector, Either, LazyBuffers, NextTimeout,
+    SocksConnector, TcpConnector, Transport, TransportAdapter,
+};
+
+use super::TlsConfig;
+use crate::Result;
+
+#[derive(Clone, Copy, Debug)]
+pub(super) enum Pin {
+    /// SHA-256 of the DER-encoded certificate.
+    Certificate([u8; 32]),
+
+    /// SHA-256 of the DER-encoded `SubjectPublicKeyInfo` of the certificate.
+    PublicKey([u8; 32]),
+}
+
+impl Pin {
+    fn matches(&self, certificate: &CertificateDer<'_>) -> bool {
+        match self {
+            Self::Certificate(sha256) => sha256_eq(certificate, sha256),
+            Self::PublicKey(sha256) => webpki::EndEntityCert::try_from(certificate)
+                .is_ok_and(|parsed| sha256_eq(&parsed.subject_public_key_info(), sha256)),
+        }
+    }
+}
+
+fn sha256_eq(data: &[u8], sha256: &[u8; 32]) -> bool {
+    ring::digest::digest(&ring::digest::SHA256, data).as_ref() == sha256
+}
+
+/// An agent like the one ureq would build for the configuration, but one that also checks the pins.
+pub(super) fn agent(tls: &TlsConfig) -> Result<ureq::Agent> {
+    // Like ureq's default connector, but with our TLS:
+    let connector =
+        ().chain(SocksConnector::default())
+            .chain(ConnectProxyConnector::default())
+            .chain(TcpConnector::default())
+            .chain(TlsConnector(super::client_config(tls)?));
+    // We follow redirects ourselves, see `Request::redirect_policy`:
+    let config = ureq::Agent::config_builder().max_redirects(0).build();
+    Ok(ureq::Agent::with_parts(
+        config,
+        connector,
+        ureq::unversioned::resolver::DefaultResolver::default(),
+    ))
+}
+
+/// Also check the pins after `verifier` has accepted the certificate.
+pub(super) fn with_pins(
+    verifier: Arc<dyn ServerCertVerifier>,
+    pins: &[Pin],
+) -> Arc<dyn ServerCertVerifier> {
+    Arc::new(PinningVerifier {
+        inner: verifier,
+        pins: pins.to_vec(),
+    })
+}
+
+/// The message of a rejection by [`PinningVerifier`], if that's what this is.
+pub(super) fn pin_mismatch(err: &(dyn std::error::Error + 'static)) -> Option<String> {
+    match err.downcast_ref::<rustls::Error>()? {
+        rustls::Error::InvalidCertificate(CertificateError::Other(other))
+            if other.0.is::<PinMismatch>() =>
+        {
+            Some(other.to_string())
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+struct PinMismatch;
+
+impl std::fmt::Display for PinMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(
+            "The server certificate doesn't match any of the pinned certificates or public keys",
+        )
+    }
+}
+
+impl std::error::Error for PinMismatch {}
+
+/// Checks the pins after the inner verifier has accepted the certificate.
+#[derive(Debug)]
+struct PinningVerifier {
+    inner: Arc<dyn ServerCertVerifier>,
+    pins: Vec<Pin>,
+}
+
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+
+        let is_pinned =
+            |certificate: &CertificateDer<'_>| self.pins.iter().any(|pin| pin.matches(certificate));
+        if std::iter::once(end_entity)
+            .chain(intermediates)
+            .any(is_pinned)
+        {
+            Ok(verified)
+        } else {
+            Err(rustls::Error::InvalidCertificate(CertificateError::Other(
+                rustls::OtherError(Arc::new(PinMismatch)),
+            )))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        certificate: &CertificateDer<'_>,
+        signature: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner
+            .verify_tls12_signature(message, certificate, signature)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        certificate: &CertificateDer<'_>,
+        signature: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner
+            .verify_tls13_signature(message, certificate, signature)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Wraps connections to `https://` URLs in TLS.
+#[derive(Debug)]
+struct TlsConnector(Arc<rustls::ClientConfig>);
+
+impl<In: Transport> Connector<In> for TlsConnector {
+    type Out = Either<In, TlsTransport>;
+
+    fn connect(
+        &self,
+        details: &ConnectionDetails<'_>,
+        chained: Option<In>,
+    ) -> std::result::Result<Option<Self::Out>, ureq::Error> {
+        let Some(transport) = chained else {
+            return Ok(None);
+        };
+        if !details.needs_tls() || transport.is_tls() {
+            return Ok(Some(Either::A(transport)));
+        }
+
+        let host = details
+            .uri
+            .host()
+            .unwrap_or_default()
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+        let server_name = ServerName::try_from(host.to_owned())
+            .map_err(|_| ureq::Error::Tls("Invalid server name for TLS"))?;
+        let connection = rustls::ClientConnection::new(self.0.clone(), server_name)?;
+
+        Ok(Some(Either::B(TlsTransport {
+            buffers: LazyBuffers::new(
+                details.config.input_buffer_size(),
+                details.config.output_buffer_size(),
+            ),
+            stream: rustls::StreamOwned::new(connection, TransportAdapter::new(transport.boxed())),
+        })))
+    }
+}
+
+struct TlsTransport {
+    buffers: LazyBuffers,
+    stream: rustls::StreamOwned<rustls::ClientConnection, TransportAdapter>,
+}
+
+impl std::fmt::Debug for TlsTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsTransport").finish_non_exhaustive()
+    }
+}
+
+impl Transport for TlsTransport {
+    fn buffers(&mut self) -> &mut dyn Buffers {
+        &mut self.buffers
+    }
+
+    fn transmit_output(
+        &mut self,
+        amount: usize,
+        timeout: NextTimeout,
+    ) -> std::result::Result<(), ureq::Error> {
+        use std::io::Write as _;
+
+        self.stream.get_mut().set_timeout(timeout);
+        self.stream.write_all(&self.buffers.output()[..amount])?;
+        Ok(())
+    }
+
+    fn await_input(&mut self, timeout: NextTimeout) -> std::result::Result<bool, ureq::Error> {
+        use std::io::Read as _;
+
+        self.stream.get_mut().set_timeout(timeout);
+        let amount = self.stream.read(self.buffers.input_append_buf())?;
+        self.buffers.input_appended(amount);
+        Ok(amount > 0)
+    }
+
+    fn is_open(&mut self) -> bool {
+        self.stream.get_mut().get_mut().is_open()
+    }
+
+    fn is_tls(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn certificate() -> CertificateDer<'static> {
+        let certificates =
+            super::super::parse_certificates(super::super::tests::CERTIFICATE_PEM.as_bytes())
+                .unwrap();
+        CertificateDer::from(certificates[0].der().to_vec())
+    }
+
+    fn sha256(hex: &str) -> [u8; 32] {
+        let mut sha256 = [0; 32];
+        for (i, byte) in sha256.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+        }
+        sha256
+    }
+
+    // From `openssl x509 -outform DER | sha256sum`:
+    const CERTIFICATE_SHA256: &str =
+        "ecd76496d0cc52abdef17df8e4f17e6811c63846d25d0e223491de1d54ea4b79";
+
+    // From `openssl x509 -pubkey -noout | openssl pkey -pubin -outform DER | sha256sum`:
+    const PUBLIC_KEY_SHA256: &str =
+        "db7fdc4e63ba13d95f7e75af2a510ee1f29ac6ce07eb61444d244845fbda443b";
+
+    #[test]
+    fn pins_match() {
+        let certificate = certificate();
+        assert!(Pin::Certificate(sha256(CERTIFICATE_SHA256)).matches(&certificate));
+        assert!(Pin::PublicKey(sha256(PUBLIC_KEY_SHA256)).matches(&certificate));
+
+        assert!(!Pin::Certificate(sha256(PUBLIC_KEY_SHA256)).matches(&certificate));
+        assert!(!Pin::PublicKey(sha256(CERTIFICATE_SHA256)).matches(&certificate));
+    }
+
+    #[test]
+    fn broken_certificates_match_no_public_key() {
+        let certificate = certificate();
+        let pin = Pin::PublicKey(sha256(PUBLIC_KEY_SHA256));
+        for len in [0, 1, 2, 4, 100, certificate.len() - 1] {
+            let truncated = CertificateDer::from(&certificate[..len]);
+            assert!(!pin.matches(&truncated), "truncated to {} bytes", len);
+        }
+
+        // A length that claims more than there is:
+        let mut long_length = certificate.to_vec();
+        long_length[1] = 0x84; // four length bytes follow
+        assert!(!pin.matches(&CertificateDer::from(long_length)));
+    }
+
+    #[test]
+    fn pin_mismatch_is_recognized() {
+        let mismatch = rustls::Error::InvalidCertificate(CertificateError::Other(
+            rustls::OtherError(Arc::new(PinMismatch)),
+        ));
+        assert_eq!(pin_mismatch(&mismatch), Some(PinMismatch.to_string()));
+        assert_eq!(
+            super::super::certificate_error(&mismatch),
+            Some(PinMismatch.to_string())
+        );
+
+        let expired = rustls::Error::InvalidCertificate(CertificateError::Expired);
+        assert_eq!(pin_mismatch(&expired), None);
+        assert!(super::super::certificate_error(&expired).is_some());
+    }
+}
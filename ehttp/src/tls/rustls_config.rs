@@ -0,0 +1,134 @@
+//! A rustls configuration for a [`TlsConfig`], for when we do the TLS ourselves
+//! (for pinned certificates, and for WebSockets).
+
+use std::{convert::TryFrom as _, sync::Arc};
+
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::CryptoProvider,
+    pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime},
+    DigitallySignedStruct, SignatureScheme,
+};
+use ureq::tls::RootCerts;
+
+use super::TlsConfig;
+use crate::{Error, Result};
+
+/// The rustls configuration that ureq would use for this [`TlsConfig`], plus the pins.
+pub(crate) fn client_config(tls: &TlsConfig) -> Result<Arc<rustls::ClientConfig>> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+    let verifier = verifier(tls, &provider)?;
+    #[cfg(feature = "tls-pinning")]
+    let verifier = if tls.pins.is_empty() {
+        verifier
+    } else {
+        super::pinning::with_pins(verifier, &tls.pins)
+    };
+
+    let builder = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|err| Error::Tls(err.to_string()))?
+        .dangerous() // Not dangerous in itself: it's the verifier ureq would use
+        .with_custom_certificate_verifier(verifier);
+    let config = match &tls.client_certificate {
+        Some(client_certificate) => {
+            let certificate_chain = client_certificate
+                .certs()
+                .iter()
+                .map(|certificate| CertificateDer::from(certificate.der().to_vec()))
+                .collect();
+            let private_key = PrivateKeyDer::try_from(client_certificate.private_key().der())
+                .map_err(|err| Error::Tls(format!("Invalid private key: {err}")))?;
+            builder
+                .with_client_auth_cert(certificate_chain, private_key.clone_key())
+                .map_err(|err| Error::Tls(format!("Invalid client certificate: {err}")))?
+        }
+        None => builder.with_no_client_auth(),
+    };
+    Ok(Arc::new(config))
+}
+
+fn verifier(
+    tls: &TlsConfig,
+    provider: &Arc<CryptoProvider>,
+) -> Result<Arc<dyn ServerCertVerifier>> {
+    if tls.accept_invalid_certificates {
+        return Ok(Arc::new(AcceptAnyCertificate(provider.clone())));
+    }
+
+    let roots = match tls.root_certs()? {
+        RootCerts::PlatformVerifier => {
+            let verifier = rustls_platform_verifier::Verifier::new(provider.clone())
+                .map_err(|err| Error::Tls(format!("Failed to use the system verifier: {err}")))?;
+            return Ok(Arc::new(verifier));
+        }
+        RootCerts::WebPki => super::bundled_root_certificates().collect(),
+        RootCerts::Specific(certificates) => certificates.to_vec(),
+        _ => vec![],
+    };
+    let mut store = rustls::RootCertStore::empty();
+    for certificate in roots {
+        store
+            .add(CertificateDer::from(certificate.der().to_vec()))
+            .map_err(|err| Error::Tls(format!("Invalid root certificate: {err}")))?;
+    }
+    let verifier = rustls::client::WebPkiServerVerifier::builder_with_provider(
+        Arc::new(store),
+        provider.clone(),
+    )
+    .build()
+    .map_err(|err| Error::Tls(format!("Invalid root certificates: {err}")))?;
+    Ok(verifier)
+}
+
+/// For [`TlsConfig::danger_accept_invalid_certificates`].
+///
+/// The handshake signatures are still checked, so at least the server has the key of the certificate.
+#[derive(Debug)]
+struct AcceptAnyCertificate(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        certificate: &CertificateDer<'_>,
+        signature: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            certificate,
+            signature,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        certificate: &CertificateDer<'_>,
+        signature: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            certificate,
+            signature,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
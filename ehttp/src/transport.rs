@@ -0,0 +1,353 @@
+use std::sync::{Arc, RwLock};
+
+use crate::{client::Backend, Request, RequestHandle, Response, Result};
+
+#[cfg(feature = "streaming")]
+use std::ops::ControlFlow;
+
+#[cfg(feature = "streaming")]
+use crate::{streaming::Part, PartialResponse};
+
+/// What actually performs the requests of a [`crate::Client`].
+///
+/// The default is `ureq` on native and the browser `fetch` API on web.
+/// Plug in your own with [`crate::Client::with_transport`],
+/// e.g. an in-process fake for tests, or a different HTTP library:
+///
+/// ```
+/// struct Fake;
+///
+/// impl ehttp::Transport for Fake {
+///     fn fetch(
+///         &self,
+///         request: ehttp::Request,
+///         _handle: ehttp::RequestHandle,
+///         on_done: Box<dyn FnOnce(ehttp::Result<ehttp::Response>) + Send>,
+///     ) {
+///         on_done(Ok(ehttp::Response {
+///             url: request.url,
+///             ok: true,
+///             status: 200,
+///             status_text: "OK".to_owned(),
+///             headers: ehttp::Headers::new(&[("content-type", "text/plain")]),
+///             redirects: vec![],
+///             bytes: b"Hello!".to_vec(),
+///         }));
+///     }
+/// }
+///
+/// let client = ehttp::Client::new().with_transport(Fake);
+/// # #[cfg(not(target_arch = "wasm32"))]
+/// # {
+/// let response = client.fetch_blocking(&ehttp::Request::get("https://www.example.com")).unwrap();
+/// assert_eq!(response.text(), Some("Hello!"));
+/// # }
+/// ```
+///
+/// To use a transport for the free functions too (e.g. [`crate::fetch`]), use [`set_global_transport`].
+///
+/// The transport gets the request after the [`crate::Client`] configuration and the [`crate::Interceptor`]s
+/// have been applied, and the interceptors see the result.
+/// Everything else is up to the transport: the cache ([`crate::Client::with_cache`]),
+/// retries ([`Request::retry`]), redirects, cookies, timeouts and progress callbacks
+/// are only handled by the default transport.
+pub trait Transport: Send + Sync {
+    /// Perform the request, and call `on_done` with the result.
+    ///
+    /// This must not block, so do the work in the background (or call `on_done` right away, if you can).
+    /// If the request is aborted through the `handle`, `on_done` should receive [`crate::Error::Aborted`].
+    fn fetch(
+        &self,
+        request: Request,
+        handle: RequestHandle,
+        on_done: Box<dyn FnOnce(Result<Response>) + Send>,
+    );
+
+    /// Perform the request for the [`crate::streaming`] API.
+    ///
+    /// Call `on_data` once with the [`Part::Response`], and then once for each chunk of the body,
+    /// ending with an empty chunk (or an error).
+    /// Stop as soon as `on_data` returns [`ControlFlow::Break`].
+    ///
+    /// The default implementation calls [`Self::fetch`], and passes on the whole body as a single chunk.
+    #[cfg(feature = "streaming")]
+    fn fetch_streaming(
+        &self,
+        request: Request,
+        handle: RequestHandle,
+        on_data: Box<dyn Fn(Result<Part>) -> ControlFlow<()> + Send>,
+    ) {
+        self.fetch(
+            request,
+            handle,
+            Box::new(move |result| {
+                let Response {
+                    url,
+                    ok,
+                    status,
+                    status_text,
+                    headers,
+                    redirects,
+                    bytes,
+                } = match result {
+                    Ok(response) => response,
+                    Err(err) => {
+                        let _ = on_data(Err(err));
+                        return;
+                    }
+                };
+                let response = PartialResponse {
+                    url,
+                    ok,
+                    status,
+                    status_text,
+                    headers,
+                    redirects,
+                };
+                if on_data(Ok(Part::Response(response))).is_break() {
+                    return;
+                }
+                if !bytes.is_empty() && on_data(Ok(Part::Chunk(bytes))).is_break() {
+                    return;
+                }
+                let _ = on_data(Ok(Part::Chunk(vec![])));
+            }),
+        );
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Arc<T> {
+    fn fetch(
+        &self,
+        request: Request,
+        handle: RequestHandle,
+        on_done: Box<dyn FnOnce(Result<Response>) + Send>,
+    ) {
+        (**self).fetch(request, handle, on_done);
+    }
+
+    #[cfg(feature = "streaming")]
+    fn fetch_streaming(
+        &self,
+        request: Request,
+        handle: RequestHandle,
+        on_data: Box<dyn Fn(Result<Part>) -> ControlFlow<()> + Send>,
+    ) {
+        (**self).fetch_streaming(request, handle, on_data);
+    }
+}
+
+/// The default transport.
+impl Transport for Backend {
+    fn fetch(
+        &self,
+        request: Request,
+        handle: RequestHandle,
+        on_done: Box<dyn FnOnce(Result<Response>) + Send>,
+    ) {
+        #[cfg(not(target_arch = "wasm32"))]
+        crate::native::fetch(&self.executor, self.clone(), request, handle, on_done);
+
+        #[cfg(target_arch = "wasm32")]
+        crate::web::fetch(self.clone(), request, handle, on_done);
+    }
+
+    #[cfg(feature = "streaming")]
+    fn fetch_streaming(
+        &self,
+        request: Request,
+        handle: RequestHandle,
+        on_data: Box<dyn Fn(Result<Part>) -> ControlFlow<()> + Send>,
+    ) {
+        #[cfg(not(target_arch = "wasm32"))]
+        crate::streaming::native::fetch_streaming(self.clone(), request, handle, on_data);
+
+        #[cfg(target_arch = "wasm32")]
+        crate::streaming::web::fetch_streaming(request, handle, on_data);
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+static GLOBAL_TRANSPORT: RwLock<Option<Arc<dyn Transport>>> = RwLock::new(None);
+
+/// Use the given [`Transport`] for all requests, by all clients that don't have their own
+/// (see [`crate::Client::with_transport`]).
+///
+/// This includes the free functions, e.g. [`crate::fetch`].
+pub fn set_global_transport(transport: impl Transport + 'static) {
+    replace_global_transport(Some(Arc::new(transport)));
+}
+
+/// Go back to the default transport, after [`set_global_transport`].
+pub fn clear_global_transport() {
+    replace_global_transport(None);
+}
+
+/// The transport set with [`set_global_transport`], if any.
+pub(crate) fn global_transport() -> Option<Arc<dyn Transport>> {
+    GLOBAL_TRANSPORT.read().unwrap().clone()
+}
+
+pub(crate) fn replace_global_transport(transport: Option<Arc<dyn Transport>>) {
+    *GLOBAL_TRANSPORT.write().unwrap() = transport;
+}
+
+// ----------------------------------------------------------------------------
+
+/// Turns the callback of [`Transport::fetch`] into a future.
+///
+/// The request is sent when the future is first polled.
+#[cfg(any(target_arch = "wasm32", feature = "native-async"))]
+pub(crate) fn fetch_async(
+    transport: Arc<dyn Transport>,
+    request: Request,
+    handle: RequestHandle,
+) -> impl std::future::Future<Output = Result<Response>> {
+    use std::{
+        sync::Mutex,
+        task::{Poll, Waker},
+    };
+
+    #[derive(Default)]
+    struct Slot {
+        result: Option<Result<Response>>,
+        waker: Option<Waker>,
+    }
+
+    /// Fills the slot when dropped, so we don't wait forever if the transport drops the callback.
+    struct Sender {
+        slot: Arc<Mutex<Slot>>,
+        result: Option<Result<Response>>,
+    }
+
+    impl Sender {
+        fn send(mut self, result: Result<Response>) {
+            self.result = Some(result);
+        }
+    }
+
+    impl Drop for Sender {
+        fn drop(&mut self) {
+            let result = self.result.take().unwrap_or_else(|| Err(dropped()));
+            let mut slot = self.slot.lock().unwrap();
+            slot.result = Some(result);
+            if let Some(waker) = slot.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    let slot = Arc::new(Mutex::new(Slot::default()));
+    let mut request = Some(request);
+    std::future::poll_fn(move |cx| {
+        if let Some(request) = request.take() {
+            let sender = Sender {
+                slot: slot.clone(),
+                result: None,
+            };
+            transport.fetch(
+                request,
+                handle.clone(),
+                Box::new(move |result| sender.send(result)),
+            );
+        }
+
+        let mut slot = slot.lock().unwrap();
+        match slot.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    })
+}
+
+fn dropped() -> crate::Error {
+    crate::Error::Other("The transport dropped the request without a result".to_owned())
+}
+
+/// Calls [`Transport::fetch`], and blocks the thread until it is done.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn fetch_blocking(
+    transport: &dyn Transport,
+    request: Request,
+    handle: RequestHandle,
+) -> Result<Response> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    transport.fetch(
+        request,
+        handle,
+        Box::new(move |result| {
+            tx.send(result).ok();
+        }),
+    );
+    rx.recv().unwrap_or_else(|_| Err(dropped()))
+}
+
+/// Calls [`Transport::fetch_streaming`], and blocks the thread until it is done.
+#[cfg(all(feature = "streaming", not(target_arch = "wasm32")))]
+pub(crate) fn fetch_streaming_blocking(
+    transport: &dyn Transport,
+    request: Request,
+    handle: RequestHandle,
+    on_data: Box<dyn Fn(Result<Part>) -> ControlFlow<()> + Send>,
+) {
+    // The transport is done once it drops the callback:
+    let (tx, rx) = std::sync::mpsc::channel::<()>();
+    transport.fetch_streaming(
+        request,
+        handle,
+        Box::new(move |part| {
+            let _ = &tx;
+            on_data(part)
+        }),
+    );
+    rx.recv().ok();
+}
+
+/// Calls [`Transport::fetch_streaming`], and returns the parts as a stream.
+///
+/// Fails if the request fails before the [`Part::Response`].
+#[cfg(all(feature = "streaming", target_arch = "wasm32"))]
+pub(crate) async fn fetch_async_streaming(
+    transport: &dyn Transport,
+    request: Request,
+    handle: RequestHandle,
+) -> Result<impl futures_util::Stream<Item = Result<Part>>> {
+    use futures_util::StreamExt as _;
+
+    /// Ends the stream even if the transport drops the callback without finishing.
+    struct CloseOnDrop(crate::streaming::Queue<Result<Part>>);
+
+    impl Drop for CloseOnDrop {
+        fn drop(&mut self) {
+            self.0.close();
+        }
+    }
+
+    let queue = crate::streaming::Queue::default();
+    transport.fetch_streaming(request, handle, {
+        let queue = CloseOnDrop(queue.clone());
+        Box::new(move |part| {
+            let queue = &queue.0;
+            match part {
+                Ok(Part::Chunk(chunk)) if chunk.is_empty() => queue.close(),
+                Ok(part) => queue.push(Ok(part)),
+                Err(err) => {
+                    queue.push(Err(err));
+                    queue.close();
+                }
+            }
+            ControlFlow::Continue(())
+        })
+    });
+
+    let mut parts = futures_util::stream::poll_fn(move |cx| queue.poll_next(cx));
+    match parts.next().await {
+        Some(Ok(first)) => Ok(futures_util::stream::once(async { Ok(first) }).chain(parts)),
+        Some(Err(err)) => Err(err),
+        None => Err(dropped()),
+    }
+}
@@ -1,6 +1,8 @@
 use std::time::Duration;
 
-#[cfg(feature = "json")]
+use crate::{Body, Progress, ProgressCallback, Redirect, RedirectPolicy, RetryPolicy, StatusCode};
+
+#[cfg(any(feature = "json", feature = "form"))]
 use serde::Serialize;
 
 #[cfg(feature = "multipart")]
@@ -9,7 +11,7 @@ use crate::multipart::MultipartBuilder;
 /// Headers in a [`Request`] or [`Response`].
 ///
 /// Note that the same header key can appear twice.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct Headers {
     /// Name-value pairs.
     pub headers: Vec<(String, String)>,
@@ -175,14 +177,50 @@ pub struct Request {
     pub url: String,
 
     /// The data you send with e.g. "POST".
-    pub body: Vec<u8>,
+    pub body: Body,
 
     /// ("Accept", "*/*"), …
     pub headers: Headers,
 
     /// Cancel the request if it doesn't complete fast enough.
+    ///
+    /// The request then fails with [`Error::Timeout`].
     pub timeout: Option<Duration>,
 
+    /// Try again if the request fails, e.g. with a `503 Service Unavailable`.
+    ///
+    /// If `None`, the [`crate::RetryPolicy`] of the [`crate::Client`] is used (if any).
+    pub retry: Option<RetryPolicy>,
+
+    /// Which redirects to follow.
+    pub redirect_policy: RedirectPolicy,
+
+    /// Called as the [`Self::body`] is being sent.
+    ///
+    /// See [`Self::with_upload_progress`].
+    pub upload_progress: Option<ProgressCallback>,
+
+    /// Called as the body of the response is being received.
+    ///
+    /// See [`Self::with_download_progress`].
+    pub download_progress: Option<ProgressCallback>,
+
+    /// Send the request through this proxy.
+    ///
+    /// If `None`, the proxy of the [`crate::Client`] is used (if any).
+    ///
+    /// Only available when compiling for native.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub proxy: Option<crate::Proxy>,
+
+    /// TLS options for `https://` URLs.
+    ///
+    /// If `None`, the TLS options of the [`crate::Client`] are used (if any).
+    ///
+    /// Only available when compiling for native.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub tls: Option<crate::TlsConfig>,
+
     /// Request mode used on fetch.
     ///
     /// Used on Web to control CORS.
@@ -206,9 +244,17 @@ impl Request {
         Self {
             method,
             url: url.to_string(),
-            body: vec![],
+            body: Body::default(),
             headers: headers.into(),
             timeout: Some(Self::DEFAULT_TIMEOUT),
+            retry: None,
+            redirect_policy: RedirectPolicy::default(),
+            upload_progress: None,
+            download_progress: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            proxy: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            tls: None,
             #[cfg(target_arch = "wasm32")]
             mode: Mode::default(),
             #[cfg(target_arch = "wasm32")]
@@ -334,6 +380,59 @@ impl Request {
         .with_body(serde_json::to_string(body)?.into_bytes()))
     }
 
+    /// Create a `POST` request with the given url and `application/x-www-form-urlencoded` body.
+    ///
+    /// The body can be anything that serializes to a flat list of keys and values,
+    /// e.g. a struct, a map or a slice of pairs:
+    /// ```
+    /// let request = ehttp::Request::post_form(
+    ///     "https://www.example.com/login",
+    ///     &[("username", "ferris"), ("password", "hunter2")],
+    /// )?;
+    /// assert_eq!(request.body.as_bytes(), Some(&b"username=ferris&password=hunter2"[..]));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[cfg(feature = "form")]
+    pub fn post_form<T>(
+        url: impl ToString,
+        body: &T,
+    ) -> std::result::Result<Self, serde_urlencoded::ser::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(Self::new(
+            Method::POST,
+            url,
+            &[
+                ("Accept", "*/*"),
+                ("Content-Type", "application/x-www-form-urlencoded"),
+            ],
+        )
+        .with_body(serde_urlencoded::to_string(body)?.into_bytes()))
+    }
+
+    /// Create a `PUT` request with the given url and `application/x-www-form-urlencoded` body.
+    ///
+    /// See [`Self::post_form`].
+    #[cfg(feature = "form")]
+    pub fn put_form<T>(
+        url: impl ToString,
+        body: &T,
+    ) -> std::result::Result<Self, serde_urlencoded::ser::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(Self::new(
+            Method::PUT,
+            url,
+            &[
+                ("Accept", "*/*"),
+                ("Content-Type", "application/x-www-form-urlencoded"),
+            ],
+        )
+        .with_body(serde_urlencoded::to_string(body)?.into_bytes()))
+    }
+
     /// Set the HTTP method.
     pub fn with_method(mut self, method: Method) -> Self {
         self.method = method;
@@ -346,9 +445,66 @@ impl Request {
         self
     }
 
+    /// Append query parameters to the URL, percent-encoding them.
+    ///
+    /// Any existing query parameters are kept.
+    /// ```
+    /// let request = ehttp::Request::get("https://www.example.com/search?page=2")
+    ///     .with_query(&[("q", "rust & wasm"), ("lang", "en")]);
+    /// assert_eq!(request.url, "https://www.example.com/search?page=2&q=rust+%26+wasm&lang=en");
+    /// ```
+    pub fn with_query<K: AsRef<str>, V: AsRef<str>>(mut self, params: &[(K, V)]) -> Self {
+        let query = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(params)
+            .finish();
+        self.append_query(&query);
+        self
+    }
+
+    /// Append query parameters to the URL, serializing them with `serde`.
+    ///
+    /// Like [`Self::with_query`], but for anything that serializes to a flat list of keys and values,
+    /// e.g. a struct or a map. Fields that are `None` are left out.
+    #[cfg(feature = "form")]
+    pub fn with_query_serde<T>(
+        mut self,
+        params: &T,
+    ) -> std::result::Result<Self, serde_urlencoded::ser::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let query = serde_urlencoded::to_string(params)?;
+        self.append_query(&query);
+        Ok(self)
+    }
+
+    /// Append an encoded query string to [`Self::url`], before any fragment.
+    fn append_query(&mut self, query: &str) {
+        if query.is_empty() {
+            return;
+        }
+        let (url, fragment) = match self.url.split_once('#') {
+            Some((url, fragment)) => (url, Some(fragment)),
+            None => (self.url.as_str(), None),
+        };
+        let separator = match url.find('?') {
+            None => "?",
+            Some(index) if index + 1 == url.len() || url.ends_with('&') => "",
+            Some(_) => "&",
+        };
+        let mut new_url = format!("{url}{separator}{query}");
+        if let Some(fragment) = fragment {
+            new_url.push('#');
+            new_url.push_str(fragment);
+        }
+        self.url = new_url;
+    }
+
     /// Set the request body.
-    pub fn with_body(mut self, body: Vec<u8>) -> Self {
-        self.body = body;
+    ///
+    /// This can be a `Vec<u8>`, a `String`, or any other [`Body`], e.g. one that streams a file.
+    pub fn with_body(mut self, body: impl Into<Body>) -> Self {
+        self.body = body.into();
         self
     }
 
@@ -370,6 +526,92 @@ impl Request {
         self
     }
 
+    /// Retry the request according to the given policy if it fails.
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Set which redirects to follow.
+    pub fn with_redirect_policy(mut self, redirect_policy: RedirectPolicy) -> Self {
+        self.redirect_policy = redirect_policy;
+        self
+    }
+
+    /// Send the request through the given proxy.
+    ///
+    /// Only available when compiling for native.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_proxy(mut self, proxy: crate::Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Use the given TLS options, e.g. to trust a private CA or to present a client certificate.
+    ///
+    /// Only available when compiling for native.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_tls(mut self, tls: crate::TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Call the given callback as the body is being sent, e.g. to show a progress bar.
+    ///
+    /// It is called once before any bytes are sent, and then every time more bytes have been sent.
+    /// If the request is retried, the progress starts over.
+    ///
+    /// On web, requests with an upload progress callback are sent with
+    /// [`XMLHttpRequest`](https://developer.mozilla.org/en-US/docs/Web/API/XMLHttpRequest)
+    /// instead of `fetch`, because `fetch` can't report upload progress.
+    /// This means they don't work in Node.js, and that `mode` and [`Self::redirect_policy`] are ignored.
+    ///
+    /// ```
+    /// let request = ehttp::Request::post("https://www.example.com", vec![0; 1_000_000])
+    ///     .with_upload_progress(|progress: ehttp::Progress| {
+    ///         if let Some(fraction) = progress.fraction() {
+    ///             println!("Uploaded {:.0}%", 100.0 * fraction);
+    ///         }
+    ///     });
+    /// ```
+    pub fn with_upload_progress(
+        mut self,
+        on_progress: impl Fn(Progress) + Send + Sync + 'static,
+    ) -> Self {
+        self.upload_progress = Some(ProgressCallback::new(on_progress));
+        self
+    }
+
+    /// Call the given callback as the body of the response is being received, e.g. to show a progress bar.
+    ///
+    /// It is called once the response headers have arrived, and then every time more bytes have been received.
+    /// [`Progress::total`] is taken from the `Content-Length` header,
+    /// and is `None` if there is none, or if the response is compressed.
+    /// If the request is retried, the progress starts over.
+    /// It is not called for responses served from a [`crate::cache::Cache`] without contacting the server.
+    ///
+    /// This works with the plain [`crate::fetch`] and [`crate::fetch_async`];
+    /// no need for the `streaming` API.
+    ///
+    /// ```
+    /// let request = ehttp::Request::get("https://www.example.com")
+    ///     .with_download_progress(|progress: ehttp::Progress| {
+    ///         println!(
+    ///             "Downloaded {} of {:?} bytes ({:.0} bytes/s)",
+    ///             progress.bytes,
+    ///             progress.total,
+    ///             progress.bytes_per_second()
+    ///         );
+    ///     });
+    /// ```
+    pub fn with_download_progress(
+        mut self,
+        on_progress: impl Fn(Progress) + Send + Sync + 'static,
+    ) -> Self {
+        self.download_progress = Some(ProgressCallback::new(on_progress));
+        self
+    }
+
     /// Set the request mode (controls CORS behavior on web).
     #[cfg(target_arch = "wasm32")]
     pub fn with_mode(mut self, mode: Mode) -> Self {
@@ -387,11 +629,40 @@ impl Request {
     /// Fetch the ureq response from a page
     #[cfg(not(target_arch = "wasm32"))]
     pub fn fetch_raw_native(&self, with_timeout: bool) -> Result<ureq::http::Response<ureq::Body>> {
+        self.fetch_raw_native_with_agent(&ureq::Agent::new_with_defaults(), with_timeout)
+    }
+
+    /// Fetch the ureq response from a page, reusing the connections of the given agent.
+    ///
+    /// If `with_timeout` is set, [`Self::timeout`] applies to the whole request,
+    /// otherwise only to receiving the response body.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn fetch_raw_native_with_agent(
+        &self,
+        agent: &ureq::Agent,
+        with_timeout: bool,
+    ) -> Result<ureq::http::Response<ureq::Body>> {
+        // This also keeps ureq from picking a proxy from the environment variables:
+        let proxy = match &self.proxy {
+            Some(proxy) => proxy.for_url(&self.url)?,
+            None => None,
+        };
+
+        // Custom TLS needs an agent of its own:
+        let tls_agent;
+        let agent = match &self.tls {
+            Some(tls) => {
+                tls_agent = tls.agent()?;
+                &tls_agent
+            }
+            None => agent,
+        };
+
         if self.method.contains_body() {
             let mut req = match self.method {
-                Method::POST => ureq::post(&self.url),
-                Method::PATCH => ureq::patch(&self.url),
-                Method::PUT => ureq::put(&self.url),
+                Method::POST => agent.post(&self.url),
+                Method::PATCH => agent.patch(&self.url),
+                Method::PUT => agent.put(&self.url),
                 // These three are the only requests which contain a body, no other requests will be matched
                 _ => unreachable!(), // because of the `.contains_body()` call
             };
@@ -402,48 +673,76 @@ impl Request {
 
             req = {
                 if with_timeout {
-                    req.config()
+                    req.config().timeout_global(self.timeout)
                 } else {
                     req.config().timeout_recv_body(self.timeout)
                 }
                 .http_status_as_error(false)
+                .proxy(proxy.clone())
                 .build()
             };
 
             if self.body.is_empty() {
-                req.send_empty()
+                req.send_empty().map_err(Error::from)
             } else {
-                req.send(&self.body)
+                self.send_body_native(req)
             }
         } else {
             let mut req = match self.method {
-                Method::GET => ureq::get(&self.url),
-                Method::DELETE => ureq::delete(&self.url),
-                Method::CONNECT => ureq::connect(&self.url),
-                Method::HEAD => ureq::head(&self.url),
-                Method::OPTIONS => ureq::options(&self.url),
-                Method::TRACE => ureq::trace(&self.url),
+                Method::GET => agent.get(&self.url),
+                Method::DELETE => agent.delete(&self.url),
+                Method::CONNECT => agent.connect(&self.url),
+                Method::HEAD => agent.head(&self.url),
+                Method::OPTIONS => agent.options(&self.url),
+                Method::TRACE => agent.trace(&self.url),
                 // Include all other variants rather than a catch all here to prevent confusion if another variant were to be added
                 Method::PATCH | Method::POST | Method::PUT => unreachable!(), // because of the `.contains_body()` call
             };
 
-            req = req
-                .config()
-                .timeout_recv_body(self.timeout)
+            req = {
+                if with_timeout {
+                    req.config().timeout_global(self.timeout)
+                } else {
+                    req.config().timeout_recv_body(self.timeout)
+                }
                 .http_status_as_error(false)
-                .build();
+                .proxy(proxy.clone())
+                .build()
+            };
 
             for (k, v) in &self.headers {
                 req = req.header(k, v);
             }
 
             if self.body.is_empty() {
-                req.call()
+                req.call().map_err(Error::from)
             } else {
-                req.force_send_body().send(&self.body)
+                self.send_body_native(req.force_send_body())
             }
         }
-        .map_err(|err| err.to_string())
+    }
+
+    /// Send [`Self::body`], reporting the progress to [`Self::upload_progress`].
+    #[cfg(not(target_arch = "wasm32"))]
+    fn send_body_native(
+        &self,
+        mut req: ureq::RequestBuilder<ureq::typestate::WithBody>,
+    ) -> Result<ureq::http::Response<ureq::Body>> {
+        let (reader, length) = self.body.reader()?;
+        let mut reader = match &self.upload_progress {
+            Some(upload_progress) => {
+                let tracker =
+                    crate::progress::ProgressTracker::start(upload_progress.clone(), length);
+                Box::new(crate::progress::ProgressReader::new(reader, tracker))
+            }
+            None => reader,
+        };
+        if let Some(length) = length {
+            // Without this, ureq would use chunked transfer encoding for the reader:
+            req = req.header("Content-Length", length);
+        }
+        req.send(ureq::SendBody::from_reader(&mut reader))
+            .map_err(Error::from)
     }
 }
 
@@ -465,6 +764,11 @@ pub struct Response {
     /// The returned headers.
     pub headers: Headers,
 
+    /// The redirects that were followed to get here, in order.
+    ///
+    /// Always empty on web, where the browser doesn't tell us about them.
+    pub redirects: Vec<Redirect>,
+
     /// The raw bytes of the response body.
     pub bytes: Vec<u8>,
 }
@@ -484,6 +788,28 @@ impl Response {
     pub fn content_type(&self) -> Option<&str> {
         self.headers.get("content-type")
     }
+
+    /// [`Self::status`] as a [`StatusCode`].
+    pub fn status_code(&self) -> StatusCode {
+        StatusCode::from(self.status)
+    }
+
+    /// Turn a `4xx` or `5xx` response into an [`Error::Status`].
+    ///
+    /// ```no_run
+    /// # fn main() -> ehttp::Result<()> {
+    /// let response = ehttp::fetch_blocking(&ehttp::Request::get("https://www.example.com"))?
+    ///     .error_for_status()?;
+    /// # Ok(()) }
+    /// ```
+    pub fn error_for_status(self) -> Result<Self> {
+        let status = self.status_code();
+        if status.is_client_error() || status.is_server_error() {
+            Err(Error::Status(Box::new(StatusError::new(self))))
+        } else {
+            Ok(self)
+        }
+    }
 }
 
 impl std::fmt::Debug for Response {
@@ -494,6 +820,7 @@ impl std::fmt::Debug for Response {
             status,
             status_text,
             headers,
+            redirects,
             bytes,
         } = self;
 
@@ -503,6 +830,7 @@ impl std::fmt::Debug for Response {
             .field("status", status)
             .field("status_text", status_text)
             .field("headers", headers)
+            .field("redirects", redirects)
             .field("bytes", &format!("{} bytes", bytes.len()))
             .finish_non_exhaustive()
     }
@@ -525,9 +853,19 @@ pub struct PartialResponse {
 
     /// The returned headers.
     pub headers: Headers,
+
+    /// The redirects that were followed to get here, in order.
+    ///
+    /// Always empty on web, where the browser doesn't tell us about them.
+    pub redirects: Vec<Redirect>,
 }
 
 impl PartialResponse {
+    /// [`Self::status`] as a [`StatusCode`].
+    pub fn status_code(&self) -> StatusCode {
+        StatusCode::from(self.status)
+    }
+
     pub fn complete(self, bytes: Vec<u8>) -> Response {
         let Self {
             url,
@@ -535,6 +873,7 @@ impl PartialResponse {
             status,
             status_text,
             headers,
+            redirects,
         } = self;
         Response {
             url,
@@ -542,6 +881,7 @@ impl PartialResponse {
             status,
             status_text,
             headers,
+            redirects,
             bytes,
         }
     }
@@ -551,7 +891,143 @@ impl PartialResponse {
 ///
 /// This is only used when we fail to make a request.
 /// Any response results in `Ok`, including things like 404 (file not found).
-pub type Error = String;
+///
+/// The [`std::fmt::Display`] implementation gives a human-readable description of the error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The request did not complete before its [`Request::timeout`].
+    Timeout(String),
+
+    /// We failed to connect to the server, e.g. because the connection was refused.
+    Connect(String),
+
+    /// DNS resolution of the host name failed.
+    Dns(String),
+
+    /// The server certificate was rejected, e.g. because it is expired, self-signed,
+    /// for another host, or doesn't match a pinned certificate.
+    Certificate(String),
+
+    /// Something else went wrong with TLS, e.g. the handshake failed.
+    Tls(String),
+
+    /// The URL is invalid.
+    InvalidUrl(String),
+
+    /// We got a response, but failed to read its body.
+    BodyRead(String),
+
+    /// The request was aborted before it completed.
+    Aborted,
+
+    /// A network error, e.g. a lost connection.
+    ///
+    /// On web, this is also what you get for CORS errors and requests blocked by the browser,
+    /// since the `fetch` API does not tell them apart.
+    Network(String),
+
+    /// The server responded with a `4xx` or `5xx` status.
+    ///
+    /// Only returned by [`Response::error_for_status`];
+    /// otherwise such responses are `Ok`.
+    Status(Box<StatusError>),
+
+    /// Any other error.
+    Other(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Timeout(msg)
+            | Self::Connect(msg)
+            | Self::Dns(msg)
+            | Self::Certificate(msg)
+            | Self::Tls(msg)
+            | Self::InvalidUrl(msg)
+            | Self::BodyRead(msg)
+            | Self::Network(msg)
+            | Self::Other(msg) => f.write_str(msg),
+            Self::Status(err) => err.fmt(f),
+            Self::Aborted => f.write_str("The request was aborted"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// The details of an [`Error::Status`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StatusError {
+    /// The URL of the response.
+    pub url: String,
+
+    /// E.g. `404`.
+    pub status: StatusCode,
+
+    /// E.g. "Not Found".
+    pub status_text: String,
+
+    /// The headers of the response.
+    pub headers: Headers,
+
+    /// The start of the response body, as text, e.g. for an error message.
+    pub body: String,
+}
+
+impl StatusError {
+    /// How much of the body we keep.
+    const MAX_BODY_LEN: usize = 1024;
+
+    fn new(response: Response) -> Self {
+        let mut body = String::from_utf8_lossy(&response.bytes).trim().to_owned();
+        if body.len() > Self::MAX_BODY_LEN {
+            let mut end = Self::MAX_BODY_LEN;
+            while !body.is_char_boundary(end) {
+                end -= 1;
+            }
+            body.truncate(end);
+            body.push('…');
+        }
+        Self {
+            status: response.status_code(),
+            url: response.url,
+            status_text: response.status_text,
+            headers: response.headers,
+            body,
+        }
+    }
+}
+
+/// E.g. `404 Not Found from https://example.com/users/42: No such user`.
+impl std::fmt::Display for StatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} from {}",
+            self.status.as_u16(),
+            self.status_text,
+            self.url
+        )?;
+        if !self.body.is_empty() {
+            write!(f, ": {}", self.body)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<String> for Error {
+    fn from(msg: String) -> Self {
+        Self::Other(msg)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(msg: &str) -> Self {
+        Self::Other(msg.to_owned())
+    }
+}
 
 /// A type-alias for `Result<T, ehttp::Error>`.
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,247 @@
+use std::{convert::TryFrom, str::FromStr};
+
+use crate::{Error, Result};
+
+/// A validated, absolute `http://` or `https://` URL.
+///
+/// Parsing normalizes the URL, e.g. international domain names are converted to punycode,
+/// and the path `/` is added if there is none:
+///
+/// ```
+/// let url = ehttp::Url::parse("https://bücher.example/search?q=rust")?;
+/// assert_eq!(url.as_str(), "https://xn--bcher-kva.example/search?q=rust");
+/// assert_eq!(url.host(), "xn--bcher-kva.example");
+/// assert_eq!(url.port_or_default(), 443);
+/// assert_eq!(url.path(), "/search");
+///
+/// let url = url.join("../images/cover.png")?;
+/// assert_eq!(url.as_str(), "https://xn--bcher-kva.example/images/cover.png");
+///
+/// let request = ehttp::Request::get(url);
+/// # Ok::<(), ehttp::Error>(())
+/// ```
+///
+/// [`Request::url`](crate::Request::url) is a plain `String`, so you don't have to use this.
+/// The URL of every request is validated and normalized anyway before it is sent,
+/// and a bad one results in an [`Error::InvalidUrl`] right away.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Url(::url::Url);
+
+impl Url {
+    /// Parse and validate an absolute URL.
+    pub fn parse(url: &str) -> Result<Self> {
+        let parsed = ::url::Url::parse(url.trim())
+            .map_err(|err| Error::InvalidUrl(format!("Invalid URL {url:?}: {err}")))?;
+        Self::validate(parsed)
+    }
+
+    /// Resolve a relative URL against this one, like a browser resolves a link on a page.
+    ///
+    /// Note that this is not the same as appending a path:
+    /// `https://example.com/api/v1` joined with `users` is `https://example.com/api/users`.
+    /// Use a trailing slash in the base (`https://example.com/api/v1/`) to get `https://example.com/api/v1/users`.
+    ///
+    /// An absolute `url` replaces this one.
+    pub fn join(&self, url: &str) -> Result<Self> {
+        let joined = self.0.join(url.trim()).map_err(|err| {
+            Error::InvalidUrl(format!("Invalid URL {url:?} relative to {self}: {err}"))
+        })?;
+        Self::validate(joined)
+    }
+
+    fn validate(url: ::url::Url) -> Result<Self> {
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(Error::InvalidUrl(format!(
+                "Unsupported scheme {:?} in URL {:?}: expected http or https",
+                url.scheme(),
+                url.as_str()
+            )));
+        }
+        if url.host().is_none() {
+            // Not possible for http(s) URLs, but let's not rely on that:
+            return Err(Error::InvalidUrl(format!(
+                "Missing host in URL {:?}",
+                url.as_str()
+            )));
+        }
+        Ok(Self(url))
+    }
+
+    /// The whole URL.
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    /// `http` or `https`.
+    pub fn scheme(&self) -> &str {
+        self.0.scheme()
+    }
+
+    /// The host, e.g. `example.com`, `127.0.0.1` or `[::1]`.
+    ///
+    /// International domain names are in punycode.
+    pub fn host(&self) -> &str {
+        self.0.host_str().unwrap_or_default()
+    }
+
+    /// The port, if it is in the URL and not the default of the scheme.
+    pub fn port(&self) -> Option<u16> {
+        self.0.port()
+    }
+
+    /// The port, or the default of the scheme (80 for `http`, 443 for `https`).
+    pub fn port_or_default(&self) -> u16 {
+        self.0.port_or_known_default().unwrap_or_default()
+    }
+
+    /// The percent-encoded path, e.g. `/` or `/search`.
+    pub fn path(&self) -> &str {
+        self.0.path()
+    }
+
+    /// The percent-encoded query string (without the `?`), if any.
+    pub fn query(&self) -> Option<&str> {
+        self.0.query()
+    }
+}
+
+impl std::fmt::Debug for Url {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl std::fmt::Display for Url {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl AsRef<str> for Url {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl FromStr for Url {
+    type Err = Error;
+
+    fn from_str(url: &str) -> Result<Self> {
+        Self::parse(url)
+    }
+}
+
+impl TryFrom<&str> for Url {
+    type Error = Error;
+
+    fn try_from(url: &str) -> Result<Self> {
+        Self::parse(url)
+    }
+}
+
+impl TryFrom<String> for Url {
+    type Error = Error;
+
+    fn try_from(url: String) -> Result<Self> {
+        Self::parse(&url)
+    }
+}
+
+impl From<Url> for String {
+    fn from(url: Url) -> Self {
+        url.0.into()
+    }
+}
+
+/// Validate and normalize the URL of a request.
+///
+/// On web, relative URLs are left for the browser to resolve against the page,
+/// as are `blob:` and `data:` URLs.
+pub(crate) fn normalize(url: &str) -> Result<String> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        match ::url::Url::parse(url.trim()) {
+            Err(::url::ParseError::RelativeUrlWithoutBase) => return Ok(url.to_owned()),
+            Ok(parsed) if matches!(parsed.scheme(), "blob" | "data") => return Ok(url.to_owned()),
+            _ => {}
+        }
+    }
+
+    Url::parse(url).map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_normalize() {
+        let cases = [
+            ("https://example.com", "https://example.com/"),
+            ("  https://example.com/a b  ", "https://example.com/a%20b"),
+            ("HTTPS://EXAMPLE.COM:443/Path", "https://example.com/Path"),
+            (
+                "http://example.com:8080/?q=1#top",
+                "http://example.com:8080/?q=1#top",
+            ),
+            ("http://[::1]/", "http://[::1]/"),
+        ];
+        for (url, expected) in cases {
+            assert_eq!(Url::parse(url).unwrap().as_str(), expected, "{url:?}");
+        }
+
+        let url = Url::parse("http://[::1]:8080/search?q=rust").unwrap();
+        assert_eq!(url.scheme(), "http");
+        assert_eq!(url.host(), "[::1]");
+        assert_eq!(url.port(), Some(8080));
+        assert_eq!(url.port_or_default(), 8080);
+        assert_eq!(url.path(), "/search");
+        assert_eq!(url.query(), Some("q=rust"));
+        assert_eq!(Url::parse("http://a.com").unwrap().port_or_default(), 80);
+    }
+
+    #[test]
+    fn invalid_urls() {
+        for url in [
+            "",
+            "example.com",
+            "/relative",
+            "users:42",
+            "ftp://example.com/",
+            "file:///etc/passwd",
+            "data:text/plain,hi",
+            "https://",
+            "https://exa mple.com/",
+        ] {
+            assert!(
+                matches!(Url::parse(url), Err(Error::InvalidUrl(_))),
+                "{:?}",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn join() {
+        let base = Url::parse("https://example.com/api/v1").unwrap();
+        let join = |url| base.join(url).map(String::from);
+        assert_eq!(join("users").unwrap(), "https://example.com/api/users");
+        assert_eq!(join("/users").unwrap(), "https://example.com/users");
+        assert_eq!(
+            join("?page=2").unwrap(),
+            "https://example.com/api/v1?page=2"
+        );
+        assert_eq!(join("http://other.com").unwrap(), "http://other.com/");
+        assert!(join("ftp://other.com").is_err());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn normalize_requires_absolute_urls() {
+        assert_eq!(
+            normalize("https://example.com").unwrap(),
+            "https://example.com/"
+        );
+        assert!(normalize("/relative").is_err());
+    }
+}
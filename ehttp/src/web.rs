@@ -1,14 +1,23 @@
+use std::{cell::RefCell, collections::HashMap};
+
 use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::JsFuture;
 
 use crate::types::PartialResponse;
-use crate::{Request, Response};
+use crate::{client::Backend, Error, Request, RequestHandle, Response};
 
 /// Binds the JavaScript `fetch` method for use in both Node.js (>= v18.0) and browser environments.
 #[wasm_bindgen]
 extern "C" {
     #[wasm_bindgen(js_name = fetch)]
     fn fetch_with_request(input: &web_sys::Request) -> js_sys::Promise;
+
+    /// Returns a timer id (a number in browsers, a `Timeout` object in Node.js).
+    #[wasm_bindgen(js_name = setTimeout)]
+    fn set_timeout(handler: &js_sys::Function, millis: i32) -> JsValue;
+
+    #[wasm_bindgen(js_name = clearTimeout)]
+    fn clear_timeout(id: &JsValue);
 }
 
 /// Only available when compiling for web.
@@ -27,49 +36,471 @@ extern "C" {
 /// * The initial GET which returned HTML contained CSP headers to block access to the resource
 /// * A browser extension blocked the request (e.g. ad blocker)
 /// * …
-pub async fn fetch_async(request: &Request) -> crate::Result<Response> {
-    fetch_jsvalue(request)
-        .await
-        .map_err(string_from_fetch_error)
+///
+/// The request can be aborted with the given handle.
+pub(crate) async fn fetch_async_with_handle(
+    backend: &Backend,
+    request: &Request,
+    handle: &RequestHandle,
+) -> crate::Result<Response> {
+    let Some(cache) = &backend.cache else {
+        return fetch_with_retries(request, handle).await;
+    };
+
+    match crate::cache::lookup(&**cache, request) {
+        crate::cache::Lookup::Fresh(response) => Ok(response),
+        crate::cache::Lookup::Send(conditional, pending) => {
+            let to_send = conditional.as_deref().unwrap_or(request);
+            let result = fetch_with_retries(to_send, handle).await;
+            pending.finish(&**cache, request, result)
+        }
+    }
 }
 
-/// This should only be used to handle opaque exceptions thrown by the `fetch` call.
-pub(crate) fn string_from_fetch_error(value: JsValue) -> String {
-    value.as_string().unwrap_or_else(|| {
-        // TypeError means that this is an opaque `network error`, as defined by the spec:
-        // https://fetch.spec.whatwg.org/
-        if value.has_type::<js_sys::TypeError>() {
-            web_sys::console::error_1(&value);
-            "Failed to fetch, check the developer console for details".to_owned()
-        } else {
-            format!("{value:#?}")
+/// Retries according to [`Request::retry`].
+async fn fetch_with_retries(request: &Request, handle: &RequestHandle) -> crate::Result<Response> {
+    let Some(retry) = &request.retry else {
+        return fetch_once(request, handle).await;
+    };
+
+    let mut request = request.clone();
+    retry.prepare(&mut request);
+
+    let mut attempt = 1;
+    loop {
+        let result = fetch_once(&request, handle).await;
+        let Some(delay) = retry.delay_before_retry(&request, attempt, &result) else {
+            return result;
+        };
+        sleep(delay).await;
+        if handle.is_aborted() {
+            return Err(Error::Aborted);
         }
+        attempt += 1;
+    }
+}
+
+async fn fetch_once(request: &Request, handle: &RequestHandle) -> crate::Result<Response> {
+    if request.upload_progress.is_some() {
+        return fetch_with_xhr(request, handle).await;
+    }
+
+    let (response, _abort_registration) = fetch_base(request, handle).await?;
+    let base = get_response_base(&response).map_err(error_from_fetch_error)?;
+
+    let bytes = if let Some(callback) = request.download_progress.clone() {
+        let total = crate::progress::expected_size(&base.headers);
+        let tracker = crate::progress::ProgressTracker::start(callback, total);
+        read_body_with_progress(&response, tracker).await?
+    } else {
+        let array_buffer = response.array_buffer().map_err(body_read_error)?;
+        let array_buffer = JsFuture::from(array_buffer)
+            .await
+            .map_err(body_read_error)?;
+        js_sys::Uint8Array::new(&array_buffer).to_vec()
+    };
+
+    Ok(Response {
+        url: base.url,
+        ok: base.ok,
+        status: base.status,
+        status_text: base.status_text,
+        bytes,
+        headers: base.headers,
+        redirects: base.redirects,
     })
 }
 
-pub(crate) async fn fetch_base(request: &Request) -> Result<web_sys::Response, JsValue> {
+/// Read the body chunk by chunk, reporting the progress after each chunk.
+async fn read_body_with_progress(
+    response: &web_sys::Response,
+    mut tracker: crate::progress::ProgressTracker,
+) -> crate::Result<Vec<u8>> {
+    let Some(body) = response.body() else {
+        return Ok(vec![]);
+    };
+    let reader = web_sys::ReadableStreamDefaultReader::new(&body).map_err(body_read_error)?;
+
+    let mut bytes = vec![];
+    loop {
+        let result: web_sys::ReadableStreamReadResult = JsFuture::from(reader.read())
+            .await
+            .map_err(body_read_error)?
+            .unchecked_into();
+        if result.get_done().unwrap_or(false) {
+            return Ok(bytes);
+        }
+        let chunk: js_sys::Uint8Array = result.get_value().unchecked_into();
+        let start = bytes.len();
+        bytes.resize(start + chunk.length() as usize, 0);
+        chunk.copy_to(&mut bytes[start..]);
+        tracker.set(bytes.len() as u64, None);
+    }
+}
+
+/// `fetch` can't report upload progress, so requests with [`Request::upload_progress`]
+/// are sent with `XMLHttpRequest` instead.
+async fn fetch_with_xhr(request: &Request, handle: &RequestHandle) -> crate::Result<Response> {
+    if handle.is_aborted() {
+        return Err(Error::Aborted);
+    }
+
+    let xhr = web_sys::XmlHttpRequest::new().map_err(error_from_fetch_error)?;
+    xhr.open_with_async(request.method.as_str(), &request.url, true)
+        .map_err(|err| match err.dyn_into::<web_sys::DomException>() {
+            Ok(err) if err.name() == "SyntaxError" => {
+                Error::InvalidUrl(format!("Invalid URL {:?}: {}", request.url, err.message()))
+            }
+            Ok(err) => error_from_fetch_error(err.into()),
+            Err(err) => error_from_fetch_error(err),
+        })?;
+    xhr.set_response_type(web_sys::XmlHttpRequestResponseType::Arraybuffer);
+    xhr.set_with_credentials(matches!(
+        request.credentials,
+        crate::types::Credentials::Include
+    ));
+    for (k, v) in &request.headers {
+        xhr.set_request_header(k, v)
+            .map_err(error_from_fetch_error)?;
+    }
+
+    // Aborts (and timeouts) go through an `AbortController`, just like for `fetch`.
+    // It is registered before reading the body, which may take a while:
+    let controller = web_sys::AbortController::new().map_err(error_from_fetch_error)?;
+    let signal = controller.signal();
+    let _abort_registration = AbortRegistration::new(handle, controller, request.timeout);
+
+    let body_bytes = request.body.to_bytes().await?;
+    if signal.aborted() {
+        return Err(error_from_fetch_error(signal.reason()));
+    }
+
+    let on_upload_progress = request.upload_progress.clone().map(|callback| {
+        let total = Some(body_bytes.len() as u64);
+        let mut tracker = crate::progress::ProgressTracker::start(callback, total);
+        Closure::<dyn FnMut(web_sys::ProgressEvent)>::new(move |event: web_sys::ProgressEvent| {
+            let total = event.length_computable().then(|| event.total() as u64);
+            tracker.set(event.loaded() as u64, total);
+        })
+    });
+    if let Some(on_upload_progress) = &on_upload_progress {
+        let upload = xhr.upload().map_err(error_from_fetch_error)?;
+        upload.set_onprogress(Some(on_upload_progress.as_ref().unchecked_ref()));
+    }
+
+    // The download progress starts once the response headers have arrived:
+    let on_download_progress = request.download_progress.clone().map(|callback| {
+        let xhr = xhr.clone();
+        let mut tracker = None;
+        Closure::<dyn FnMut(web_sys::ProgressEvent)>::new(move |event: web_sys::ProgressEvent| {
+            let tracker = tracker.get_or_insert_with(|| {
+                let mut headers = crate::Headers::default();
+                for (key, value) in response_headers(&xhr) {
+                    headers.insert(key, value);
+                }
+                let total = crate::progress::expected_size(&headers);
+                crate::progress::ProgressTracker::start(callback.clone(), total)
+            });
+            tracker.set(event.loaded() as u64, None);
+        })
+    });
+    if let Some(on_download_progress) = &on_download_progress {
+        xhr.set_onprogress(Some(on_download_progress.as_ref().unchecked_ref()));
+    }
+
+    let on_abort = {
+        let xhr = xhr.clone();
+        Closure::<dyn FnMut()>::new(move || {
+            let _ = xhr.abort();
+        })
+    };
+    signal.set_onabort(Some(on_abort.as_ref().unchecked_ref()));
+
+    let done = js_sys::Promise::new(&mut |resolve, reject| {
+        xhr.set_onload(Some(&resolve));
+        xhr.set_onerror(Some(&reject));
+        xhr.set_onabort(Some(&reject));
+        xhr.set_ontimeout(Some(&reject));
+    });
+    xhr.send_with_opt_u8_array((!body_bytes.is_empty()).then_some(body_bytes.as_ref()))
+        .map_err(error_from_fetch_error)?;
+    let result = JsFuture::from(done).await;
+
+    signal.set_onabort(None);
+    if let Some(upload) = on_upload_progress.as_ref().and_then(|_| xhr.upload().ok()) {
+        upload.set_onprogress(None);
+    }
+    xhr.set_onprogress(None);
+
+    if result.is_err() {
+        return Err(if signal.aborted() {
+            error_from_fetch_error(signal.reason())
+        } else {
+            Error::Network(
+                "XMLHttpRequest failed, check the developer console for details".to_owned(),
+            )
+        });
+    }
+
+    let mut headers = crate::Headers::default();
+    for (key, value) in response_headers(&xhr) {
+        headers.insert(key, value);
+    }
+    // Same order as the `Headers` of `fetch`:
+    headers.sort();
+
+    let body = xhr.response().map_err(body_read_error)?;
+    let bytes = if body.is_null() {
+        vec![]
+    } else {
+        js_sys::Uint8Array::new(&body).to_vec()
+    };
+
+    let status = xhr.status().map_err(error_from_fetch_error)?;
+    Ok(Response {
+        url: xhr.response_url(),
+        ok: (200..300).contains(&status),
+        status,
+        status_text: xhr.status_text().map_err(error_from_fetch_error)?,
+        bytes,
+        headers,
+        redirects: vec![],
+    })
+}
+
+/// The response headers of an `XMLHttpRequest`, as `(name, value)` pairs.
+fn response_headers(xhr: &web_sys::XmlHttpRequest) -> Vec<(String, String)> {
+    let all_headers = xhr.get_all_response_headers().unwrap_or_default();
+    all_headers
+        .split("\r\n")
+        .filter_map(|line| {
+            let (key, value) = line.split_once(": ")?;
+            Some((key.to_owned(), value.to_owned()))
+        })
+        .collect()
+}
+
+/// This should only be used to handle opaque exceptions thrown by the `fetch` call.
+pub(crate) fn error_from_fetch_error(value: JsValue) -> Error {
+    if let Some(string) = value.as_string() {
+        return Error::Other(string);
+    }
+
+    if let Some(exception) = value.dyn_ref::<web_sys::DomException>() {
+        match exception.name().as_str() {
+            "AbortError" => return Error::Aborted,
+            "TimeoutError" => return Error::Timeout(exception.message()),
+            _ => {}
+        }
+    }
+
+    // TypeError means that this is an opaque `network error`, as defined by the spec:
+    // https://fetch.spec.whatwg.org/
+    if value.has_type::<js_sys::TypeError>() {
+        web_sys::console::error_1(&value);
+        Error::Network("Failed to fetch, check the developer console for details".to_owned())
+    } else {
+        Error::Other(format!("{value:#?}"))
+    }
+}
+
+/// Used for exceptions thrown while reading the body of a response.
+pub(crate) fn body_read_error(value: JsValue) -> Error {
+    match error_from_fetch_error(value) {
+        Error::Network(msg) | Error::Other(msg) => {
+            Error::BodyRead(format!("Failed to read response body: {msg}"))
+        }
+        err => err,
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+thread_local! {
+    /// The `AbortController`s of all in-flight requests, keyed by [`RequestHandle::id`].
+    static ABORT_CONTROLLERS: RefCell<HashMap<u64, web_sys::AbortController>> = Default::default();
+}
+
+/// Aborts the request with the given id, if it is still in flight.
+pub(crate) fn abort(id: u64) {
+    let controller = ABORT_CONTROLLERS.with_borrow_mut(|controllers| controllers.remove(&id));
+    if let Some(controller) = controller {
+        controller.abort();
+    }
+}
+
+/// Makes the `AbortController` of a request reachable from its [`RequestHandle`],
+/// and aborts the request if it doesn't complete before [`Request::timeout`].
+///
+/// Keep this alive until the whole response body has been read.
+pub(crate) struct AbortRegistration {
+    id: u64,
+    timer: Option<(JsValue, Closure<dyn FnMut()>)>,
+}
+
+impl AbortRegistration {
+    fn new(
+        handle: &RequestHandle,
+        controller: web_sys::AbortController,
+        timeout: Option<std::time::Duration>,
+    ) -> Self {
+        let timer = timeout.map(|timeout| {
+            let controller = controller.clone();
+            let on_timeout = Closure::once(move || {
+                // Aborting with a `TimeoutError` makes `fetch` reject with it,
+                // so that we can tell timeouts apart from other aborts.
+                let message = format!("Request timed out after {timeout:?}");
+                match web_sys::DomException::new_with_message_and_name(&message, "TimeoutError") {
+                    Ok(reason) => controller.abort_with_reason(&reason),
+                    Err(_) => controller.abort(),
+                }
+            });
+            use std::convert::TryFrom as _;
+            let millis = i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX);
+            let timer_id = set_timeout(on_timeout.as_ref().unchecked_ref(), millis);
+            (timer_id, on_timeout)
+        });
+
+        let id = handle.id();
+        ABORT_CONTROLLERS.with_borrow_mut(|controllers| controllers.insert(id, controller));
+        Self { id, timer }
+    }
+}
+
+impl Drop for AbortRegistration {
+    fn drop(&mut self) {
+        if let Some((timer_id, _on_timeout)) = &self.timer {
+            clear_timeout(timer_id);
+        }
+        ABORT_CONTROLLERS.with_borrow_mut(|controllers| controllers.remove(&self.id));
+    }
+}
+
+pub(crate) async fn fetch_base(
+    request: &Request,
+    handle: &RequestHandle,
+) -> crate::Result<(web_sys::Response, AbortRegistration)> {
+    if handle.is_aborted() {
+        return Err(Error::Aborted);
+    }
+
+    let controller = web_sys::AbortController::new().map_err(error_from_fetch_error)?;
+    let signal = controller.signal();
+    // Before reading the body, which may take a while (then `fetch` rejects right away):
+    let abort_registration = AbortRegistration::new(handle, controller, request.timeout);
+
     let opts = web_sys::RequestInit::new();
+    opts.set_signal(Some(&signal));
     opts.set_method(request.method.as_str());
     opts.set_mode(request.mode.into());
     opts.set_credentials(request.credentials.into());
+    opts.set_redirect((&request.redirect_policy).into());
+
+    #[cfg(feature = "streaming")]
+    let send_as_stream = request.body.is_stream() && supports_request_streams();
+    #[cfg(not(feature = "streaming"))]
+    let send_as_stream = false;
 
-    if !request.body.is_empty() {
-        let body_bytes: &[u8] = &request.body;
-        let body_array: js_sys::Uint8Array = body_bytes.into();
-        let js_value: &JsValue = body_array.as_ref();
-        opts.set_body(js_value);
+    if send_as_stream {
+        #[cfg(feature = "streaming")]
+        {
+            use futures_util::StreamExt as _;
+
+            let stream = request.body.take_stream()?.map(|chunk| match chunk {
+                Ok(chunk) => Ok(js_sys::Uint8Array::from(chunk.as_slice()).into()),
+                Err(err) => Err(JsValue::from_str(&err.to_string())),
+            });
+            let stream = wasm_streams::ReadableStream::from_stream(stream).into_raw();
+            opts.set_body(&stream);
+            // Required for streaming request bodies:
+            js_sys::Reflect::set(&opts, &"duplex".into(), &"half".into())
+                .map_err(error_from_fetch_error)?;
+        }
+    } else {
+        let body_bytes = request.body.to_bytes().await?;
+        if !body_bytes.is_empty() {
+            let body_array: js_sys::Uint8Array = body_bytes.as_ref().into();
+            let js_value: &JsValue = body_array.as_ref();
+            opts.set_body(js_value);
+        }
     }
 
-    let js_request = web_sys::Request::new_with_str_and_init(&request.url, &opts)?;
+    let js_request =
+        web_sys::Request::new_with_str_and_init(&request.url, &opts).map_err(|err| {
+            // The `Request` constructor throws a `TypeError` if the URL can't be parsed.
+            match err.dyn_into::<js_sys::TypeError>() {
+                Ok(err) => Error::InvalidUrl(format!(
+                    "Invalid URL {:?}: {}",
+                    request.url,
+                    String::from(err.message())
+                )),
+                Err(err) => error_from_fetch_error(err),
+            }
+        })?;
 
     for (k, v) in &request.headers {
-        js_request.headers().set(k, v)?;
+        js_request
+            .headers()
+            .set(k, v)
+            .map_err(error_from_fetch_error)?;
+    }
+
+    let response = JsFuture::from(fetch_with_request(&js_request))
+        .await
+        .map_err(error_from_fetch_error)?;
+    let response: web_sys::Response = response.dyn_into().map_err(error_from_fetch_error)?;
+
+    Ok((response, abort_registration))
+}
+
+/// Can `fetch` send a `ReadableStream` as the request body?
+///
+/// This is the feature detection recommended by
+/// <https://developer.chrome.com/docs/capabilities/web-apis/fetch-streaming-requests>:
+/// browsers that don't support it turn the stream into the text `[object ReadableStream]`,
+/// and never look at the `duplex` option.
+#[cfg(feature = "streaming")]
+fn supports_request_streams() -> bool {
+    thread_local! {
+        static SUPPORTED: bool = detect_request_streams();
     }
 
-    let response = JsFuture::from(fetch_with_request(&js_request)).await?;
-    let response: web_sys::Response = response.dyn_into()?;
+    fn detect_request_streams() -> bool {
+        use js_sys::Reflect;
+        use std::{cell::Cell, rc::Rc};
+
+        let Ok(stream) = web_sys::ReadableStream::new() else {
+            return false;
+        };
+
+        let duplex_accessed = Rc::new(Cell::new(false));
+        let get_duplex = Closure::<dyn FnMut() -> JsValue>::new({
+            let duplex_accessed = duplex_accessed.clone();
+            move || {
+                duplex_accessed.set(true);
+                JsValue::from_str("half")
+            }
+        });
+        let duplex = js_sys::Object::new();
+        let init = js_sys::Object::new();
+        let init_ok = Reflect::set(&duplex, &"get".into(), get_duplex.as_ref()).is_ok()
+            && Reflect::set(&init, &"method".into(), &"POST".into()).is_ok()
+            && Reflect::set(&init, &"body".into(), &stream).is_ok();
+        if !init_ok {
+            return false;
+        }
+        js_sys::Object::define_property(&init, &"duplex".into(), &duplex);
+
+        let Ok(request) =
+            web_sys::Request::new_with_str_and_init("https://example.com", init.unchecked_ref())
+        else {
+            return false;
+        };
+        let has_content_type = request.headers().has("Content-Type").unwrap_or(true);
+        duplex_accessed.get() && !has_content_type
+    }
 
-    Ok(response)
+    SUPPORTED.with(|supported| *supported)
 }
 
 pub(crate) fn get_response_base(response: &web_sys::Response) -> Result<PartialResponse, JsValue> {
@@ -103,28 +534,18 @@ pub(crate) fn get_response_base(response: &web_sys::Response) -> Result<PartialR
         status: response.status(),
         status_text: response.status_text(),
         headers,
+        redirects: vec![],
     })
 }
 
-/// NOTE: `Ok(…)` is returned on network error.
-/// `Err` is only for failure to use the fetch API.
-async fn fetch_jsvalue(request: &Request) -> Result<Response, JsValue> {
-    let response = fetch_base(request).await?;
-
-    let array_buffer = JsFuture::from(response.array_buffer()?).await?;
-    let uint8_array = js_sys::Uint8Array::new(&array_buffer);
-    let bytes = uint8_array.to_vec();
-
-    let base = get_response_base(&response)?;
-
-    Ok(Response {
-        url: base.url,
-        ok: base.ok,
-        status: base.status,
-        status_text: base.status_text,
-        bytes,
-        headers: base.headers,
-    })
+/// Wait for the given duration without blocking.
+pub(crate) async fn sleep(duration: std::time::Duration) {
+    use std::convert::TryFrom as _;
+    let millis = i32::try_from(duration.as_millis()).unwrap_or(i32::MAX);
+    let promise = js_sys::Promise::new(&mut |resolve, _reject| {
+        set_timeout(&resolve, millis);
+    });
+    let _ = JsFuture::from(promise).await;
 }
 
 /// Spawn an async task.
@@ -140,9 +561,14 @@ where
 
 // ----------------------------------------------------------------------------
 
-pub(crate) fn fetch(request: Request, on_done: Box<dyn FnOnce(crate::Result<Response>) + Send>) {
+pub(crate) fn fetch(
+    backend: Backend,
+    request: Request,
+    handle: RequestHandle,
+    on_done: Box<dyn FnOnce(crate::Result<Response>) + Send>,
+) {
     spawn_future(async move {
-        let result = fetch_async(&request).await;
+        let result = fetch_async_with_handle(&backend, &request, &handle).await;
         on_done(result)
     });
 }
@@ -0,0 +1,177 @@
+//! WebSocket client for both native and WASM.
+//!
+//! Requires the `websocket` feature to be enabled.
+//!
+//! Example:
+//! ```
+//! use std::ops::ControlFlow;
+//!
+//! let sender = ehttp::ws::connect(
+//!     "wss://echo.websocket.org",
+//!     ehttp::ws::Options::default(),
+//!     move |event: ehttp::ws::WsEvent| {
+//!         match event {
+//!             ehttp::ws::WsEvent::Opened => println!("Connected"),
+//!             ehttp::ws::WsEvent::Message(message) => println!("Received {message:?}"),
+//!             ehttp::ws::WsEvent::Error(err) => eprintln!("Error: {err}"),
+//!             ehttp::ws::WsEvent::Closed { code, reason } => println!("Closed: {code} {reason}"),
+//!         }
+//!         ControlFlow::Continue(())
+//!     },
+//! );
+//!
+//! sender.send(ehttp::ws::WsMessage::Text("Hello!".to_owned()));
+//! ```
+//!
+//! Like with [`crate::fetch`], the callback is called on a background thread on native,
+//! and on the main thread on web.
+//! Return [`ControlFlow::Break`] from it to close the connection.
+//!
+//! On web this uses the browser [`WebSocket`](https://developer.mozilla.org/en-US/docs/Web/API/WebSocket),
+//! which answers pings by itself and never tells us about them.
+//! So [`WsMessage::Ping`] and [`WsMessage::Pong`] are only received on native,
+//! and sending them does nothing on web.
+//!
+//! On native, each connection runs on its own thread, which also answers pings with pongs.
+//! `wss://` URLs use TLS as configured with [`Options::with_tls`].
+//! The connection is always direct: [proxies](crate::Proxy) are not used for WebSockets.
+
+use std::ops::ControlFlow;
+
+use crate::Headers;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native;
+#[cfg(not(target_arch = "wasm32"))]
+pub use native::WsSender;
+
+#[cfg(target_arch = "wasm32")]
+mod web;
+#[cfg(target_arch = "wasm32")]
+pub use web::WsSender;
+
+/// A message sent or received over a WebSocket.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WsMessage {
+    /// A text message.
+    Text(String),
+
+    /// A binary message.
+    Binary(Vec<u8>),
+
+    /// A ping. Only received on native, where it is answered with a pong automatically.
+    Ping(Vec<u8>),
+
+    /// A pong, e.g. in response to a ping you sent. Only received on native.
+    Pong(Vec<u8>),
+}
+
+/// Something that happened to a WebSocket connection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WsEvent {
+    /// The connection was established. Always the first event, unless the connection fails.
+    Opened,
+
+    /// A message from the server.
+    Message(WsMessage),
+
+    /// Something went wrong. The connection is then closed with code `1006`.
+    Error(String),
+
+    /// The connection was closed. Always the last event.
+    Closed {
+        /// The [close code](https://developer.mozilla.org/en-US/docs/Web/API/CloseEvent/code),
+        /// e.g. `1000` for a normal closure,
+        /// `1005` if the server gave no code,
+        /// or `1006` if the connection was lost without a proper close.
+        code: u16,
+
+        /// Why the connection was closed, if the other side said so.
+        reason: String,
+    },
+}
+
+/// Options for [`connect`].
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// Extra headers for the opening handshake.
+    ///
+    /// Ignored on web, where the browser doesn't allow setting them.
+    pub headers: Headers,
+
+    /// The subprotocols to offer in the `Sec-WebSocket-Protocol` header, in order of preference.
+    pub protocols: Vec<String>,
+
+    /// Messages larger than this close the connection with code `1009`.
+    ///
+    /// Only applies to native. The default is 64 MiB.
+    pub max_message_size: usize,
+
+    /// TLS options for `wss://` URLs (native only).
+    ///
+    /// The default is [`crate::TlsConfig::default`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub tls: Option<crate::TlsConfig>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            headers: Headers::default(),
+            protocols: vec![],
+            max_message_size: 64 * 1024 * 1024,
+            #[cfg(not(target_arch = "wasm32"))]
+            tls: None,
+        }
+    }
+}
+
+impl Options {
+    /// Add a header to the opening handshake (native only).
+    pub fn with_header(mut self, key: impl ToString, value: impl ToString) -> Self {
+        self.headers.insert(key, value);
+        self
+    }
+
+    /// Offer a subprotocol.
+    pub fn with_protocol(mut self, protocol: impl ToString) -> Self {
+        self.protocols.push(protocol.to_string());
+        self
+    }
+
+    /// See [`Self::max_message_size`].
+    pub fn with_max_message_size(mut self, max_message_size: usize) -> Self {
+        self.max_message_size = max_message_size;
+        self
+    }
+
+    /// See [`Self::tls`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_tls(mut self, tls: crate::TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+}
+
+/// Connect to the WebSocket server at the given `ws://` or `wss://` URL,
+/// and call the given callback for everything that happens to the connection.
+///
+/// Use the returned [`WsSender`] to send messages (they are queued until the connection is open)
+/// and to close the connection.
+/// Dropping it also closes the connection.
+///
+/// If the connection can't be established, the callback is called with a [`WsEvent::Error`]
+/// followed by [`WsEvent::Closed`].
+pub fn connect(
+    url: impl ToString,
+    options: Options,
+    on_event: impl 'static + Send + Fn(WsEvent) -> ControlFlow<()>,
+) -> WsSender {
+    #[cfg(not(target_arch = "wasm32"))]
+    return native::connect(url.to_string(), options, Box::new(on_event));
+
+    #[cfg(target_arch = "wasm32")]
+    return web::connect(url.to_string(), &options, Box::new(on_event));
+}
+
+type OnEvent = Box<dyn Fn(WsEvent) -> ControlFlow<()> + Send>;
@@ -0,0 +1,907 @@
+use std::{
+    convert::{TryFrom as _, TryInto as _},
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs as _},
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use base64::Engine as _;
+
+use super::{OnEvent, Options, WsEvent, WsMessage};
+use crate::Headers;
+
+/// How long to wait for the server to accept the connection.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often to check for messages to send while waiting for the server.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How long to wait for the server to answer our close frame.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Control frames (ping, pong, close) can't have longer payloads than this.
+const MAX_CONTROL_PAYLOAD: usize = 125;
+
+/// Used to send messages over a WebSocket, or to close it.
+///
+/// Dropping it closes the connection.
+#[derive(Debug)]
+pub struct WsSender {
+    commands: mpsc::Sender<Command>,
+}
+
+impl WsSender {
+    /// Send a message. It is queued if the connection isn't open yet.
+    ///
+    /// The payload of a [`WsMessage::Ping`] or [`WsMessage::Pong`] is cut to 125 bytes.
+    pub fn send(&self, message: WsMessage) {
+        self.commands.send(Command::Send(message)).ok();
+    }
+
+    /// Close the connection with code `1000`, after sending any queued messages.
+    pub fn close(&self) {
+        self.close_with(1000, "");
+    }
+
+    /// Close the connection with the given code and reason, after sending any queued messages.
+    ///
+    /// The reason is cut to 123 bytes.
+    /// Browsers only allow the code `1000` or codes in the range `3000..=4999`.
+    pub fn close_with(&self, code: u16, reason: impl ToString) {
+        self.commands
+            .send(Command::Close(code, reason.to_string()))
+            .ok();
+    }
+}
+
+enum Command {
+    Send(WsMessage),
+    Close(u16, String),
+}
+
+pub(crate) fn connect(url: String, options: Options, on_event: OnEvent) -> WsSender {
+    let (commands, receiver) = mpsc::channel();
+    crate::executor::spawn_dedicated_thread(Box::new(move |spawned| {
+        let end = match spawned {
+            Ok(()) => connect_and_run(&url, &options, &receiver, &on_event),
+            Err(err) => End::Failed {
+                code: 1006,
+                error: err.to_string(),
+            },
+        };
+        report(end, &on_event);
+    }));
+    WsSender { commands }
+}
+
+/// Why the connection ended.
+#[derive(Debug)]
+enum End {
+    /// Closed, properly or not.
+    Closed { code: u16, reason: String },
+
+    /// Something went wrong.
+    Failed { code: u16, error: String },
+
+    /// The callback returned [`std::ops::ControlFlow::Break`], so it doesn't want to hear about the close.
+    Stopped,
+}
+
+impl From<std::io::Error> for End {
+    fn from(err: std::io::Error) -> Self {
+        Self::Failed {
+            code: 1006,
+            error: err.to_string(),
+        }
+    }
+}
+
+fn protocol_error(error: impl ToString) -> End {
+    End::Failed {
+        code: 1002,
+        error: error.to_string(),
+    }
+}
+
+fn connect_and_run(
+    url: &str,
+    options: &Options,
+    commands: &mpsc::Receiver<Command>,
+    on_event: &OnEvent,
+) -> End {
+    match handshake(url, options) {
+        Ok(stream) => {
+            let mut connection = Connection {
+                stream,
+                on_event,
+                max_message_size: options.max_message_size,
+                incoming: vec![],
+                fragmented: None,
+                close_sent: None,
+            };
+            let end = connection.run(commands);
+            connection.finish(&end);
+            end
+        }
+        Err(error) => End::Failed { code: 1006, error },
+    }
+}
+
+/// Tell the callback how the connection ended.
+fn report(end: End, on_event: &OnEvent) {
+    match end {
+        End::Closed { code, reason } => {
+            let _ = on_event(WsEvent::Closed { code, reason });
+        }
+        End::Failed { code, error } => {
+            if on_event(WsEvent::Error(error)).is_continue() {
+                let _ = on_event(WsEvent::Closed {
+                    code,
+                    reason: String::new(),
+                });
+            }
+        }
+        End::Stopped => {}
+    }
+}
+
+struct Connection<'a> {
+    stream: Stream,
+    on_event: &'a OnEvent,
+    max_message_size: usize,
+
+    /// Received bytes that aren't a complete frame yet.
+    incoming: Vec<u8>,
+
+    /// The opcode and payload so far of a message that is split into several frames.
+    fragmented: Option<(u8, Vec<u8>)>,
+
+    /// When we sent a close frame, if we did.
+    close_sent: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+mod opcode {
+    pub const CONTINUATION: u8 = 0x0;
+    pub const TEXT: u8 = 0x1;
+    pub const BINARY: u8 = 0x2;
+    pub const CLOSE: u8 = 0x8;
+    pub const PING: u8 = 0x9;
+    pub const PONG: u8 = 0xA;
+}
+
+impl Connection<'_> {
+    fn run(&mut self, commands: &mpsc::Receiver<Command>) -> End {
+        match self.run_until_closed(commands) {
+            Ok(never) => match never {},
+            Err(end) => end,
+        }
+    }
+
+    fn run_until_closed(
+        &mut self,
+        commands: &mpsc::Receiver<Command>,
+    ) -> Result<std::convert::Infallible, End> {
+        self.emit(WsEvent::Opened)?;
+        self.stream.tcp().set_read_timeout(Some(POLL_INTERVAL))?;
+
+        let mut buffer = vec![0; 16 * 1024];
+        loop {
+            loop {
+                match commands.try_recv() {
+                    Ok(Command::Send(message)) => self.send(message)?,
+                    Ok(Command::Close(code, reason)) => self.send_close(code, &reason)?,
+                    Err(mpsc::TryRecvError::Empty) => break,
+                    Err(mpsc::TryRecvError::Disconnected) => {
+                        self.send_close(1000, "")?;
+                        break;
+                    }
+                }
+            }
+
+            if let Some(close_sent) = self.close_sent {
+                if close_sent.elapsed() > CLOSE_TIMEOUT {
+                    return Err(End::Closed {
+                        code: 1006,
+                        reason: String::new(),
+                    });
+                }
+            }
+
+            match self.stream.read(&mut buffer) {
+                Ok(0) => {
+                    return Err(End::Closed {
+                        code: 1006,
+                        reason: String::new(),
+                    });
+                }
+                Ok(n) => self.incoming.extend_from_slice(&buffer[..n]),
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        std::io::ErrorKind::WouldBlock
+                            | std::io::ErrorKind::TimedOut
+                            | std::io::ErrorKind::Interrupted
+                    ) => {}
+                Err(err) => return Err(err.into()),
+            }
+
+            while let Some(frame) = self.next_frame()? {
+                self.on_frame(frame)?;
+            }
+        }
+    }
+
+    /// Tell the server why we are leaving, if we haven't yet.
+    fn finish(&mut self, end: &End) {
+        let code = match end {
+            End::Failed { code, .. } if *code != 1006 => *code,
+            End::Stopped => 1000,
+            _ => return,
+        };
+        self.send_close(code, "").ok();
+    }
+
+    fn emit(&self, event: WsEvent) -> Result<(), End> {
+        if (self.on_event)(event).is_break() {
+            Err(End::Stopped)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn send(&mut self, message: WsMessage) -> Result<(), End> {
+        if self.close_sent.is_some() {
+            return Ok(()); // Too late
+        }
+        match message {
+            WsMessage::Text(text) => self.write_frame(opcode::TEXT, text.as_bytes()),
+            WsMessage::Binary(data) => self.write_frame(opcode::BINARY, &data),
+            WsMessage::Ping(data) => {
+                self.write_frame(opcode::PING, &data[..data.len().min(MAX_CONTROL_PAYLOAD)])
+            }
+            WsMessage::Pong(data) => {
+                self.write_frame(opcode::PONG, &data[..data.len().min(MAX_CONTROL_PAYLOAD)])
+            }
+        }
+    }
+
+    fn send_close(&mut self, code: u16, reason: &str) -> Result<(), End> {
+        if self.close_sent.is_some() {
+            return Ok(());
+        }
+        let mut reason_len = reason.len().min(MAX_CONTROL_PAYLOAD - 2);
+        while !reason.is_char_boundary(reason_len) {
+            reason_len -= 1;
+        }
+        let mut payload = code.to_be_bytes().to_vec();
+        payload.extend_from_slice(&reason.as_bytes()[..reason_len]);
+        self.write_frame(opcode::CLOSE, &payload)?;
+        self.close_sent = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Clients must mask all frames they send.
+    fn write_frame(&mut self, opcode: u8, payload: &[u8]) -> Result<(), End> {
+        let frame = encode_frame(opcode, payload, random()?);
+        self.stream.write_all(&frame)?;
+        self.stream.flush()?;
+        Ok(())
+    }
+
+    /// Take the next complete frame out of [`Self::incoming`].
+    fn next_frame(&mut self) -> Result<Option<Frame>, End> {
+        decode_frame(&mut self.incoming, self.max_message_size)
+    }
+
+    fn on_frame(&mut self, frame: Frame) -> Result<(), End> {
+        let Frame {
+            fin,
+            opcode,
+            payload,
+        } = frame;
+
+        if opcode >= opcode::CLOSE && (!fin || payload.len() > MAX_CONTROL_PAYLOAD) {
+            return Err(protocol_error("Received an invalid control frame"));
+        }
+
+        match opcode {
+            opcode::CONTINUATION => {
+                let Some((_, data)) = &mut self.fragmented else {
+                    return Err(protocol_error("Received an unexpected continuation frame"));
+                };
+                if data.len() + payload.len() > self.max_message_size {
+                    return Err(too_big(self.max_message_size));
+                }
+                data.extend_from_slice(&payload);
+                if fin {
+                    if let Some((opcode, data)) = self.fragmented.take() {
+                        self.on_message(opcode, data)?;
+                    }
+                }
+                Ok(())
+            }
+            opcode::TEXT | opcode::BINARY => {
+                if self.fragmented.is_some() {
+                    return Err(protocol_error(
+                        "Received a new message before the previous one was finished",
+                    ));
+                }
+                if fin {
+                    self.on_message(opcode, payload)
+                } else {
+                    self.fragmented = Some((opcode, payload));
+                    Ok(())
+                }
+            }
+            opcode::CLOSE => {
+                let (code, reason) = if payload.len() >= 2 {
+                    (
+                        u16::from_be_bytes([payload[0], payload[1]]),
+                        String::from_utf8_lossy(&payload[2..]).into_owned(),
+                    )
+                } else {
+                    (1005, String::new())
+                };
+                if self.close_sent.is_none() {
+                    // Echo the code back, as the protocol requires:
+                    self.write_frame(opcode::CLOSE, &payload[..payload.len().min(2)])
+                        .ok();
+                    self.close_sent = Some(Instant::now());
+                }
+                Err(End::Closed { code, reason })
+            }
+            opcode::PING => {
+                self.write_frame(opcode::PONG, &payload)?;
+                self.emit(WsEvent::Message(WsMessage::Ping(payload)))
+            }
+            opcode::PONG => self.emit(WsEvent::Message(WsMessage::Pong(payload))),
+            _ => Err(protocol_error(format!(
+                "Received a frame with unknown opcode {opcode:#x}"
+            ))),
+        }
+    }
+
+    fn on_message(&mut self, opcode: u8, data: Vec<u8>) -> Result<(), End> {
+        let message = if opcode == opcode::TEXT {
+            match String::from_utf8(data) {
+                Ok(text) => WsMessage::Text(text),
+                Err(_) => {
+                    return Err(End::Failed {
+                        code: 1007,
+                        error: "Received a text message that is not valid UTF-8".to_owned(),
+                    });
+                }
+            }
+        } else {
+            WsMessage::Binary(data)
+        };
+        self.emit(WsEvent::Message(message))
+    }
+}
+
+/// A single, final frame with the given mask applied to the payload.
+fn encode_frame(opcode: u8, payload: &[u8], mask: [u8; 4]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 14);
+    frame.push(0x80 | opcode); // FIN
+    if payload.len() < 126 {
+        frame.push(0x80 | payload.len() as u8);
+    } else if let Ok(len) = u16::try_from(payload.len()) {
+        frame.push(0x80 | 126);
+        frame.extend_from_slice(&len.to_be_bytes());
+    } else {
+        frame.push(0x80 | 127);
+        frame.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+    }
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+    frame
+}
+
+/// Take the next complete frame out of `buf`, if there is one.
+fn decode_frame(buf: &mut Vec<u8>, max_message_size: usize) -> Result<Option<Frame>, End> {
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+    if buf[0] & 0x70 != 0 {
+        return Err(protocol_error("Received a frame with reserved bits set"));
+    }
+    let fin = buf[0] & 0x80 != 0;
+    let opcode = buf[0] & 0x0f;
+    let masked = buf[1] & 0x80 != 0;
+
+    let (len, mut pos) = match buf[1] & 0x7f {
+        126 if buf.len() < 4 => return Ok(None),
+        126 => (u64::from(u16::from_be_bytes([buf[2], buf[3]])), 4),
+        127 if buf.len() < 10 => return Ok(None),
+        127 => (u64::from_be_bytes(buf[2..10].try_into().unwrap()), 10),
+        len => (u64::from(len), 2),
+    };
+    if len > max_message_size as u64 {
+        return Err(too_big(max_message_size));
+    }
+    let len = len as usize;
+
+    let mask = if masked {
+        // Servers aren't supposed to mask their frames, but we may as well understand them.
+        if buf.len() < pos + 4 {
+            return Ok(None);
+        }
+        pos += 4;
+        Some([buf[pos - 4], buf[pos - 3], buf[pos - 2], buf[pos - 1]])
+    } else {
+        None
+    };
+
+    if buf.len() < pos + len {
+        return Ok(None);
+    }
+    let mut payload = buf[pos..pos + len].to_vec();
+    if let Some(mask) = mask {
+        for (i, b) in payload.iter_mut().enumerate() {
+            *b ^= mask[i % 4];
+        }
+    }
+    buf.drain(..pos + len);
+
+    Ok(Some(Frame {
+        fin,
+        opcode,
+        payload,
+    }))
+}
+
+/// Frame masks and the `Sec-WebSocket-Key` must be unpredictable, so they come from the OS.
+fn random<const N: usize>() -> std::io::Result<[u8; N]> {
+    use ring::rand::SecureRandom as _;
+    let mut bytes = [0; N];
+    ring::rand::SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| std::io::Error::other("Failed to get random bytes from the OS"))?;
+    Ok(bytes)
+}
+
+fn too_big(max_message_size: usize) -> End {
+    End::Failed {
+        code: 1009,
+        error: format!("Received a message larger than the maximum of {max_message_size} bytes"),
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+enum Stream {
+    Plain(TcpStream),
+    Tls(Box<rustls::StreamOwned<rustls::ClientConnection, TcpStream>>),
+}
+
+impl Stream {
+    fn tcp(&self) -> &TcpStream {
+        match self {
+            Self::Plain(stream) => stream,
+            Self::Tls(stream) => stream.get_ref(),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Plain(stream) => stream.read(buf),
+            Self::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Plain(stream) => stream.write(buf),
+            Self::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Plain(stream) => stream.flush(),
+            Self::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+/// Connect, and upgrade the connection to a WebSocket.
+fn handshake(url: &str, options: &Options) -> Result<Stream, String> {
+    let uri: ureq::http::Uri = url
+        .parse()
+        .map_err(|err| format!("Invalid URL {url:?}: {err}"))?;
+    let tls = match uri.scheme_str() {
+        Some("ws") => false,
+        Some("wss") => true,
+        _ => return Err(format!("Expected a ws:// or wss:// URL, got {url:?}")),
+    };
+    let host = uri
+        .host()
+        .ok_or_else(|| format!("Invalid URL {url:?}: missing host"))?;
+    let port = uri.port_u16().unwrap_or(if tls { 443 } else { 80 });
+    let path = uri.path_and_query().map_or("/", |path| path.as_str());
+    let bare_host = host.trim_start_matches('[').trim_end_matches(']'); // IPv6
+
+    // No proxies here, see the module docs:
+    let tcp = connect_tcp(bare_host, port)
+        .map_err(|err| format!("Failed to connect to {host}:{port}: {err}"))?;
+    tcp.set_nodelay(true).ok();
+    tcp.set_read_timeout(Some(HANDSHAKE_TIMEOUT))
+        .and_then(|()| tcp.set_write_timeout(Some(HANDSHAKE_TIMEOUT)))
+        .map_err(|err| err.to_string())?;
+
+    let mut stream = if tls {
+        let server_name = rustls::pki_types::ServerName::try_from(bare_host.to_owned())
+            .map_err(|err| format!("Invalid host name {host:?}: {err}"))?;
+        let config = crate::tls::client_config(options.tls.as_ref().unwrap_or(&Default::default()))
+            .map_err(|err| err.to_string())?;
+        let connection = rustls::ClientConnection::new(config, server_name)
+            .map_err(|err| format!("TLS error: {err}"))?;
+        Stream::Tls(Box::new(rustls::StreamOwned::new(connection, tcp)))
+    } else {
+        Stream::Plain(tcp)
+    };
+
+    let key: [u8; 16] = random().map_err(|err| err.to_string())?;
+    let key = base64::engine::general_purpose::STANDARD.encode(key);
+
+    let host_header = if uri.port().is_some() {
+        format!("{host}:{port}")
+    } else {
+        host.to_owned()
+    };
+    let mut request = format!(
+        "GET {path} HTTP/1.1\r\n\
+         Host: {host_header}\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Key: {key}\r\n\
+         Sec-WebSocket-Version: 13\r\n"
+    );
+    if !options.protocols.is_empty() {
+        request += &format!(
+            "Sec-WebSocket-Protocol: {}\r\n",
+            options.protocols.join(", ")
+        );
+    }
+    for (key, value) in &options.headers.headers {
+        request += &format!("{key}: {value}\r\n");
+    }
+    request += "\r\n";
+
+    let map_io_err = |err: std::io::Error| format!("Failed to connect to {url}: {err}");
+    stream.write_all(request.as_bytes()).map_err(map_io_err)?;
+    stream.flush().map_err(map_io_err)?;
+
+    let head = read_response_head(&mut stream).map_err(map_io_err)?;
+    let mut lines = head.split("\r\n");
+    let status_line = lines.next().unwrap_or_default();
+    if status_line.split(' ').nth(1) != Some("101") {
+        return Err(format!(
+            "Expected {url} to switch protocols, but got {status_line:?}"
+        ));
+    }
+    let mut headers = Headers::default();
+    for line in lines {
+        if let Some((key, value)) = line.split_once(':') {
+            headers.insert(key.trim(), value.trim());
+        }
+    }
+    if headers.get("sec-websocket-accept") != Some(&accept_key(&key)) {
+        return Err(format!("{url} sent an invalid Sec-WebSocket-Accept header"));
+    }
+
+    Ok(stream)
+}
+
+/// Read up to and including the empty line at the end of the headers, but not any further.
+fn read_response_head(stream: &mut Stream) -> std::io::Result<String> {
+    let mut head = vec![];
+    let mut byte = [0_u8];
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() > 64 * 1024 {
+            return Err(std::io::Error::other("response headers too large"));
+        }
+        stream.read_exact(&mut byte)?;
+        head.push(byte[0]);
+    }
+    head.truncate(head.len() - 4);
+    Ok(String::from_utf8_lossy(&head).into_owned())
+}
+
+/// What the server must answer to the given `Sec-WebSocket-Key`.
+fn accept_key(key: &str) -> String {
+    const GUID: &str = "258EAFA5-E914-47A5-95CA-C5AB0DC85B11";
+    let hash = ring::digest::digest(
+        &ring::digest::SHA1_FOR_LEGACY_USE_ONLY,
+        format!("{key}{GUID}").as_bytes(),
+    );
+    base64::engine::general_purpose::STANDARD.encode(hash)
+}
+
+/// Connect to the first address of the host that answers within [`HANDSHAKE_TIMEOUT`].
+fn connect_tcp(host: &str, port: u16) -> std::io::Result<TcpStream> {
+    let mut last_err = None;
+    for address in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, HANDSHAKE_TIMEOUT) {
+            Ok(tcp) => return Ok(tcp),
+            Err(err) => last_err = Some(err),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::NotFound, "The host has no addresses")
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::TcpListener,
+        ops::ControlFlow,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+
+    const MAX_MESSAGE_SIZE: usize = 1024;
+
+    /// An unmasked frame, like a server sends.
+    fn server_frame(fin: bool, opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![if fin { 0x80 | opcode } else { opcode }];
+        if payload.len() < 126 {
+            frame.push(payload.len() as u8);
+        } else {
+            frame.push(126);
+            frame.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        }
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    fn failure_code(result: Result<(), End>) -> Option<u16> {
+        match result {
+            Err(End::Failed { code, .. }) => Some(code),
+            _ => None,
+        }
+    }
+
+    /// Run `f` with a connection to a fake server, and return the events it emitted.
+    fn with_connection(f: impl FnOnce(&mut Connection<'_>, &mut TcpStream)) -> Vec<WsEvent> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut server, _) = listener.accept().unwrap();
+        server
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        let events = Arc::new(Mutex::new(vec![]));
+        let on_event: OnEvent = Box::new({
+            let events = events.clone();
+            move |event| {
+                events.lock().unwrap().push(event);
+                ControlFlow::Continue(())
+            }
+        });
+        let mut connection = Connection {
+            stream: Stream::Plain(client),
+            on_event: &on_event,
+            max_message_size: MAX_MESSAGE_SIZE,
+            incoming: vec![],
+            fragmented: None,
+            close_sent: None,
+        };
+        f(&mut connection, &mut server);
+
+        let events = events.lock().unwrap().clone();
+        events
+    }
+
+    /// Pretend the server sent `bytes`.
+    fn receive(connection: &mut Connection<'_>, bytes: &[u8]) -> Result<(), End> {
+        connection.incoming.extend_from_slice(bytes);
+        while let Some(frame) = connection.next_frame()? {
+            connection.on_frame(frame)?;
+        }
+        Ok(())
+    }
+
+    /// Read the next frame the client sent.
+    fn read_frame(server: &mut TcpStream) -> Frame {
+        let mut buf = vec![];
+        loop {
+            if let Some(frame) = decode_frame(&mut buf, MAX_MESSAGE_SIZE).unwrap() {
+                return frame;
+            }
+            let mut byte = [0];
+            server.read_exact(&mut byte).unwrap();
+            buf.push(byte[0]);
+        }
+    }
+
+    #[test]
+    fn wss_uses_the_tls_config() {
+        let url = |address: std::net::SocketAddr| format!("wss://localhost:{}/", address.port());
+        let response = "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n";
+
+        // Self-signed for another host:
+        let address = crate::tls::tests::tls_server(response);
+        let error = handshake(&url(address), &Options::default()).err().unwrap();
+        assert!(error.contains("certificate"), "{:?}", error);
+
+        let address = crate::tls::tests::tls_server(response);
+        let options = Options::default()
+            .with_tls(crate::TlsConfig::new().danger_accept_invalid_certificates());
+        let error = handshake(&url(address), &options).err().unwrap();
+        assert!(error.contains("404 Not Found"), "{:?}", error);
+    }
+
+    #[test]
+    fn encode_and_decode() {
+        for len in [0, 1, 125, 126, 65535, 65536] {
+            let payload: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let mut buf = encode_frame(opcode::BINARY, &payload, [1, 2, 3, 4]);
+
+            assert_eq!(buf[0], 0x80 | opcode::BINARY);
+            let header_len = match len {
+                0..=125 => {
+                    assert_eq!(usize::from(buf[1]), 0x80 | len);
+                    6
+                }
+                126..=65535 => {
+                    assert_eq!(buf[1], 0x80 | 126);
+                    8
+                }
+                _ => {
+                    assert_eq!(buf[1], 0x80 | 127);
+                    14
+                }
+            };
+            assert_eq!(buf.len(), header_len + len);
+            assert_eq!(&buf[header_len - 4..header_len], &[1, 2, 3, 4]);
+            if len > 0 {
+                assert_ne!(&buf[header_len..], &payload[..], "payload should be masked");
+            }
+
+            let frame = decode_frame(&mut buf, usize::MAX).unwrap().unwrap();
+            assert!(frame.fin);
+            assert_eq!(frame.opcode, opcode::BINARY);
+            assert_eq!(frame.payload, payload);
+            assert!(buf.is_empty());
+        }
+    }
+
+    #[test]
+    fn decode_waits_for_the_whole_frame() {
+        let frame = server_frame(true, opcode::TEXT, &[b'x'; 300]);
+        let mut buf = vec![];
+        for &byte in &frame[..frame.len() - 1] {
+            buf.push(byte);
+            assert!(decode_frame(&mut buf, MAX_MESSAGE_SIZE).unwrap().is_none());
+        }
+        buf.push(frame[frame.len() - 1]);
+        buf.extend_from_slice(&server_frame(true, opcode::PING, b""));
+
+        let first = decode_frame(&mut buf, MAX_MESSAGE_SIZE).unwrap().unwrap();
+        assert_eq!(first.payload.len(), 300);
+        let second = decode_frame(&mut buf, MAX_MESSAGE_SIZE).unwrap().unwrap();
+        assert_eq!(second.opcode, opcode::PING);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn decode_rejects_bad_frames() {
+        let mut reserved_bits = vec![0x80 | 0x40 | opcode::TEXT, 0];
+        assert!(matches!(
+            decode_frame(&mut reserved_bits, MAX_MESSAGE_SIZE),
+            Err(End::Failed { code: 1002, .. })
+        ));
+
+        // Rejected as soon as the length is known:
+        let mut too_big = vec![0x80 | opcode::BINARY, 126, 0x07, 0xd0];
+        assert!(matches!(
+            decode_frame(&mut too_big, MAX_MESSAGE_SIZE),
+            Err(End::Failed { code: 1009, .. })
+        ));
+    }
+
+    #[test]
+    fn fragmented_message_with_interleaved_ping() {
+        let events = with_connection(|connection, server| {
+            receive(connection, &server_frame(false, opcode::TEXT, b"Hel")).unwrap();
+            receive(connection, &server_frame(false, opcode::CONTINUATION, b"l")).unwrap();
+            receive(connection, &server_frame(true, opcode::PING, b"?")).unwrap();
+            receive(connection, &server_frame(true, opcode::CONTINUATION, b"o")).unwrap();
+
+            let pong = read_frame(server);
+            assert!(pong.fin);
+            assert_eq!(pong.opcode, opcode::PONG);
+            assert_eq!(pong.payload, b"?");
+        });
+        assert_eq!(
+            events,
+            vec![
+                WsEvent::Message(WsMessage::Ping(b"?".to_vec())),
+                WsEvent::Message(WsMessage::Text("Hello".to_owned())),
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_fragmentation() {
+        with_connection(|connection, _| {
+            let result = receive(connection, &server_frame(true, opcode::CONTINUATION, b"x"));
+            assert_eq!(failure_code(result), Some(1002));
+        });
+        with_connection(|connection, _| {
+            receive(connection, &server_frame(false, opcode::BINARY, b"x")).unwrap();
+            let result = receive(connection, &server_frame(true, opcode::BINARY, b"y"));
+            assert_eq!(failure_code(result), Some(1002));
+        });
+        with_connection(|connection, _| {
+            let half = vec![0; MAX_MESSAGE_SIZE / 2 + 1];
+            receive(connection, &server_frame(false, opcode::BINARY, &half)).unwrap();
+            let result = receive(connection, &server_frame(true, opcode::CONTINUATION, &half));
+            assert_eq!(failure_code(result), Some(1009));
+        });
+        with_connection(|connection, _| {
+            let result = receive(connection, &server_frame(true, opcode::TEXT, &[0xff]));
+            assert_eq!(failure_code(result), Some(1007));
+        });
+    }
+
+    #[test]
+    fn bad_control_frames() {
+        with_connection(|connection, _| {
+            let result = receive(connection, &server_frame(false, opcode::PING, b""));
+            assert_eq!(failure_code(result), Some(1002));
+        });
+        with_connection(|connection, _| {
+            let result = receive(connection, &server_frame(true, opcode::PING, &[0; 126]));
+            assert_eq!(failure_code(result), Some(1002));
+        });
+        with_connection(|connection, _| {
+            let result = receive(connection, &server_frame(true, 0x3, b""));
+            assert_eq!(failure_code(result), Some(1002));
+        });
+    }
+
+    #[test]
+    fn close_is_echoed() {
+        with_connection(|connection, server| {
+            let result = receive(
+                connection,
+                &server_frame(true, opcode::CLOSE, b"\x03\xe9bye"),
+            );
+            assert!(matches!(
+                result,
+                Err(End::Closed { code: 1001, reason }) if reason == "bye"
+            ));
+
+            let echo = read_frame(server);
+            assert_eq!(echo.opcode, opcode::CLOSE);
+            assert_eq!(echo.payload, b"\x03\xe9");
+        });
+        with_connection(|connection, _| {
+            let result = receive(connection, &server_frame(true, opcode::CLOSE, b""));
+            assert!(matches!(result, Err(End::Closed { code: 1005, .. })));
+        });
+    }
+
+    #[test]
+    fn masks_are_random() {
+        let a: [u8; 16] = random().unwrap();
+        let b: [u8; 16] = random().unwrap();
+        assert_ne!(a, b);
+    }
+}
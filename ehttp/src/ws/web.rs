@@ -0,0 +1,243 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use wasm_bindgen::prelude::*;
+
+use super::{OnEvent, Options, WsEvent, WsMessage};
+
+/// Used to send messages over a WebSocket, or to close it.
+///
+/// Dropping it closes the connection, without waiting for the [`WsEvent::Closed`] event.
+#[derive(Debug)]
+pub struct WsSender {
+    connection: Option<Connection>,
+}
+
+impl WsSender {
+    /// Send a message. It is queued if the connection isn't open yet.
+    ///
+    /// [`WsMessage::Ping`] and [`WsMessage::Pong`] are ignored, since browsers don't support sending them.
+    pub fn send(&self, message: WsMessage) {
+        if let Some(connection) = &self.connection {
+            connection.shared.send(message);
+        }
+    }
+
+    /// Close the connection with code `1000`, after sending any queued messages.
+    pub fn close(&self) {
+        self.close_with(1000, "");
+    }
+
+    /// Close the connection with the given code and reason, after sending any queued messages.
+    ///
+    /// The reason is cut to 123 bytes.
+    /// Browsers only allow the code `1000` or codes in the range `3000..=4999`.
+    pub fn close_with(&self, code: u16, reason: impl ToString) {
+        if let Some(connection) = &self.connection {
+            let mut reason = reason.to_string();
+            let mut reason_len = reason.len().min(123);
+            while !reason.is_char_boundary(reason_len) {
+                reason_len -= 1;
+            }
+            reason.truncate(reason_len);
+            connection.shared.close(code, &reason);
+        }
+    }
+}
+
+pub(crate) fn connect(url: String, options: &Options, on_event: OnEvent) -> WsSender {
+    let socket = if options.protocols.is_empty() {
+        web_sys::WebSocket::new(&url)
+    } else {
+        let protocols: js_sys::Array = options.protocols.iter().map(JsValue::from).collect();
+        web_sys::WebSocket::new_with_str_sequence(&url, &protocols)
+    };
+
+    match socket {
+        Ok(socket) => WsSender {
+            connection: Some(Connection::new(socket, on_event)),
+        },
+        Err(err) => {
+            // Report the error like any other, after `connect` has returned:
+            let error = match err.dyn_ref::<web_sys::DomException>() {
+                Some(exception) => exception.message(),
+                None => format!("{err:?}"),
+            };
+            crate::web::spawn_future(async move {
+                if on_event(WsEvent::Error(error)).is_continue() {
+                    let _ = on_event(WsEvent::Closed {
+                        code: 1006,
+                        reason: String::new(),
+                    });
+                }
+            });
+            WsSender { connection: None }
+        }
+    }
+}
+
+struct Shared {
+    socket: web_sys::WebSocket,
+    on_event: OnEvent,
+
+    /// Messages and a close sent before the connection was open. `None` once it is open.
+    queue: RefCell<Option<Queued>>,
+
+    /// Set once the callback should no longer be called.
+    done: Cell<bool>,
+}
+
+#[derive(Default)]
+struct Queued {
+    messages: Vec<WsMessage>,
+    close: Option<(u16, String)>,
+}
+
+impl Shared {
+    fn emit(&self, event: WsEvent) {
+        if self.done.get() {
+            return;
+        }
+        if matches!(event, WsEvent::Closed { .. }) {
+            self.done.set(true);
+        }
+        if (self.on_event)(event).is_break() {
+            self.done.set(true);
+            self.socket.close().ok();
+        }
+    }
+
+    fn send(&self, message: WsMessage) {
+        if let Some(queued) = self.queue.borrow_mut().as_mut() {
+            queued.messages.push(message);
+            return;
+        }
+        // Sending only fails while connecting, which the queue takes care of:
+        match message {
+            WsMessage::Text(text) => self.socket.send_with_str(&text).ok(),
+            WsMessage::Binary(data) => self.socket.send_with_u8_array(&data).ok(),
+            WsMessage::Ping(_) | WsMessage::Pong(_) => None,
+        };
+    }
+
+    fn close(&self, code: u16, reason: &str) {
+        if let Some(queued) = self.queue.borrow_mut().as_mut() {
+            queued
+                .close
+                .get_or_insert_with(|| (code, reason.to_owned()));
+            return;
+        }
+        if let Err(err) = self.socket.close_with_code_and_reason(code, reason) {
+            // An invalid code or reason:
+            web_sys::console::error_1(&err);
+            self.socket.close().ok();
+        }
+    }
+
+    /// Send what was queued while connecting.
+    fn flush(&self) {
+        let Some(queued) = self.queue.borrow_mut().take() else {
+            return;
+        };
+        for message in queued.messages {
+            self.send(message);
+        }
+        if let Some((code, reason)) = queued.close {
+            self.close(code, &reason);
+        }
+    }
+}
+
+/// Owns the event handlers of the `WebSocket`, which must live as long as it might call them.
+struct Connection {
+    shared: Rc<Shared>,
+    _on_open: Closure<dyn FnMut()>,
+    _on_message: Closure<dyn FnMut(web_sys::MessageEvent)>,
+    _on_error: Closure<dyn FnMut()>,
+    _on_close: Closure<dyn FnMut(web_sys::CloseEvent)>,
+}
+
+impl Connection {
+    fn new(socket: web_sys::WebSocket, on_event: OnEvent) -> Self {
+        socket.set_binary_type(web_sys::BinaryType::Arraybuffer);
+        let shared = Rc::new(Shared {
+            socket,
+            on_event,
+            queue: RefCell::new(Some(Queued::default())),
+            done: Cell::new(false),
+        });
+
+        let on_open = Closure::<dyn FnMut()>::new({
+            let shared = shared.clone();
+            move || {
+                shared.emit(WsEvent::Opened);
+                shared.flush();
+            }
+        });
+        let on_message = Closure::<dyn FnMut(web_sys::MessageEvent)>::new({
+            let shared = shared.clone();
+            move |event: web_sys::MessageEvent| {
+                let data = event.data();
+                let message = if let Some(text) = data.as_string() {
+                    WsMessage::Text(text)
+                } else {
+                    WsMessage::Binary(js_sys::Uint8Array::new(&data).to_vec())
+                };
+                shared.emit(WsEvent::Message(message));
+            }
+        });
+        // The browser deliberately doesn't tell us what went wrong:
+        let on_error = Closure::<dyn FnMut()>::new({
+            let shared = shared.clone();
+            move || {
+                shared.emit(WsEvent::Error(
+                    "WebSocket error, check the developer console for details".to_owned(),
+                ));
+            }
+        });
+        let on_close = Closure::<dyn FnMut(web_sys::CloseEvent)>::new({
+            let shared = shared.clone();
+            move |event: web_sys::CloseEvent| {
+                shared.emit(WsEvent::Closed {
+                    code: event.code(),
+                    reason: event.reason(),
+                });
+            }
+        });
+
+        let socket = &shared.socket;
+        socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        socket.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+        socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+
+        Self {
+            shared,
+            _on_open: on_open,
+            _on_message: on_message,
+            _on_error: on_error,
+            _on_close: on_close,
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let socket = &self.shared.socket;
+        socket.set_onopen(None);
+        socket.set_onmessage(None);
+        socket.set_onerror(None);
+        socket.set_onclose(None);
+        socket.close().ok();
+    }
+}
+
+impl std::fmt::Debug for Connection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Connection")
+            .field("url", &self.shared.socket.url())
+            .finish_non_exhaustive()
+    }
+}
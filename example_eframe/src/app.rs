@@ -92,7 +92,7 @@ impl eframe::App for DemoApp {
                 }
                 Download::Done(response) => match response {
                     Err(err) => {
-                        ui.label(err);
+                        ui.label(err.to_string());
                     }
                     Ok(response) => {
                         response_ui(ui, response);
@@ -104,7 +104,7 @@ impl eframe::App for DemoApp {
 }
 
 fn on_fetch_part(
-    part: Result<ehttp::streaming::Part, String>,
+    part: ehttp::Result<ehttp::streaming::Part>,
     download_store: &mut Download,
 ) -> ControlFlow<()> {
     let part = match part {